@@ -13,7 +13,7 @@ interface WebhookRequest {
   request_id: string;
   method: string;
   path: string;
-  query_params: Record<string, string>;
+  query_params: Record<string, string[]>;
   headers: Record<string, string>;
   body: string;
   ip_address: string;
@@ -22,6 +22,15 @@ interface WebhookRequest {
   content_length: number;
 }
 
+// SSE event names for captured requests, one per content category
+const REQUEST_EVENTS = [
+  'request',
+  'request.json',
+  'request.xml',
+  'request.form',
+  'request.text',
+  'request.binary',
+];
 
 export default function SessionPage() {
   const params = useParams();
@@ -145,7 +154,8 @@ export default function SessionPage() {
         reconnectAttempts = 0; // Reset on successful connection
       };
 
-      eventSource.addEventListener('request', (event) => {
+      // Requests arrive as `request` or `request.<category>` (json, xml, ...)
+      const handleRequest = (event: MessageEvent) => {
         try {
           const newRequest: WebhookRequest = JSON.parse(event.data);
           setRequests((prev) => {
@@ -157,7 +167,10 @@ export default function SessionPage() {
         } catch (err) {
           console.error('Error parsing SSE request:', err);
         }
-      });
+      };
+      for (const eventName of REQUEST_EVENTS) {
+        eventSource.addEventListener(eventName, handleRequest);
+      }
 
       eventSource.addEventListener('ping', () => {
         // Connection is alive - update connection status
@@ -9,7 +9,7 @@ interface WebhookRequest {
   request_id: string;
   method: string;
   path: string;
-  query_params: Record<string, string>;
+  query_params: Record<string, string[]>;
   headers: Record<string, string>;
   body: string;
   ip_address: string;
@@ -214,9 +214,11 @@ export default function RequestDetails({ request, apiUrl }: RequestDetailsProps)
             </div>
             <pre className="p-4 bg-zinc-100 dark:bg-zinc-900 rounded border border-zinc-200 dark:border-zinc-800 overflow-x-auto text-sm font-mono text-black dark:text-zinc-50">
               {/* Show the query params as a key-value pairs */}
-              {Object.entries(request.query_params).map(([key, value]) => (
-                <div key={key}>{key}: {value}</div>
-              ))}
+              {Object.entries(request.query_params).flatMap(([key, values]) =>
+                values.map((value, index) => (
+                  <div key={`${key}-${index}`}>{key}: {value}</div>
+                ))
+              )}
             </pre>
           </div>
         )}
@@ -9,7 +9,7 @@ interface WebhookRequest {
   request_id: string;
   method: string;
   path: string;
-  query_params: Record<string, string>;
+  query_params: Record<string, string[]>;
   headers: Record<string, string>;
   body: string;
   ip_address: string;
@@ -8,7 +8,7 @@ export const constructCURL = ({
 }: {
     method: string;
     path: string;
-    query_params: Record<string, string>;
+    query_params: Record<string, string[]>;
     headers: Record<string, string>;
     body: string | object | null;
     apiUrl?: string;
@@ -18,8 +18,8 @@ export const constructCURL = ({
     
     // Add query parameters if any
     const searchParams = new URLSearchParams();
-    Object.entries(query_params).forEach(([key, value]) => {
-        if (key) searchParams.append(key, value);
+    Object.entries(query_params).forEach(([key, values]) => {
+        if (key) values.forEach((value) => searchParams.append(key, value));
     });
     
     const queryString = searchParams.toString();
@@ -0,0 +1,89 @@
+use crate::body;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::Error;
+
+/// Version of the JSON response shapes. Bumped on breaking changes to a
+/// response body, independently of the server's package version.
+pub const API_VERSION: &str = "1";
+
+/// Header advertising `API_VERSION` on JSON responses
+pub const API_VERSION_HEADER: HeaderName = HeaderName::from_static("x-echohook-api-version");
+
+/// Stamp `X-Echohook-Api-Version` onto every JSON (or NDJSON) response,
+/// errors included
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let mut response = next.call(req).await?;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| {
+            body::is_json_content_type(content_type)
+                || content_type.trim_start().starts_with("application/x-ndjson")
+        });
+    if is_json {
+        response
+            .headers_mut()
+            .insert(API_VERSION_HEADER, HeaderValue::from_static(API_VERSION));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    async fn created() -> HttpResponse {
+        HttpResponse::Created().json(serde_json::json!({"session_id": "abc"}))
+    }
+
+    async fn requests() -> HttpResponse {
+        HttpResponse::Ok().json(serde_json::json!({"requests": []}))
+    }
+
+    async fn missing() -> Result<HttpResponse, AppError> {
+        Err(AppError::SessionNotFound)
+    }
+
+    async fn badge() -> HttpResponse {
+        HttpResponse::Ok().content_type("image/svg+xml").body("<svg/>")
+    }
+
+    #[actix_web::test]
+    async fn json_and_error_responses_carry_the_version() {
+        let app = App::new()
+            .wrap(from_fn(middleware))
+            .route("/c", web::post().to(created))
+            .route("/r/abc", web::get().to(requests))
+            .route("/r/missing", web::get().to(missing))
+            .route("/r/abc/badge.svg", web::get().to(badge));
+        let app = init_service(app).await;
+
+        for req in [
+            TestRequest::post().uri("/c"),
+            TestRequest::get().uri("/r/abc"),
+            TestRequest::get().uri("/r/missing"),
+        ] {
+            let response = call_service(&app, req.to_request()).await;
+            let version = response.headers().get(API_VERSION_HEADER);
+            assert_eq!(version.unwrap(), API_VERSION, "{}", response.request().path());
+        }
+        let missing = call_service(&app, TestRequest::get().uri("/r/missing").to_request()).await;
+        assert_eq!(missing.status(), 404);
+
+        let req = TestRequest::get().uri("/r/abc/badge.svg").to_request();
+        let response = call_service(&app, req).await;
+        assert!(response.headers().get(API_VERSION_HEADER).is_none());
+    }
+}
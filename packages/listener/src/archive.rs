@@ -0,0 +1,245 @@
+use crate::config::{ArchiveBackend, ArchiveSettings};
+use crate::models::WebhookRequest;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Queued requests written per batch by the archive task
+const BATCH_SIZE: usize = 64;
+
+/// A serialized request waiting to be archived
+struct ArchiveEntry {
+    session_id: String,
+    /// Only needed for S3 object keys
+    #[cfg_attr(not(feature = "s3"), allow(dead_code))]
+    request_id: String,
+    json: String,
+}
+
+/// Handle to the background archive writer. Archiving never blocks ingest:
+/// requests are queued and dropped (with a warning) when the queue is full
+#[derive(Clone)]
+pub struct Archiver {
+    sender: Option<mpsc::Sender<ArchiveEntry>>,
+}
+
+impl Archiver {
+    /// Set up the configured sink and spawn its writer task
+    pub async fn start(settings: &ArchiveSettings) -> anyhow::Result<Self> {
+        let sink = match settings.backend {
+            ArchiveBackend::None => return Ok(Self { sender: None }),
+            ArchiveBackend::File => {
+                let dir = PathBuf::from(&settings.dir);
+                tokio::fs::create_dir_all(&dir).await?;
+                Sink::File(dir)
+            }
+            #[cfg(feature = "s3")]
+            ArchiveBackend::S3 => Sink::S3(s3::S3Sink::new(settings).await?),
+            #[cfg(not(feature = "s3"))]
+            ArchiveBackend::S3 => {
+                anyhow::bail!("ARCHIVE_BACKEND=s3 requires building with the `s3` feature")
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel(settings.queue_size.max(1));
+        tokio::spawn(run(sink, receiver));
+        Ok(Self {
+            sender: Some(sender),
+        })
+    }
+
+    /// Queue a captured request for archival
+    pub fn archive(&self, session_id: &str, request: &WebhookRequest) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        let json = match serde_json::to_string(request) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!(
+                    request_id = %request.request_id,
+                    error = %e,
+                    "Failed to serialize request for archive"
+                );
+                return;
+            }
+        };
+
+        let entry = ArchiveEntry {
+            session_id: session_id.to_string(),
+            request_id: request.request_id.clone(),
+            json,
+        };
+        if sender.try_send(entry).is_err() {
+            warn!(
+                session_id = %session_id,
+                request_id = %request.request_id,
+                "Archive queue full, dropping request"
+            );
+        }
+    }
+}
+
+enum Sink {
+    /// Directory of `{session_id}.ndjson` files
+    File(PathBuf),
+    #[cfg(feature = "s3")]
+    S3(s3::S3Sink),
+}
+
+/// Drain the queue in batches until every `Archiver` handle is dropped
+async fn run(sink: Sink, mut receiver: mpsc::Receiver<ArchiveEntry>) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while receiver.recv_many(&mut batch, BATCH_SIZE).await > 0 {
+        match &sink {
+            Sink::File(dir) => write_files(dir, &batch).await,
+            #[cfg(feature = "s3")]
+            Sink::S3(sink) => sink.upload(&batch).await,
+        }
+        batch.clear();
+    }
+}
+
+/// Append a batch to the per-session files, one write per session
+async fn write_files(dir: &std::path::Path, batch: &[ArchiveEntry]) {
+    let mut by_session: HashMap<&str, String> = HashMap::new();
+    for entry in batch {
+        let lines = by_session.entry(&entry.session_id).or_default();
+        lines.push_str(&entry.json);
+        lines.push('\n');
+    }
+
+    for (session_id, lines) in by_session {
+        let path = dir.join(format!("{}.ndjson", session_id));
+        let result = async {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await?;
+            file.write_all(lines.as_bytes()).await?;
+            file.flush().await
+        }
+        .await;
+
+        match result {
+            Ok(()) => debug!(path = %path.display(), "Archived requests"),
+            Err(e) => warn!(path = %path.display(), error = %e, "Failed to archive requests"),
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+mod s3 {
+    use super::ArchiveEntry;
+    use crate::config::ArchiveSettings;
+    use aws_sdk_s3::primitives::ByteStream;
+    use aws_sdk_s3::Client;
+    use tracing::{debug, warn};
+
+    /// Uploads each request as `{prefix}{session_id}/{request_id}.json`;
+    /// credentials and region come from the standard AWS environment
+    pub struct S3Sink {
+        client: Client,
+        bucket: String,
+        prefix: String,
+    }
+
+    impl S3Sink {
+        pub async fn new(settings: &ArchiveSettings) -> anyhow::Result<Self> {
+            let Some(bucket) = settings.s3_bucket.clone() else {
+                anyhow::bail!("ARCHIVE_S3_BUCKET is required for the s3 backend");
+            };
+            let config = aws_config::load_from_env().await;
+
+            Ok(Self {
+                client: Client::new(&config),
+                bucket,
+                prefix: settings.s3_prefix.clone(),
+            })
+        }
+
+        pub async fn upload(&self, batch: &[ArchiveEntry]) {
+            for entry in batch {
+                let key = format!("{}{}/{}.json", self.prefix, entry.session_id, entry.request_id);
+                let result = self
+                    .client
+                    .put_object()
+                    .bucket(&self.bucket)
+                    .key(&key)
+                    .content_type("application/json")
+                    .body(ByteStream::from(entry.json.clone().into_bytes()))
+                    .send()
+                    .await;
+
+                match result {
+                    Ok(_) => debug!(key = %key, "Archived request to S3"),
+                    Err(e) => warn!(key = %key, error = %e, "Failed to archive request to S3"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn request(request_id: &str) -> WebhookRequest {
+        WebhookRequest {
+            request_id: request_id.to_string(),
+            method: "POST".to_string(),
+            body: "{\"n\":1}".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Wait for the background writer to put `lines` lines into `path`
+    async fn read_lines(path: &std::path::Path, lines: usize) -> String {
+        let mut contents = String::new();
+        for _ in 0..100 {
+            contents = tokio::fs::read_to_string(path).await.unwrap_or_default();
+            if contents.lines().count() >= lines {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        contents
+    }
+
+    #[tokio::test]
+    async fn file_backend_appends_requests_to_the_session_file() {
+        let dir = std::env::temp_dir().join(format!("echohook-archive-{}", uuid::Uuid::now_v7()));
+        let settings = ArchiveSettings {
+            backend: ArchiveBackend::File,
+            dir: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let archiver = Archiver::start(&settings).await.unwrap();
+        archiver.archive("abc", &request("first"));
+        archiver.archive("abc", &request("second"));
+        archiver.archive("other", &request("third"));
+        drop(archiver);
+
+        let ids = |contents: String| -> Vec<String> {
+            contents
+                .lines()
+                .map(|line| serde_json::from_str::<WebhookRequest>(line).unwrap().request_id)
+                .collect()
+        };
+        assert_eq!(ids(read_lines(&dir.join("abc.ndjson"), 2).await), ["first", "second"]);
+        assert_eq!(ids(read_lines(&dir.join("other.ndjson"), 1).await), ["third"]);
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn no_backend_archives_nothing() {
+        let archiver = Archiver::start(&ArchiveSettings::default()).await.unwrap();
+        assert!(archiver.sender.is_none());
+        archiver.archive("abc", &request("first"));
+    }
+}
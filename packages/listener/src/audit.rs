@@ -0,0 +1,97 @@
+use serde::Serialize;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Entries buffered for the audit writer before new ones are dropped
+const QUEUE_SIZE: usize = 4096;
+
+/// Queued entries written (and flushed) per batch by the audit task
+const BATCH_SIZE: usize = 64;
+
+/// One ingest attempt as recorded in the audit log. Attempts are logged
+/// whether or not they were captured, so rejected requests leave a trace too.
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    /// When the request arrived
+    pub timestamp: String,
+    pub session_id: String,
+    /// Set once the request was captured (or matched as a duplicate)
+    pub request_id: Option<String>,
+    pub ip: String,
+    pub method: String,
+    pub path: String,
+    /// Body size on the wire (0 when rejected before the body was read)
+    pub content_length: usize,
+    /// Status returned to the sender
+    pub status: u16,
+    /// `captured`, `duplicate`, `paused`, `preflight` or the error code
+    pub outcome: &'static str,
+}
+
+/// Handle to the background audit log writer. Entries are appended as JSON
+/// lines to `AUDIT_LOG_PATH` and never expire; writing never blocks ingest.
+#[derive(Clone)]
+pub struct AuditLog {
+    sender: Option<mpsc::Sender<String>>,
+}
+
+impl AuditLog {
+    /// Open the log for appending and spawn its writer task
+    pub async fn start(path: Option<&str>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self { sender: None });
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(path).await?;
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(run(BufWriter::new(file), receiver));
+        Ok(Self {
+            sender: Some(sender),
+        })
+    }
+
+    /// Queue an entry for the audit log
+    pub fn record(&self, entry: &AuditEntry) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!(session_id = %entry.session_id, error = %e, "Failed to serialize audit entry");
+                return;
+            }
+        };
+
+        if sender.try_send(line).is_err() {
+            warn!(
+                session_id = %entry.session_id,
+                request_id = ?entry.request_id,
+                "Audit log queue full, dropping entry"
+            );
+        }
+    }
+}
+
+/// Append queued entries in batches, flushing after each one, until every
+/// `AuditLog` handle is dropped
+async fn run(mut writer: BufWriter<File>, mut receiver: mpsc::Receiver<String>) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while receiver.recv_many(&mut batch, BATCH_SIZE).await > 0 {
+        let result = async {
+            for line in &batch {
+                writer.write_all(line.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+            }
+            writer.flush().await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!(error = %e, entries = batch.len(), "Failed to write audit log");
+        }
+        batch.clear();
+    }
+}
@@ -0,0 +1,103 @@
+use crate::negotiate::escape_xml;
+
+/// Default left-hand text of the request-count badge
+pub const DEFAULT_LABEL: &str = "requests";
+
+/// Longest label accepted via `?label=`
+pub const MAX_LABEL_CHARS: usize = 32;
+
+/// Flat shields.io-style badge; `{label_width}`, `{value_width}`, `{width}`,
+/// `{label_x}`, `{value_x}`, `{label}` and `{value}` are substituted
+const TEMPLATE: &str = concat!(
+    r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" "##,
+    r##"aria-label="{label}: {value}"><title>{label}: {value}</title>"##,
+    r##"<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" "##,
+    r##"stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>"##,
+    r##"<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>"##,
+    r##"<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/>"##,
+    r##"<rect x="{label_width}" width="{value_width}" height="20" fill="#4c1"/>"##,
+    r##"<rect width="{width}" height="20" fill="url(#s)"/></g>"##,
+    r##"<g fill="#fff" text-anchor="middle" "##,
+    r##"font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">"##,
+    r##"<text x="{label_x}" y="14">{label}</text><text x="{value_x}" y="14">{value}</text>"##,
+    r##"</g></svg>"##,
+);
+
+/// Approximate rendered width of a string at Verdana 11px
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+/// Render a badge showing `value` next to `label`
+pub fn render(label: &str, value: &str) -> String {
+    let label_width = text_width(label);
+    let value_width = text_width(value);
+    TEMPLATE
+        .replace("{label_width}", &label_width.to_string())
+        .replace("{value_width}", &value_width.to_string())
+        .replace("{width}", &(label_width + value_width).to_string())
+        .replace("{label_x}", &(label_width / 2).to_string())
+        .replace("{value_x}", &(label_width + value_width / 2).to_string())
+        // The value first, so a label containing `{value}` is kept literally
+        .replace("{value}", &escape_xml(value))
+        .replace("{label}", &escape_xml(label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xmlparser::{ElementEnd, Token, Tokenizer};
+
+    /// Parse `svg` as XML, checking that every element is closed, and return
+    /// the root element name with the text content of the document
+    fn parse(svg: &str) -> (String, Vec<String>) {
+        let mut open: Vec<String> = Vec::new();
+        let mut root = None;
+        let mut texts = Vec::new();
+        for token in Tokenizer::from(svg) {
+            match token.expect("well-formed XML") {
+                Token::ElementStart { local, .. } => {
+                    root.get_or_insert_with(|| local.to_string());
+                    open.push(local.to_string());
+                }
+                Token::ElementEnd { end: ElementEnd::Close(_, local), .. } => {
+                    assert_eq!(open.pop().as_deref(), Some(local.as_str()));
+                }
+                Token::ElementEnd { end: ElementEnd::Empty, .. } => {
+                    open.pop();
+                }
+                Token::Text { text } => texts.push(text.to_string()),
+                _ => {}
+            }
+        }
+        assert!(open.is_empty(), "unclosed elements: {:?}", open);
+        (root.unwrap(), texts)
+    }
+
+    #[test]
+    fn badge_is_an_svg_showing_the_count() {
+        let svg = render(DEFAULT_LABEL, "42");
+        let (root, texts) = parse(&svg);
+        assert_eq!(root, "svg");
+        assert!(texts.contains(&"42".to_string()));
+        assert!(texts.contains(&"requests: 42".to_string()));
+        assert!(svg.contains(r#"aria-label="requests: 42""#));
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        let label = r#"<b>"a" & 'b'</b>"#;
+        let svg = render(label, "7");
+        let (_, texts) = parse(&svg);
+        assert!(!svg.contains("<b>"));
+        let escaped = "&lt;b&gt;&quot;a&quot; &amp; &apos;b&apos;&lt;/b&gt;";
+        assert!(texts.contains(&escaped.to_string()));
+    }
+
+    #[test]
+    fn labels_cannot_inject_placeholders() {
+        let svg = render("{value}{width}", "7");
+        let (_, texts) = parse(&svg);
+        assert!(texts.contains(&"{value}{width}".to_string()));
+    }
+}
@@ -0,0 +1,407 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{BodyEncoding, MultipartPart};
+use actix_web::web::{self, Bytes, BytesMut};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use brotli::Decompressor;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+/// Read a raw request payload into memory, failing once it exceeds `limit`.
+/// `applied` names the limit in the resulting error.
+///
+/// The body is consumed chunk by chunk and the limit is checked before each
+/// chunk is kept, so an oversized upload is abandoned as soon as it crosses
+/// the limit. A declared `Content-Length` above the limit is rejected before
+/// anything is read, and one within it sizes the buffer up front.
+///
+/// Unlike the `Bytes` extractor this keeps the body exactly as sent on the
+/// wire (no automatic Content-Encoding decompression).
+pub async fn read_payload(
+    mut payload: web::Payload,
+    declared_length: Option<usize>,
+    limit: usize,
+    applied: &str,
+) -> AppResult<Bytes> {
+    if let Some(size) = declared_length.filter(|&size| size > limit) {
+        return Err(AppError::PayloadTooLarge {
+            size,
+            limit,
+            applied: applied.to_string(),
+        });
+    }
+
+    let mut buffer = BytesMut::with_capacity(declared_length.unwrap_or(0));
+
+    while let Some(chunk) = payload.next().await {
+        let chunk =
+            chunk.map_err(|e| AppError::BadRequest(format!("Failed to read request body: {}", e)))?;
+        if buffer.len() + chunk.len() > limit {
+            return Err(AppError::PayloadTooLarge {
+                size: buffer.len() + chunk.len(),
+                limit,
+                applied: applied.to_string(),
+            });
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+
+    Ok(buffer.freeze())
+}
+
+/// Decode a body according to its `Content-Encoding` header.
+///
+/// Encodings are undone in reverse order of application (e.g. `gzip, br`).
+/// `identity` is skipped; unknown encodings are an error. The decoded output
+/// is capped at `max_size` bytes to guard against decompression bombs.
+pub fn decode_content_encoding(
+    content_encoding: &str,
+    body: &[u8],
+    max_size: usize,
+) -> io::Result<Vec<u8>> {
+    let mut data = body.to_vec();
+
+    for encoding in content_encoding.rsplit(',').map(str::trim) {
+        data = match encoding.to_ascii_lowercase().as_str() {
+            "" | "identity" => continue,
+            "gzip" | "x-gzip" => read_capped(GzDecoder::new(data.as_slice()), max_size)?,
+            "deflate" => read_capped(ZlibDecoder::new(data.as_slice()), max_size)?,
+            "br" => read_capped(Decompressor::new(data.as_slice(), 4096), max_size)?,
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported content encoding: {}", other),
+                ))
+            }
+        };
+    }
+
+    Ok(data)
+}
+
+/// Read a decoder to the end, failing if it produces more than `max_size` bytes
+fn read_capped(reader: impl Read, max_size: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    reader.take(max_size as u64 + 1).read_to_end(&mut out)?;
+    if out.len() > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("decoded body exceeds {} bytes", max_size),
+        ));
+    }
+    Ok(out)
+}
+
+/// Whether a Content-Type denotes JSON (`application/json` or a `+json` suffix)
+pub fn is_json_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    mime == "application/json" || mime.ends_with("+json")
+}
+
+/// Parse a JSON body when the Content-Type declares JSON; `None` otherwise or if malformed
+pub fn parse_json_body(content_type: &str, body: &str) -> Option<serde_json::Value> {
+    if !is_json_content_type(content_type) {
+        return None;
+    }
+    serde_json::from_str(body).ok()
+}
+
+/// Guess the media type of a body sent without a `Content-Type`: a leading
+/// `{` or `[` is JSON, `<` is XML and `key=value&...` pairs are form data.
+/// Returns `None` for empty bodies and anything else.
+pub fn sniff_content_type(body: &[u8]) -> Option<&'static str> {
+    // Skip a UTF-8 byte order mark and surrounding whitespace
+    let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body).trim_ascii();
+    match body.first()? {
+        b'{' | b'[' => Some("application/json"),
+        b'<' => Some("application/xml"),
+        _ if is_form_encoded(body) => Some("application/x-www-form-urlencoded"),
+        _ => None,
+    }
+}
+
+/// Whether a body looks like `key=value` pairs joined by `&`
+fn is_form_encoded(body: &[u8]) -> bool {
+    body.split(|&b| b == b'&').all(|pair| {
+        pair.iter().position(|&b| b == b'=').is_some_and(|eq| eq > 0)
+            && pair.iter().all(|b| b.is_ascii_graphic())
+    })
+}
+
+/// Decode an `application/x-www-form-urlencoded` body into its fields,
+/// keeping every value of repeated keys in order
+pub fn parse_form_body(content_type: &str, body: &[u8]) -> Option<HashMap<String, Vec<String>>> {
+    let media_type = content_type.split(';').next().unwrap_or("").trim();
+    if !media_type.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+        return None;
+    }
+
+    let mut fields: HashMap<String, Vec<String>> = HashMap::new();
+    for (key, value) in form_urlencoded::parse(body) {
+        fields.entry(key.into_owned()).or_default().push(value.into_owned());
+    }
+    Some(fields)
+}
+
+/// Validate a parsed JSON body against a session's JSON Schema, returning
+/// whether it conforms and the validation errors (`/path: message`).
+/// A body that is not JSON never conforms.
+pub fn validate_body_schema(
+    schema: &serde_json::Value,
+    body_json: Option<&serde_json::Value>,
+) -> Result<(bool, Vec<String>), String> {
+    let validator = jsonschema::validator_for(schema).map_err(|e| e.to_string())?;
+    let Some(body_json) = body_json else {
+        return Ok((false, vec!["Body is not valid JSON".to_string()]));
+    };
+
+    let errors: Vec<String> = validator
+        .iter_errors(body_json)
+        .map(|error| {
+            let path = error.instance_path().to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{}: {}", path, error)
+            }
+        })
+        .collect();
+    Ok((errors.is_empty(), errors))
+}
+
+/// Apply a session's JSON Patch to a copy of the parsed body
+pub fn apply_transform(
+    patch: &json_patch::Patch,
+    body_json: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let mut transformed = body_json.clone();
+    json_patch::patch(&mut transformed, patch).map_err(|e| e.to_string())?;
+    Ok(transformed)
+}
+
+/// Cut a body to at most `limit` bytes. When the cut lands inside a UTF-8
+/// character of a text body, back off to the character boundary so the
+/// prefix stays valid text.
+pub fn truncate_body(body: &[u8], limit: usize) -> &[u8] {
+    if body.len() <= limit {
+        return body;
+    }
+
+    let prefix = &body[..limit];
+    match std::str::from_utf8(prefix) {
+        Err(e) if e.error_len().is_none() => &prefix[..e.valid_up_to()],
+        _ => prefix,
+    }
+}
+
+/// Bytes of each multipart part kept as its preview
+const MULTIPART_PREVIEW_BYTES: usize = 1024;
+
+/// Split a `multipart/form-data` body into its parts. Returns `None` when the
+/// Content-Type is not multipart or the body does not parse.
+pub async fn parse_multipart(content_type: &str, body: &[u8]) -> Option<Vec<MultipartPart>> {
+    let media_type = content_type.split(';').next().unwrap_or("").trim();
+    if !media_type.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    let boundary = multer::parse_boundary(content_type).ok()?;
+
+    let body = Bytes::copy_from_slice(body);
+    let stream = futures::stream::once(async move { Ok::<_, io::Error>(body) });
+    let mut multipart = multer::Multipart::new(stream, boundary);
+
+    let mut parts = Vec::new();
+    while let Some(field) = multipart.next_field().await.ok()? {
+        let name = field.name().map(String::from);
+        let filename = field.file_name().map(String::from);
+        let content_type = field.content_type().map(ToString::to_string);
+        let data = field.bytes().await.ok()?;
+
+        let prefix = truncate_body(&data, MULTIPART_PREVIEW_BYTES);
+        let (preview, preview_encoding) = match std::str::from_utf8(prefix) {
+            Ok(text) => (text.to_string(), BodyEncoding::Utf8),
+            Err(_) => (BASE64.encode(prefix), BodyEncoding::Base64),
+        };
+        parts.push(MultipartPart {
+            name,
+            filename,
+            content_type,
+            size: data.len(),
+            preview,
+            preview_encoding,
+            preview_truncated: prefix.len() < data.len(),
+        });
+    }
+    Some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORM: &str = "application/x-www-form-urlencoded";
+
+    /// A chunked payload of `chunks` 4-byte chunks, counting the chunks read
+    async fn chunked(
+        chunks: usize,
+    ) -> (web::Payload, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use actix_web::FromRequest;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let read = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = read.clone();
+        let stream = futures::stream::iter((0..chunks).map(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, actix_web::error::PayloadError>(Bytes::from_static(b"abcd"))
+        }));
+        let mut payload = actix_web::dev::Payload::from(
+            Box::pin(stream) as std::pin::Pin<Box<dyn futures::Stream<Item = _>>>
+        );
+        let req = actix_web::test::TestRequest::post().to_http_request();
+        let payload = web::Payload::from_request(&req, &mut payload).await.unwrap();
+        (payload, read)
+    }
+
+    #[actix_web::test]
+    async fn chunked_bodies_within_the_limit_are_read_whole() {
+        let (payload, _) = chunked(3).await;
+        let body = read_payload(payload, None, 12, "default").await.unwrap();
+        assert_eq!(body, "abcdabcdabcd");
+    }
+
+    #[actix_web::test]
+    async fn chunked_bodies_over_the_limit_stop_being_read() {
+        use std::sync::atomic::Ordering;
+
+        let (payload, read) = chunked(1000).await;
+        let error = read_payload(payload, None, 10, "text/plain").await.unwrap_err();
+
+        match error {
+            AppError::PayloadTooLarge {
+                size,
+                limit,
+                applied,
+            } => assert_eq!((size, limit, applied.as_str()), (12, 10, "text/plain")),
+            other => panic!("unexpected error: {}", other),
+        }
+        // The third chunk crossed the limit; the rest were never pulled
+        assert_eq!(read.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_web::test]
+    async fn an_oversized_content_length_is_rejected_before_reading() {
+        use std::sync::atomic::Ordering;
+
+        let (payload, read) = chunked(1000).await;
+        let error = read_payload(payload, Some(4000), 10, "default").await.unwrap_err();
+
+        assert!(matches!(error, AppError::PayloadTooLarge { size: 4000, .. }));
+        assert_eq!(read.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn json_bodies_are_sniffed() {
+        assert_eq!(sniff_content_type(br#"{"a": 1}"#), Some("application/json"));
+        assert_eq!(sniff_content_type(b"  \n[1, 2]"), Some("application/json"));
+        assert_eq!(sniff_content_type(b"\xEF\xBB\xBF{}"), Some("application/json"));
+    }
+
+    #[test]
+    fn xml_bodies_are_sniffed() {
+        let xml = br#"<?xml version="1.0"?><event/>"#;
+        assert_eq!(sniff_content_type(xml), Some("application/xml"));
+        assert_eq!(sniff_content_type(b"\r\n<event></event>"), Some("application/xml"));
+    }
+
+    #[test]
+    fn form_bodies_are_sniffed() {
+        assert_eq!(sniff_content_type(b"a=1&b=2"), Some(FORM));
+        assert_eq!(sniff_content_type(b"name=Ada+Lovelace&empty="), Some(FORM));
+        assert_eq!(sniff_content_type(b"a=1&b"), None);
+        assert_eq!(sniff_content_type(b"=1"), None);
+        assert_eq!(sniff_content_type(b"hello world=1"), None);
+    }
+
+    #[test]
+    fn empty_and_unknown_bodies_are_not_sniffed() {
+        assert_eq!(sniff_content_type(b""), None);
+        assert_eq!(sniff_content_type(b" \r\n\t"), None);
+        assert_eq!(sniff_content_type(b"plain text"), None);
+        assert_eq!(sniff_content_type(&[0x89, b'P', b'N', b'G']), None);
+    }
+
+    #[test]
+    fn form_bodies_keep_duplicate_keys_and_decode_values() {
+        let body = b"tag=a&name=Ada+Lovelace&tag=b&note=50%25+off%26more&caf%C3%A9=%E2%9C%93";
+        let fields = parse_form_body(FORM, body).unwrap();
+        assert_eq!(fields.len(), 4);
+        assert_eq!(fields["tag"], ["a", "b"]);
+        assert_eq!(fields["name"], ["Ada Lovelace"]);
+        assert_eq!(fields["note"], ["50% off&more"]);
+        assert_eq!(fields["café"], ["✓"]);
+    }
+
+    #[test]
+    fn form_parsing_follows_the_content_type() {
+        let charset = "Application/X-WWW-Form-Urlencoded; charset=utf-8";
+        assert_eq!(parse_form_body(charset, b"a=1").unwrap()["a"], ["1"]);
+        assert!(parse_form_body(FORM, b"").unwrap().is_empty());
+        assert!(parse_form_body("application/json", b"a=1").is_none());
+        assert!(parse_form_body("", b"a=1").is_none());
+    }
+
+    const MULTIPART: &str = "multipart/form-data; boundary=XyZ";
+
+    #[actix_web::test]
+    async fn multipart_forms_are_split_into_parts() {
+        let body = b"--XyZ\r\n\
+            Content-Disposition: form-data; name=\"note\"\r\n\r\n\
+            hello\r\n\
+            --XyZ\r\n\
+            Content-Disposition: form-data; name=\"upload\"; filename=\"a.bin\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n\
+            \x00\xFF\x01\r\n\
+            --XyZ--\r\n";
+
+        let parts = parse_multipart(MULTIPART, body).await.unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name.as_deref(), Some("note"));
+        assert_eq!(parts[0].filename, None);
+        assert_eq!((parts[0].size, parts[0].preview.as_str()), (5, "hello"));
+        assert_eq!(parts[0].preview_encoding, BodyEncoding::Utf8);
+        assert_eq!(parts[1].name.as_deref(), Some("upload"));
+        assert_eq!(parts[1].filename.as_deref(), Some("a.bin"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("application/octet-stream"));
+        assert_eq!((parts[1].size, parts[1].preview.as_str()), (3, "AP8B"));
+        assert_eq!(parts[1].preview_encoding, BodyEncoding::Base64);
+        assert!(!parts[1].preview_truncated);
+    }
+
+    #[actix_web::test]
+    async fn large_multipart_parts_keep_only_a_preview() {
+        let body = format!(
+            "--XyZ\r\nContent-Disposition: form-data; name=\"big\"\r\n\r\n{}\r\n--XyZ--\r\n",
+            "a".repeat(MULTIPART_PREVIEW_BYTES + 1)
+        );
+
+        let parts = parse_multipart(MULTIPART, body.as_bytes()).await.unwrap();
+
+        assert_eq!(parts[0].size, MULTIPART_PREVIEW_BYTES + 1);
+        assert_eq!(parts[0].preview.len(), MULTIPART_PREVIEW_BYTES);
+        assert!(parts[0].preview_truncated);
+    }
+
+    #[actix_web::test]
+    async fn other_bodies_are_not_parsed_as_multipart() {
+        assert!(parse_multipart("application/json", b"{}").await.is_none());
+        assert!(parse_multipart("multipart/form-data", b"--XyZ--").await.is_none());
+        assert!(parse_multipart(MULTIPART, b"not a form").await.is_none());
+    }
+}
@@ -1,76 +1,729 @@
+use crate::session_id;
+use ::config::{Config, File, FileFormat};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
+use std::path::Path;
+use std::str::FromStr;
 
 /// Application configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub server: ServerSettings,
     pub redis: RedisSettings,
     pub session: SessionSettings,
+    pub sse: SseSettings,
+    pub rate_limit: RateLimitSettings,
+    pub redact: RedactSettings,
+    pub archive: ArchiveSettings,
+    pub forward: ForwardSettings,
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct ServerSettings {
     pub host: String,
     pub port: u16,
     pub listen_url: String,
     pub max_body_size: usize,
+    /// Per-content-type overrides of `max_body_size`, keyed by media type
+    pub max_body_size_by_type: HashMap<String, usize>,
+    /// Store only this many bytes of larger bodies, marking them truncated (0 = off)
+    pub body_truncate_bytes: usize,
     pub cors_allowed_origins: String,
+    /// How often the `config:cors_origins` Redis override is reloaded (0 = never)
+    pub cors_refresh_seconds: u64,
+    /// File that every ingest attempt is appended to as a JSON line
+    pub audit_log_path: Option<String>,
+    /// Base domain whose subdomains name sessions: requests to
+    /// `{session_id}.{domain}` are ingested whatever their path
+    pub subdomain_routing_domain: Option<String>,
+    /// Query parameter whose values tag captured requests (empty = off)
+    pub tag_query_param: String,
+    /// Header whose comma-separated values tag captured requests (empty = off)
+    pub tag_header: String,
+    pub receipt_signing_key: Option<String>,
+    /// When set, `POST /c` requires `Authorization: Bearer <api_key>`
+    pub api_key: Option<String>,
+    /// Allow replay, forwarding and notification targets on localhost and
+    /// private/metadata addresses
+    pub allow_private_targets: bool,
+    /// Compress responses according to `Accept-Encoding` (SSE is never compressed)
+    pub enable_compression: bool,
+    /// Headers stored per captured request (0 = unlimited)
+    pub max_headers: usize,
+    /// Bytes stored per header value (0 = unlimited)
+    pub max_header_value_bytes: usize,
+    /// What to do with requests over `max_headers` / `max_header_value_bytes`
+    pub header_limit_strategy: HeaderLimitStrategy,
+}
+
+/// Handling of captured requests that exceed the header limits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeaderLimitStrategy {
+    /// Store the headers within the limits and flag the request `headers_truncated`
+    #[default]
+    Truncate,
+    /// Reject the request with `431`
+    Reject,
+}
+
+impl FromStr for HeaderLimitStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "reject" => Ok(Self::Reject),
+            other => Err(format!("unknown header limit strategy: {}", other)),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct RedisSettings {
+    /// One URL, or a comma-separated list to shard sessions across nodes
     pub url: String,
     pub pool_size: usize,
+    /// Initial delay between reconnection attempts, doubled on each failure
+    pub reconnect_min_delay_ms: u64,
+    /// Upper bound for the reconnection backoff delay
+    pub reconnect_max_delay_ms: u64,
+    /// Reconnection attempts before giving up until the next command
+    pub reconnect_retries: usize,
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct SessionSettings {
     pub ttl_seconds: u64,
+    /// Lifetime of individual captured requests (0 = same as the session)
+    pub request_ttl_seconds: u64,
+    /// Lifetime cap on stored requests per session (0 = unlimited)
     pub max_requests_per_session: usize,
+    /// Seconds before expiry at which SSE clients get a `session_expiring` event
+    pub expiry_warning_seconds: u64,
+    /// Upper bound for a custom TTL requested when extending a session
+    pub max_ttl_seconds: u64,
+    /// Global cap on concurrently active sessions (0 = unlimited)
+    pub max_sessions: usize,
+    /// How new session IDs are generated
+    pub id_format: SessionIdFormat,
+    /// Length of `short` session IDs
+    pub short_id_length: usize,
+    /// Characters `short` session IDs are drawn from
+    pub short_id_alphabet: String,
+    /// Create unknown sessions (with default options) on their first ingest
+    /// instead of rejecting them with 404
+    pub auto_create_sessions: bool,
+    /// Session that captures requests sent to unknown or expired sessions
+    /// (created on demand with the default TTL)
+    pub dead_letter_session: Option<String>,
+}
+
+/// Format of generated session IDs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionIdFormat {
+    /// UUIDv7 (time-ordered)
+    #[default]
+    Uuid,
+    /// Random string of `short_id_length` characters from `short_id_alphabet`
+    Short,
+}
+
+impl FromStr for SessionIdFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "uuid" => Ok(Self::Uuid),
+            "short" => Ok(Self::Short),
+            other => Err(format!("unknown session ID format: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SseSettings {
+    /// How often channels without subscribers are swept from memory
+    pub sweep_interval_seconds: u64,
+    /// Messages buffered per session broadcast channel before slow clients lag
+    pub channel_capacity: usize,
+    /// What to do with an SSE client that fell behind the channel
+    pub lag_strategy: LagStrategy,
+    /// Streams are ended with a `reconnect` event after this many seconds so
+    /// abandoned connections do not live forever (0 = unlimited)
+    pub max_lifetime_seconds: u64,
+    /// Relay captured requests between instances over Redis pub/sub so
+    /// stream clients receive them whichever instance captured them
+    pub pubsub: bool,
+    /// Live stream clients (SSE, WebSocket and NDJSON) allowed per session on
+    /// this instance; further connections get 429 (0 = unlimited)
+    pub max_subscribers_per_session: usize,
+}
+
+/// Handling of SSE clients that miss messages because they lagged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LagStrategy {
+    /// Skip the missed messages and keep streaming
+    #[default]
+    Drop,
+    /// Send an `error` event and end the stream so the client reconnects
+    /// and replays via `Last-Event-ID`
+    Close,
+}
+
+impl FromStr for LagStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "drop" => Ok(Self::Drop),
+            "close" => Ok(Self::Close),
+            other => Err(format!("unknown SSE lag strategy: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    /// Ingest requests allowed per client IP per window (0 = unlimited)
+    pub per_ip: u64,
+    pub window_seconds: u64,
+    /// Ingest requests allowed per session per window (0 = unlimited)
+    pub per_session: u64,
+    pub session_window_seconds: u64,
+}
+
+/// Values replaced before captured requests are stored
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RedactSettings {
+    /// Header names, matched case-insensitively
+    pub headers: Vec<String>,
+    /// Top-level JSON body field names, matched exactly
+    pub json_fields: Vec<String>,
+}
+
+/// Permanent copies of captured requests, kept beyond their Redis TTL
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ArchiveSettings {
+    pub backend: ArchiveBackend,
+    /// Directory holding one NDJSON file per session (`file` backend)
+    pub dir: String,
+    /// Bucket receiving one object per request (`s3` backend)
+    pub s3_bucket: Option<String>,
+    /// Key prefix for archived objects (`s3` backend)
+    pub s3_prefix: String,
+    /// Requests buffered for the archive writer before new ones are dropped
+    pub queue_size: usize,
+}
+
+/// Redelivery of requests whose forwarding failed
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ForwardSettings {
+    /// Attempts per request, the first included (1 = no retries)
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each one after
+    pub retry_base_delay_seconds: u64,
+    /// Upper bound for the delay between retries
+    pub retry_max_delay_seconds: u64,
+}
+
+/// Where captured requests are archived
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveBackend {
+    /// Archival disabled
+    #[default]
+    None,
+    /// Append to local newline-delimited JSON files
+    File,
+    /// Upload to S3 (requires the `s3` build feature)
+    S3,
+}
+
+impl FromStr for ArchiveBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "file" => Ok(Self::File),
+            "s3" => Ok(Self::S3),
+            other => Err(format!("unknown archive backend: {}", other)),
+        }
+    }
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            // Derived from `port` at load time when left empty
+            listen_url: String::new(),
+            max_body_size: 10_485_760, // 10 MB
+            max_body_size_by_type: HashMap::new(),
+            body_truncate_bytes: 0,
+            cors_allowed_origins: "*".to_string(),
+            cors_refresh_seconds: 30,
+            audit_log_path: None,
+            subdomain_routing_domain: None,
+            tag_query_param: "tag".to_string(),
+            tag_header: "x-echohook-tag".to_string(),
+            receipt_signing_key: None,
+            api_key: None,
+            allow_private_targets: false,
+            enable_compression: true,
+            max_headers: 0,
+            max_header_value_bytes: 0,
+            header_limit_strategy: HeaderLimitStrategy::Truncate,
+        }
+    }
+}
+
+impl Default for RedisSettings {
+    fn default() -> Self {
+        Self {
+            url: "redis://localhost:6379".to_string(),
+            pool_size: 10,
+            reconnect_min_delay_ms: 100,
+            reconnect_max_delay_ms: 5000,
+            reconnect_retries: 6,
+        }
+    }
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            per_ip: 0,
+            window_seconds: 60,
+            per_session: 0,
+            session_window_seconds: 60,
+        }
+    }
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self {
+            backend: ArchiveBackend::None,
+            dir: "./archive".to_string(),
+            s3_bucket: None,
+            s3_prefix: "echohook/".to_string(),
+            queue_size: 1024,
+        }
+    }
+}
+
+impl Default for ForwardSettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            retry_base_delay_seconds: 5,
+            retry_max_delay_seconds: 300, // 5 minutes
+        }
+    }
+}
+
+impl ServerSettings {
+    /// Body size limit for a request's `Content-Type`, along with the name of
+    /// the limit applied (the media type, or `default` for `max_body_size`)
+    pub fn body_size_limit(&self, content_type: &str) -> (usize, &str) {
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+
+        self.max_body_size_by_type
+            .get_key_value(&media_type)
+            .map(|(media_type, limit)| (*limit, media_type.as_str()))
+            .unwrap_or((self.max_body_size, "default"))
+    }
+}
+
+impl SessionSettings {
+    /// Effective TTL for captured requests; never outlives the session TTL
+    pub fn request_ttl(&self) -> u64 {
+        match self.request_ttl_seconds {
+            0 => self.ttl_seconds,
+            ttl => ttl.min(self.ttl_seconds),
+        }
+    }
+}
+
+impl RedisSettings {
+    /// Individual node URLs, in shard order
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.url.split(',').map(str::trim).filter(|url| !url.is_empty())
+    }
+}
+
+/// Lowercase letters and digits, safe in URLs and easy to read aloud
+const DEFAULT_SHORT_ID_ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            ttl_seconds: 10800, // 3 hours
+            request_ttl_seconds: 0,
+            max_requests_per_session: 1000,
+            expiry_warning_seconds: 300, // 5 minutes
+            max_ttl_seconds: 86400,      // 24 hours
+            max_sessions: 0,
+            id_format: SessionIdFormat::Uuid,
+            short_id_length: 10,
+            short_id_alphabet: DEFAULT_SHORT_ID_ALPHABET.to_string(),
+            auto_create_sessions: false,
+            dead_letter_session: None,
+        }
+    }
+}
+
+impl Default for SseSettings {
+    fn default() -> Self {
+        Self {
+            sweep_interval_seconds: 60,
+            channel_capacity: 256,
+            lag_strategy: LagStrategy::Drop,
+            max_lifetime_seconds: 0,
+            pubsub: false,
+            max_subscribers_per_session: 0,
+        }
+    }
+}
+
+impl SessionSettings {
+    /// Short IDs must fit in a URL path segment and leave enough room to
+    /// avoid constant collisions
+    fn validate_short_ids(&self) -> anyhow::Result<()> {
+        let alphabet = &self.short_id_alphabet;
+        if let Some(c) = alphabet
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_')
+        {
+            anyhow::bail!(
+                "SESSION_SHORT_ID_ALPHABET contains a character that is not URL-safe: {:?}",
+                c
+            );
+        }
+        let mut unique: Vec<char> = alphabet.chars().collect();
+        unique.sort_unstable();
+        unique.dedup();
+        if unique.len() != alphabet.len() || unique.len() < 2 {
+            anyhow::bail!(
+                "SESSION_SHORT_ID_ALPHABET must contain at least two characters and no duplicates"
+            );
+        }
+        if self.short_id_length < 4 {
+            anyhow::bail!("SESSION_SHORT_ID_LENGTH must be at least 4");
+        }
+        Ok(())
+    }
 }
 
 impl Settings {
-    /// Load configuration from environment variables
+    /// Load configuration from an optional config file (`ECHOHOOK_CONFIG`,
+    /// TOML or YAML by extension), then apply environment variable overrides
     pub fn load() -> anyhow::Result<Self> {
-        let server_port: u16 = env::var("SERVER_PORT")
-            .unwrap_or_else(|_| "8080".to_string())
-            .parse()
-            .unwrap_or(8080);
-
-        let settings = Settings {
-            server: ServerSettings {
-                host: env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-                port: server_port,
-                listen_url: env::var("LISTEN_URL")
-                    .unwrap_or_else(|_| format!("http://localhost:{}", server_port)),
-                max_body_size: env::var("MAX_BODY_SIZE")
-                    .unwrap_or_else(|_| "10485760".to_string())
-                    .parse()
-                    .unwrap_or(10_485_760), // 10 MB
-                cors_allowed_origins: env::var("CORS_ALLOWED_ORIGINS")
-                    .unwrap_or_else(|_| "*".to_string()),
-            },
-            redis: RedisSettings {
-                url: env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string()),
-                pool_size: env::var("REDIS_POOL_SIZE")
-                    .unwrap_or_else(|_| "10".to_string())
-                    .parse()
-                    .unwrap_or(10),
-            },
-            session: SessionSettings {
-                ttl_seconds: env::var("SESSION_TTL")
-                    .unwrap_or_else(|_| "10800".to_string())
-                    .parse()
-                    .unwrap_or(10800), // 3 hours
-                max_requests_per_session: env::var("MAX_REQUESTS_PER_SESSION")
-                    .unwrap_or_else(|_| "1000".to_string())
-                    .parse()
-                    .unwrap_or(1000),
-            },
+        let mut settings = match env::var("ECHOHOOK_CONFIG") {
+            Ok(path) if !path.is_empty() => Self::from_file(&path)?,
+            _ => Self::default(),
+        };
+
+        settings.apply_env_overrides();
+
+        if settings.server.listen_url.is_empty() {
+            settings.server.listen_url = format!("http://localhost:{}", settings.server.port);
+        }
+
+        if settings.session.id_format == SessionIdFormat::Short {
+            settings.session.validate_short_ids()?;
+        }
+
+        if let Some(dead_letter) = &settings.session.dead_letter_session {
+            if !session_id::is_valid(dead_letter, &settings.session) {
+                anyhow::bail!("DEAD_LETTER_SESSION is not a valid session ID: {}", dead_letter);
+            }
+        }
+
+        if settings.forward.max_attempts == 0 {
+            anyhow::bail!("FORWARD_MAX_ATTEMPTS must be at least 1");
+        }
+
+        Ok(settings)
+    }
+
+    /// Read settings from a TOML or YAML file; missing keys keep their defaults
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let format = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("toml") => FileFormat::Toml,
+            Some("yaml") | Some("yml") => FileFormat::Yaml,
+            _ => anyhow::bail!(
+                "Unsupported config file extension (expected .toml, .yaml or .yml): {}",
+                path
+            ),
         };
 
+        let mut settings: Self = Config::builder()
+            .add_source(File::new(path, format))
+            .build()?
+            .try_deserialize()?;
+
+        // Media types are matched case-insensitively
+        settings.server.max_body_size_by_type = settings
+            .server
+            .max_body_size_by_type
+            .into_iter()
+            .map(|(media_type, limit)| (media_type.trim().to_ascii_lowercase(), limit))
+            .collect();
+
         Ok(settings)
     }
+
+    /// Override any values that are set in the environment
+    fn apply_env_overrides(&mut self) {
+        env_override(&mut self.server.host, "SERVER_HOST");
+        env_override(&mut self.server.port, "SERVER_PORT");
+        env_override(&mut self.server.listen_url, "LISTEN_URL");
+        env_override(&mut self.server.max_body_size, "MAX_BODY_SIZE");
+        if let Ok(value) = env::var("MAX_BODY_SIZE_BY_TYPE") {
+            self.server.max_body_size_by_type = parse_size_map(&value);
+        }
+        env_override(&mut self.server.body_truncate_bytes, "BODY_TRUNCATE_BYTES");
+        env_override(&mut self.server.cors_allowed_origins, "CORS_ALLOWED_ORIGINS");
+        env_override(&mut self.server.cors_refresh_seconds, "CORS_REFRESH_SECONDS");
+        env_override_optional(&mut self.server.audit_log_path, "AUDIT_LOG_PATH");
+        env_override_optional(
+            &mut self.server.subdomain_routing_domain,
+            "SUBDOMAIN_ROUTING_DOMAIN",
+        );
+        env_override(&mut self.server.tag_query_param, "TAG_QUERY_PARAM");
+        env_override(&mut self.server.tag_header, "TAG_HEADER");
+        env_override_optional(&mut self.server.receipt_signing_key, "RECEIPT_SIGNING_KEY");
+        env_override_optional(&mut self.server.api_key, "API_KEY");
+        env_override(&mut self.server.allow_private_targets, "ALLOW_PRIVATE_TARGETS");
+        env_override(&mut self.server.enable_compression, "ENABLE_COMPRESSION");
+        env_override(&mut self.server.max_headers, "MAX_HEADERS");
+        env_override(&mut self.server.max_header_value_bytes, "MAX_HEADER_VALUE_BYTES");
+        env_override(&mut self.server.header_limit_strategy, "HEADER_LIMIT_STRATEGY");
+
+        env_override(&mut self.redis.url, "REDIS_URL");
+        env_override(&mut self.redis.pool_size, "REDIS_POOL_SIZE");
+        env_override(&mut self.redis.reconnect_min_delay_ms, "REDIS_RECONNECT_MIN_DELAY_MS");
+        env_override(&mut self.redis.reconnect_max_delay_ms, "REDIS_RECONNECT_MAX_DELAY_MS");
+        env_override(&mut self.redis.reconnect_retries, "REDIS_RECONNECT_RETRIES");
+
+        env_override(&mut self.session.ttl_seconds, "SESSION_TTL");
+        env_override(&mut self.session.request_ttl_seconds, "REQUEST_TTL");
+        env_override(&mut self.session.max_requests_per_session, "MAX_REQUESTS_PER_SESSION");
+        env_override(&mut self.session.expiry_warning_seconds, "SESSION_EXPIRY_WARNING");
+        env_override(&mut self.session.max_ttl_seconds, "SESSION_MAX_TTL");
+        env_override(&mut self.session.max_sessions, "MAX_SESSIONS");
+        env_override(&mut self.session.auto_create_sessions, "AUTO_CREATE_SESSIONS");
+        env_override_optional(&mut self.session.dead_letter_session, "DEAD_LETTER_SESSION");
+        env_override(&mut self.session.id_format, "SESSION_ID_FORMAT");
+        env_override(&mut self.session.short_id_length, "SESSION_SHORT_ID_LENGTH");
+        env_override(&mut self.session.short_id_alphabet, "SESSION_SHORT_ID_ALPHABET");
+
+        env_override(&mut self.sse.sweep_interval_seconds, "SSE_SWEEP_INTERVAL");
+        env_override(&mut self.sse.channel_capacity, "SSE_CHANNEL_CAPACITY");
+        env_override(&mut self.sse.lag_strategy, "SSE_LAG_STRATEGY");
+        env_override(&mut self.sse.max_lifetime_seconds, "SSE_MAX_LIFETIME_SECONDS");
+        env_override(&mut self.sse.pubsub, "SSE_PUBSUB");
+        env_override(
+            &mut self.sse.max_subscribers_per_session,
+            "SSE_MAX_SUBSCRIBERS_PER_SESSION",
+        );
+
+        env_override(&mut self.rate_limit.per_ip, "RATE_LIMIT_PER_IP");
+        env_override(&mut self.rate_limit.window_seconds, "RATE_LIMIT_WINDOW_SECONDS");
+        env_override(&mut self.rate_limit.per_session, "SESSION_RATE_LIMIT");
+        env_override(
+            &mut self.rate_limit.session_window_seconds,
+            "SESSION_RATE_WINDOW_SECONDS",
+        );
+
+        env_override_list(&mut self.redact.headers, "REDACT_HEADERS");
+        env_override_list(&mut self.redact.json_fields, "REDACT_JSON_FIELDS");
+
+        env_override(&mut self.archive.backend, "ARCHIVE_BACKEND");
+        env_override(&mut self.archive.dir, "ARCHIVE_DIR");
+        env_override_optional(&mut self.archive.s3_bucket, "ARCHIVE_S3_BUCKET");
+        env_override(&mut self.archive.s3_prefix, "ARCHIVE_S3_PREFIX");
+        env_override(&mut self.archive.queue_size, "ARCHIVE_QUEUE_SIZE");
+
+        env_override(&mut self.forward.max_attempts, "FORWARD_MAX_ATTEMPTS");
+        env_override(
+            &mut self.forward.retry_base_delay_seconds,
+            "FORWARD_RETRY_BASE_DELAY_SECONDS",
+        );
+        env_override(&mut self.forward.retry_max_delay_seconds, "FORWARD_RETRY_MAX_DELAY_SECONDS");
+    }
 }
 
+/// Replace `target` with the parsed env var, ignoring unset or unparsable values
+fn env_override<T: FromStr>(target: &mut T, key: &str) {
+    if let Some(value) = env::var(key).ok().and_then(|v| v.parse().ok()) {
+        *target = value;
+    }
+}
+
+/// Replace an optional `target` with the env var when it is set and non-empty
+fn env_override_optional(target: &mut Option<String>, key: &str) {
+    if let Some(value) = env::var(key).ok().filter(|v| !v.is_empty()) {
+        *target = Some(value);
+    }
+}
+
+/// Replace `target` with a comma-separated env var list when it is set
+fn env_override_list(target: &mut Vec<String>, key: &str) {
+    if let Ok(value) = env::var(key) {
+        *target = value
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect();
+    }
+}
+
+/// Parse `type=bytes` pairs separated by commas, skipping malformed entries
+fn parse_size_map(value: &str) -> HashMap<String, usize> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (media_type, limit) = entry.split_once('=')?;
+            let media_type = media_type.trim().to_ascii_lowercase();
+            let limit = limit.trim().parse().ok()?;
+            (!media_type.is_empty()).then_some((media_type, limit))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `contents` to a fresh temp file with the given extension
+    fn config_file(extension: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!(
+            "echohook-config-{}.{}",
+            uuid::Uuid::now_v7(),
+            extension
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn toml_files_override_only_the_keys_they_set() {
+        let path = config_file(
+            "toml",
+            r#"
+            [server]
+            port = 9000
+            max_body_size_by_type = { " Application/JSON " = 1024 }
+
+            [session]
+            ttl_seconds = 60
+            "#,
+        );
+        let settings = Settings::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(settings.server.port, 9000);
+        assert_eq!(settings.session.ttl_seconds, 60);
+        assert_eq!(
+            settings.server.max_body_size_by_type,
+            HashMap::from([("application/json".to_string(), 1024)])
+        );
+        let defaults = Settings::default();
+        assert_eq!(settings.server.host, defaults.server.host);
+        assert_eq!(settings.redis.url, defaults.redis.url);
+    }
+
+    #[test]
+    fn yaml_files_are_read_by_either_extension() {
+        for extension in ["yaml", "yml"] {
+            let yaml = "server:\n  port: 9001\nsse:\n  lag_strategy: close\n";
+            let path = config_file(extension, yaml);
+            let settings = Settings::from_file(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(settings.server.port, 9001);
+            assert_eq!(settings.sse.lag_strategy, LagStrategy::Close);
+        }
+    }
+
+    #[test]
+    fn other_extensions_are_rejected() {
+        for path in ["echohook.json", "echohook", "echohook.TOML"] {
+            let error = Settings::from_file(path).unwrap_err().to_string();
+            assert!(error.starts_with("Unsupported config file extension"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn the_environment_takes_precedence_over_the_file() {
+        let path = config_file(
+            "toml",
+            "[server]\nport = 9000\nhost = \"file-host\"\n[redis]\nreconnect_retries = 2\n",
+        );
+        let mut settings = Settings::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Only read by this test
+        env::set_var("REDIS_RECONNECT_RETRIES", "7");
+        settings.apply_env_overrides();
+        env::remove_var("REDIS_RECONNECT_RETRIES");
+
+        assert_eq!(settings.redis.reconnect_retries, 7);
+        assert_eq!(settings.server.port, 9000);
+        assert_eq!(settings.server.host, "file-host");
+    }
+
+    #[test]
+    fn body_size_limits_follow_the_media_type() {
+        let server = ServerSettings {
+            max_body_size: 100,
+            max_body_size_by_type: parse_size_map("application/json=50,multipart/form-data=10"),
+            ..Default::default()
+        };
+        assert_eq!(server.body_size_limit("application/json"), (50, "application/json"));
+        assert_eq!(
+            server.body_size_limit(" Multipart/Form-Data; boundary=x"),
+            (10, "multipart/form-data")
+        );
+        assert_eq!(server.body_size_limit("text/plain"), (100, "default"));
+        assert_eq!(server.body_size_limit(""), (100, "default"));
+        // Only exact media types match
+        assert_eq!(server.body_size_limit("application/json-patch"), (100, "default"));
+    }
+
+    #[test]
+    fn size_maps_skip_malformed_entries() {
+        let map = parse_size_map(" Application/JSON = 1024 ,image/png=5, bad, =3, text/plain=x,");
+        assert_eq!(
+            map,
+            HashMap::from([
+                ("application/json".to_string(), 1024),
+                ("image/png".to_string(), 5),
+            ])
+        );
+        assert!(parse_size_map("").is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage};
+use std::time::Instant;
+use tracing::Span;
+use tracing_actix_web::{root_span, DefaultRootSpanBuilder, RootSpanBuilder};
+use uuid::Uuid;
+
+/// Header carrying the correlation ID, echoed when the client sends one
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Header reporting how long the server spent on the request
+pub const SERVER_TIMING_HEADER: HeaderName = HeaderName::from_static("server-timing");
+
+/// Longest client-supplied ID that is propagated rather than replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Correlation ID of the current request, stored in its extensions
+#[derive(Debug, Clone)]
+pub struct CorrelationId(pub String);
+
+/// Take the client's `X-Request-Id` (or generate a UUIDv7), expose it to the
+/// tracing span and handlers, and stamp it and the handling time onto the
+/// response
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::now_v7().to_string());
+    req.extensions_mut().insert(CorrelationId(id.clone()));
+
+    let mut response = next.call(req).await?;
+
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+    let timing = format!("app;dur={:.1}", started.elapsed().as_secs_f64() * 1000.0);
+    if let Ok(value) = HeaderValue::from_str(&timing) {
+        headers.insert(SERVER_TIMING_HEADER, value);
+    }
+    Ok(response)
+}
+
+/// Root span builder that records the correlation ID on every request span
+pub struct CorrelationRootSpan;
+
+impl RootSpanBuilder for CorrelationRootSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let correlation_id = request
+            .extensions()
+            .get::<CorrelationId>()
+            .map(|id| id.0.clone())
+            .unwrap_or_default();
+        root_span!(request, correlation_id = %correlation_id)
+    }
+
+    fn on_request_end<B: MessageBody>(
+        span: Span,
+        outcome: &Result<ServiceResponse<B>, Error>,
+    ) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{web, App, HttpRequest, HttpResponse};
+
+    /// Answers with the correlation ID handlers see
+    async fn echo_id(req: HttpRequest) -> HttpResponse {
+        let id = req.extensions().get::<CorrelationId>().unwrap().0.clone();
+        HttpResponse::Ok().body(id)
+    }
+
+    fn header<'a>(response: &'a ServiceResponse<impl MessageBody>, name: &HeaderName) -> &'a str {
+        response.headers().get(name).unwrap().to_str().unwrap()
+    }
+
+    #[actix_web::test]
+    async fn responses_carry_a_generated_id_and_the_handling_time() {
+        let app = App::new()
+            .wrap(from_fn(middleware))
+            .route("/", web::get().to(echo_id));
+        let app = init_service(app).await;
+
+        let response = call_service(&app, TestRequest::get().uri("/").to_request()).await;
+        let id = header(&response, &REQUEST_ID_HEADER).to_string();
+        assert_eq!(Uuid::parse_str(&id).unwrap().get_version_num(), 7);
+        let duration = header(&response, &SERVER_TIMING_HEADER).strip_prefix("app;dur=");
+        assert!(duration.unwrap().parse::<f64>().unwrap() >= 0.0);
+        assert_eq!(read_body(response).await, id);
+
+        // Routing failures are stamped too
+        let response = call_service(&app, TestRequest::get().uri("/nope").to_request()).await;
+        assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+        assert!(response.headers().contains_key(SERVER_TIMING_HEADER));
+    }
+
+    #[actix_web::test]
+    async fn client_request_ids_are_echoed_back() {
+        let app = App::new()
+            .wrap(from_fn(middleware))
+            .route("/", web::get().to(echo_id));
+        let app = init_service(app).await;
+        let with_id = |id: &str| {
+            TestRequest::get()
+                .uri("/")
+                .insert_header((REQUEST_ID_HEADER, id))
+                .to_request()
+        };
+
+        let response = call_service(&app, with_id(" trace-abc-123 ")).await;
+        assert_eq!(header(&response, &REQUEST_ID_HEADER), "trace-abc-123");
+        assert_eq!(read_body(response).await, "trace-abc-123");
+
+        // Oversized IDs are replaced rather than propagated
+        let long = "x".repeat(MAX_REQUEST_ID_LEN + 1);
+        let response = call_service(&app, with_id(&long)).await;
+        assert!(Uuid::parse_str(header(&response, &REQUEST_ID_HEADER)).is_ok());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::api_version;
+use crate::correlation;
+use crate::redis_client::RedisClient;
+use actix_cors::Cors;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Origins allowed to make cross-origin requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AllowedOrigins {
+    Any,
+    List(HashSet<String>),
+}
+
+impl AllowedOrigins {
+    /// Parse `*` or a comma-separated origin list
+    fn parse(value: &str) -> Self {
+        if value.trim() == "*" {
+            return Self::Any;
+        }
+        Self::List(
+            value
+                .split(',')
+                .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect(),
+        )
+    }
+}
+
+/// CORS origin allowlist shared by every worker. It starts from
+/// `CORS_ALLOWED_ORIGINS` and is replaced by the `config:cors_origins` Redis
+/// key whenever that is set, so origins can change without a restart.
+#[derive(Clone)]
+pub struct CorsOrigins {
+    current: Arc<RwLock<AllowedOrigins>>,
+    fallback: AllowedOrigins,
+}
+
+impl CorsOrigins {
+    pub fn new(fallback: &str) -> Self {
+        let fallback = AllowedOrigins::parse(fallback);
+        Self {
+            current: Arc::new(RwLock::new(fallback.clone())),
+            fallback,
+        }
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        match &*self.current.read().unwrap_or_else(|e| e.into_inner()) {
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(origins) => origins.contains(origin),
+        }
+    }
+
+    /// Apply the Redis value, or the fallback when the key is unset
+    fn update(&self, value: Option<&str>) -> bool {
+        let next = value
+            .map(AllowedOrigins::parse)
+            .unwrap_or_else(|| self.fallback.clone());
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        let changed = *current != next;
+        *current = next;
+        changed
+    }
+
+    /// Build a worker's CORS middleware. Origins are checked against the
+    /// shared allowlist on every request; the method and header policy is
+    /// fixed at startup by `static_origins` (`*` allows any header).
+    pub fn build_cors(&self, static_origins: &str) -> Cors {
+        let origins = self.clone();
+        let cors = if static_origins.trim() == "*" {
+            Cors::permissive()
+        } else {
+            Cors::default()
+                .allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"])
+                .allowed_headers(vec![
+                    actix_web::http::header::CONTENT_TYPE,
+                    actix_web::http::header::AUTHORIZATION,
+                    actix_web::http::header::ACCEPT,
+                    correlation::REQUEST_ID_HEADER,
+                ])
+                .expose_headers(vec![
+                    correlation::REQUEST_ID_HEADER,
+                    correlation::SERVER_TIMING_HEADER,
+                    api_version::API_VERSION_HEADER,
+                ])
+                .max_age(3600)
+        };
+
+        cors.allowed_origin_fn(move |origin, _| {
+            origin.to_str().is_ok_and(|origin| origins.allows(origin))
+        })
+    }
+
+    /// Reload the allowlist from Redis every `interval`
+    pub fn spawn_refresh(&self, redis: Arc<RedisClient>, interval: Duration) {
+        let origins = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                match redis.get_cors_origins().await {
+                    Ok(value) => {
+                        if origins.update(value.as_deref()) {
+                            info!(
+                                source = if value.is_some() { "redis" } else { "env" },
+                                "Updated CORS allowed origins"
+                            );
+                        } else {
+                            debug!("CORS allowed origins unchanged");
+                        }
+                    }
+                    Err(e) => warn!("Failed to load CORS origins from Redis: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[test]
+    fn origin_lists_are_trimmed_and_star_allows_any() {
+        let origins = CorsOrigins::new(" https://a.example/ , ,https://b.example");
+        assert!(origins.allows("https://a.example"));
+        assert!(origins.allows("https://b.example"));
+        assert!(!origins.allows("https://c.example"));
+        assert!(CorsOrigins::new("*").allows("https://c.example"));
+    }
+
+    #[actix_web::test]
+    async fn origins_set_in_redis_are_picked_up_without_a_restart() {
+        // A Redis answering `config:cors_origins` with whatever is in `value`
+        let value = Arc::new(std::sync::Mutex::new(None::<String>));
+        let stored = value.clone();
+        let url = crate::redis_client::fake_redis::serve(move |args| {
+            Some(match (args[0].as_str(), stored.lock().unwrap().as_deref()) {
+                ("GET", Some(origins)) => format!("${}\r\n{}\r\n", origins.len(), origins),
+                ("GET", None) => "$-1\r\n".to_string(),
+                _ => "+OK\r\n".to_string(),
+            }
+            .into_bytes())
+        })
+        .await;
+        let settings = crate::config::RedisSettings {
+            url,
+            ..Default::default()
+        };
+        let redis = Arc::new(RedisClient::new(&settings, 16, false).await.unwrap());
+
+        let origins = CorsOrigins::new("https://a.example");
+        let app = App::new()
+            .wrap(origins.build_cors("https://a.example"))
+            .route("/", web::get().to(HttpResponse::Ok));
+        let app = init_service(app).await;
+        let allowed = |origin: &'static str| {
+            let req = TestRequest::get().uri("/").insert_header((header::ORIGIN, origin));
+            let response = call_service(&app, req.to_request());
+            async move {
+                let response = response.await;
+                let allowed = response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN);
+                allowed.is_some_and(|allowed| allowed == origin)
+            }
+        };
+        let settle = |expected: bool| {
+            let origins = origins.clone();
+            async move {
+                for _ in 0..100 {
+                    if origins.allows("https://b.example") == expected {
+                        return;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                panic!("allowlist never changed");
+            }
+        };
+
+        assert!(allowed("https://a.example").await);
+        assert!(!allowed("https://b.example").await);
+
+        *value.lock().unwrap() = Some("https://a.example,https://b.example".to_string());
+        origins.spawn_refresh(redis, Duration::from_millis(10));
+        settle(true).await;
+        assert!(allowed("https://b.example").await);
+
+        // Deleting the key falls back to the environment's list
+        *value.lock().unwrap() = None;
+        settle(false).await;
+        assert!(!allowed("https://b.example").await);
+        assert!(allowed("https://a.example").await);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::models::{
+    BodyDiff, BodyEncoding, ChangeKind, JsonChange, MapDiff, RequestDiffResponse, ValueChange,
+    WebhookRequest,
+};
+use serde::Serialize;
+use serde_json::Value;
+use similar::TextDiff;
+use std::collections::{BTreeSet, HashMap};
+
+/// Compare two captured requests of the same session
+pub fn diff_requests(
+    session_id: &str,
+    a: &WebhookRequest,
+    b: &WebhookRequest,
+) -> RequestDiffResponse {
+    let method = value_change(&a.method, &b.method);
+    let path = value_change(&a.path, &b.path);
+    // Header names are case-insensitive
+    let headers = diff_maps(&lowercase_keys(&a.headers), &lowercase_keys(&b.headers));
+    let query_params = diff_maps(&a.query_params, &b.query_params);
+    let body = diff_bodies(a, b);
+
+    RequestDiffResponse {
+        session_id: session_id.to_string(),
+        a: a.request_id.clone(),
+        b: b.request_id.clone(),
+        identical: method.is_none()
+            && path.is_none()
+            && headers.is_empty()
+            && query_params.is_empty()
+            && matches!(body, BodyDiff::Identical),
+        method,
+        path,
+        headers,
+        query_params,
+        body,
+    }
+}
+
+fn value_change(a: &str, b: &str) -> Option<ValueChange> {
+    (a != b).then(|| ValueChange {
+        a: Some(a.into()),
+        b: Some(b.into()),
+    })
+}
+
+fn lowercase_keys(headers: &HashMap<String, String>) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(key, value)| (key.to_ascii_lowercase(), value.clone()))
+        .collect()
+}
+
+fn diff_maps<V>(a: &HashMap<String, V>, b: &HashMap<String, V>) -> MapDiff
+where
+    V: PartialEq + Serialize,
+{
+    let to_value = |value: &V| serde_json::to_value(value).unwrap_or_default();
+    let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+
+    let mut diff = MapDiff::default();
+    for key in keys {
+        match (a.get(key), b.get(key)) {
+            (Some(a), Some(b)) if a != b => {
+                diff.changed.insert(
+                    key.clone(),
+                    ValueChange {
+                        a: Some(to_value(a)),
+                        b: Some(to_value(b)),
+                    },
+                );
+            }
+            (Some(a), None) => {
+                diff.removed.insert(key.clone(), to_value(a));
+            }
+            (None, Some(b)) => {
+                diff.added.insert(key.clone(), to_value(b));
+            }
+            _ => {}
+        }
+    }
+    diff
+}
+
+fn diff_bodies(a: &WebhookRequest, b: &WebhookRequest) -> BodyDiff {
+    if let (Some(a_json), Some(b_json)) = (&a.body_json, &b.body_json) {
+        let mut changes = Vec::new();
+        diff_json(String::new(), a_json, b_json, &mut changes);
+        return if changes.is_empty() {
+            BodyDiff::Identical
+        } else {
+            BodyDiff::Json { changes }
+        };
+    }
+
+    if a.body == b.body && a.body_encoding == b.body_encoding {
+        return BodyDiff::Identical;
+    }
+
+    match (a.body_encoding, b.body_encoding) {
+        (BodyEncoding::Utf8, BodyEncoding::Utf8) => BodyDiff::Text {
+            unified: TextDiff::from_lines(&a.body, &b.body)
+                .unified_diff()
+                .header(&a.request_id, &b.request_id)
+                .to_string(),
+        },
+        _ => BodyDiff::Binary {
+            a_length: a.decoded_length,
+            b_length: b.decoded_length,
+        },
+    }
+}
+
+/// Walk two JSON values together, recording every added, removed or changed
+/// location under `pointer`. Arrays are compared index by index.
+fn diff_json(pointer: String, a: &Value, b: &Value, changes: &mut Vec<JsonChange>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for key in keys {
+                let child = format!("{}/{}", pointer, escape_pointer_token(key));
+                diff_entry(child, a.get(key), b.get(key), changes);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for index in 0..a.len().max(b.len()) {
+                let child = format!("{}/{}", pointer, index);
+                diff_entry(child, a.get(index), b.get(index), changes);
+            }
+        }
+        _ if a != b => changes.push(JsonChange {
+            path: pointer,
+            op: ChangeKind::Changed,
+            a: Some(a.clone()),
+            b: Some(b.clone()),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_entry(
+    pointer: String,
+    a: Option<&Value>,
+    b: Option<&Value>,
+    changes: &mut Vec<JsonChange>,
+) {
+    match (a, b) {
+        (Some(a), Some(b)) => diff_json(pointer, a, b, changes),
+        (Some(a), None) => changes.push(JsonChange {
+            path: pointer,
+            op: ChangeKind::Removed,
+            a: Some(a.clone()),
+            b: None,
+        }),
+        (None, Some(b)) => changes.push(JsonChange {
+            path: pointer,
+            op: ChangeKind::Added,
+            a: None,
+            b: Some(b.clone()),
+        }),
+        (None, None) => {}
+    }
+}
+
+/// Escape a key for use in a JSON Pointer (RFC 6901)
+fn escape_pointer_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(id: &str, body: Value) -> WebhookRequest {
+        WebhookRequest {
+            request_id: id.to_string(),
+            method: "POST".to_string(),
+            path: "/i/abc".to_string(),
+            headers: HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
+            body: body.to_string(),
+            body_json: Some(body),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn one_changed_json_field_is_the_only_difference() {
+        let a = request("a", json!({"type": "invoice", "data": {"status": "pending"}}));
+        let b = request("b", json!({"type": "invoice", "data": {"status": "paid"}}));
+
+        let diff = serde_json::to_value(diff_requests("abc", &a, &b)).unwrap();
+
+        assert_eq!(diff["identical"], false);
+        assert_eq!(diff["method"], Value::Null);
+        assert_eq!(diff["path"], Value::Null);
+        assert_eq!(diff["headers"], json!({"added": {}, "removed": {}, "changed": {}}));
+        assert_eq!(
+            diff["body"],
+            json!({"type": "json", "changes": [
+                {"path": "/data/status", "op": "changed", "a": "pending", "b": "paid"}
+            ]})
+        );
+        assert!(diff_requests("abc", &a, &a).identical);
+    }
+
+    #[test]
+    fn json_keys_and_array_items_can_be_added_and_removed() {
+        let a = request("a", json!({"gone": 1, "a/b": [1, 2]}));
+        let b = request("b", json!({"new": true, "a/b": [1]}));
+
+        let diff = serde_json::to_value(diff_requests("abc", &a, &b).body).unwrap();
+
+        assert_eq!(
+            diff["changes"],
+            json!([
+                {"path": "/a~1b/1", "op": "removed", "a": 2},
+                {"path": "/gone", "op": "removed", "a": 1},
+                {"path": "/new", "op": "added", "b": true},
+            ])
+        );
+    }
+
+    #[test]
+    fn headers_and_query_params_are_compared_key_by_key() {
+        let mut a = request("a", json!({}));
+        let mut b = request("b", json!({}));
+        a.headers.insert("X-Old".to_string(), "1".to_string());
+        b.headers = HashMap::from([
+            ("content-type".to_string(), "application/json; charset=utf-8".to_string()),
+            ("x-new".to_string(), "2".to_string()),
+        ]);
+        b.query_params = HashMap::from([("page".to_string(), vec!["2".to_string()])]);
+
+        let diff = diff_requests("abc", &a, &b);
+
+        assert_eq!(diff.headers.added["x-new"], "2");
+        assert_eq!(diff.headers.removed["x-old"], "1");
+        assert_eq!(
+            diff.headers.changed["content-type"].b,
+            Some(json!("application/json; charset=utf-8"))
+        );
+        assert_eq!(diff.query_params.added["page"], json!(["2"]));
+        assert!(matches!(diff.body, BodyDiff::Identical));
+    }
+
+    #[test]
+    fn text_bodies_get_a_unified_diff() {
+        let mut a = request("a", json!(null));
+        let mut b = request("b", json!(null));
+        (a.body_json, b.body_json) = (None, None);
+        a.body = "one\ntwo\n".to_string();
+        b.body = "one\n2\n".to_string();
+
+        let BodyDiff::Text { unified } = diff_requests("abc", &a, &b).body else {
+            panic!("expected a text diff");
+        };
+        assert_eq!(unified, "--- a\n+++ b\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n");
+    }
+}
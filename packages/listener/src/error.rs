@@ -1,4 +1,7 @@
-use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
 use serde::Serialize;
 
 /// Application-specific error types
@@ -7,14 +10,65 @@ pub enum AppError {
     #[error("Session not found or expired")]
     SessionNotFound,
 
+    #[error("Request not found")]
+    RequestNotFound,
+
     #[error("Invalid UUID format: {0}")]
     InvalidUuid(String),
 
-    #[error("Payload too large: {size} bytes exceeds limit of {limit} bytes")]
-    PayloadTooLarge { size: usize, limit: usize },
+    #[error("Invalid session ID: {0}")]
+    InvalidSessionId(String),
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Missing or invalid ingest token")]
+    InvalidIngestToken,
+
+    #[error("Webhook signature verification failed")]
+    InvalidSignature,
+
+    #[error("IP address {0} is not allowed to ingest into this session")]
+    IpNotAllowed(String),
+
+    #[error("Method {method} is not allowed for this session")]
+    MethodNotAllowed {
+        method: String,
+        /// Sent as the `Allow` header
+        allowed: Vec<String>,
+    },
 
-    #[error("Rate limit exceeded: {0}")]
-    RateLimitExceeded(String),
+    #[error("Body does not match the session schema: {}", .0.join("; "))]
+    SchemaValidationFailed(Vec<String>),
+
+    #[error("Request headers too large: {0}")]
+    HeadersTooLarge(String),
+
+    #[error("Payload too large: {size} bytes exceeds the {applied} limit of {limit} bytes")]
+    PayloadTooLarge {
+        size: usize,
+        limit: usize,
+        /// Which limit was enforced: a content type, or `default`
+        applied: String,
+    },
+
+    #[error("Request subpath too long: {size} bytes exceeds the limit of {limit} bytes")]
+    SubpathTooLong { size: usize, limit: usize },
+
+    #[error("Rate limit exceeded: {message}")]
+    RateLimitExceeded {
+        message: String,
+        /// Sent as `Retry-After` when known
+        retry_after_seconds: Option<u64>,
+        /// The limit that was hit
+        limit: u64,
+    },
+
+    #[error("Session limit reached: maximum {0} active sessions")]
+    SessionLimitExceeded(usize),
 
     #[error("Redis error: {0}")]
     Redis(#[from] redis::RedisError),
@@ -32,15 +86,66 @@ pub struct ErrorResponse {
     pub error: String,
     pub message: String,
     pub status: u16,
+    /// Rate-limit details (only present on `rate_limit_exceeded`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_seconds: Option<u64>,
+    /// The limit that was hit (on `rate_limit_exceeded` and `payload_too_large`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    /// Which body size limit was enforced (only present on `payload_too_large`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub applied: Option<String>,
+    /// Individual validation errors (only present on `schema_validation_failed`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<String>>,
+}
+
+impl AppError {
+    /// Stable machine-readable code, returned as `error` in responses
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            AppError::SessionNotFound => "session_not_found",
+            AppError::RequestNotFound => "request_not_found",
+            AppError::InvalidUuid(_) => "invalid_uuid",
+            AppError::InvalidSessionId(_) => "invalid_session_id",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::InvalidIngestToken => "invalid_ingest_token",
+            AppError::InvalidSignature => "invalid_signature",
+            AppError::IpNotAllowed(_) => "ip_not_allowed",
+            AppError::MethodNotAllowed { .. } => "method_not_allowed",
+            AppError::SchemaValidationFailed(_) => "schema_validation_failed",
+            AppError::HeadersTooLarge(_) => "headers_too_large",
+            AppError::PayloadTooLarge { .. } => "payload_too_large",
+            AppError::SubpathTooLong { .. } => "subpath_too_long",
+            AppError::RateLimitExceeded { .. } => "rate_limit_exceeded",
+            AppError::SessionLimitExceeded(_) => "session_limit_exceeded",
+            AppError::Redis(_) => "redis_error",
+            AppError::Serialization(_) => "serialization_error",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
 }
 
 impl ResponseError for AppError {
     fn status_code(&self) -> StatusCode {
         match self {
             AppError::SessionNotFound => StatusCode::NOT_FOUND,
+            AppError::RequestNotFound => StatusCode::NOT_FOUND,
             AppError::InvalidUuid(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidSessionId(_) => StatusCode::BAD_REQUEST,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::InvalidIngestToken => StatusCode::UNAUTHORIZED,
+            AppError::InvalidSignature => StatusCode::UNAUTHORIZED,
+            AppError::IpNotAllowed(_) => StatusCode::FORBIDDEN,
+            AppError::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::SchemaValidationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::HeadersTooLarge(_) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
             AppError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
-            AppError::RateLimitExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::SubpathTooLong { .. } => StatusCode::URI_TOO_LONG,
+            AppError::RateLimitExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::SessionLimitExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
             AppError::Redis(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Serialization(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -49,20 +154,40 @@ impl ResponseError for AppError {
 
     fn error_response(&self) -> HttpResponse {
         let status = self.status_code();
-        let error_code = match self {
-            AppError::SessionNotFound => "session_not_found",
-            AppError::InvalidUuid(_) => "invalid_uuid",
-            AppError::PayloadTooLarge { .. } => "payload_too_large",
-            AppError::RateLimitExceeded(_) => "rate_limit_exceeded",
-            AppError::Redis(_) => "redis_error",
-            AppError::Serialization(_) => "serialization_error",
-            AppError::Internal(_) => "internal_error",
+        let error_code = self.error_code();
+
+        let (retry_after_seconds, limit) = match self {
+            AppError::RateLimitExceeded {
+                retry_after_seconds,
+                limit,
+                ..
+            } => (*retry_after_seconds, Some(*limit)),
+            AppError::PayloadTooLarge { limit, .. } => (None, Some(*limit as u64)),
+            _ => (None, None),
         };
 
-        HttpResponse::build(status).json(ErrorResponse {
+        let mut response = HttpResponse::build(status);
+        if let Some(retry_after) = retry_after_seconds {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        if let AppError::MethodNotAllowed { allowed, .. } = self {
+            response.insert_header((header::ALLOW, allowed.join(", ")));
+        }
+
+        response.json(ErrorResponse {
             error: error_code.to_string(),
             message: self.to_string(),
             status: status.as_u16(),
+            retry_after_seconds,
+            limit,
+            applied: match self {
+                AppError::PayloadTooLarge { applied, .. } => Some(applied.clone()),
+                _ => None,
+            },
+            errors: match self {
+                AppError::SchemaValidationFailed(errors) => Some(errors.clone()),
+                _ => None,
+            },
         })
     }
 }
@@ -70,3 +195,85 @@ impl ResponseError for AppError {
 /// Result type alias for handlers
 pub type AppResult<T> = Result<T, AppError>;
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body(response: HttpResponse) -> serde_json::Value {
+        let bytes = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn rate_limits_carry_retry_after_and_the_limit() {
+        let error = AppError::RateLimitExceeded {
+            message: "Maximum 5 requests per 60 seconds exceeded".to_string(),
+            retry_after_seconds: Some(42),
+            limit: 5,
+        };
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "42");
+
+        let body = body(response).await;
+        assert_eq!(body["error"], "rate_limit_exceeded");
+        assert_eq!(body["status"], 429);
+        assert_eq!(body["retry_after_seconds"], 42);
+        assert_eq!(body["limit"], 5);
+    }
+
+    #[actix_web::test]
+    async fn unknown_retry_times_leave_out_the_header() {
+        let error = AppError::RateLimitExceeded {
+            message: "slow down".to_string(),
+            retry_after_seconds: None,
+            limit: 5,
+        };
+        let response = error.error_response();
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+
+        let body = body(response).await;
+        assert!(body.get("retry_after_seconds").is_none());
+        assert_eq!(body["limit"], 5);
+    }
+
+    #[actix_web::test]
+    async fn method_not_allowed_lists_the_allowed_methods() {
+        let error = AppError::MethodNotAllowed {
+            method: "DELETE".to_string(),
+            allowed: vec!["GET".to_string(), "POST".to_string()],
+        };
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers().get(header::ALLOW).unwrap(), "GET, POST");
+        assert_eq!(body(response).await["error"], "method_not_allowed");
+    }
+
+    #[actix_web::test]
+    async fn schema_failures_list_each_error() {
+        let errors = vec!["/id: required".to_string(), "/amount: not a number".to_string()];
+        let response = AppError::SchemaValidationFailed(errors).error_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = body(response).await;
+        assert_eq!(body["errors"], serde_json::json!(["/id: required", "/amount: not a number"]));
+        assert_eq!(
+            body["message"],
+            "Body does not match the session schema: /id: required; /amount: not a number"
+        );
+    }
+
+    #[actix_web::test]
+    async fn other_errors_leave_out_the_optional_fields() {
+        let response = AppError::SessionNotFound.error_response();
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+        assert!(response.headers().get(header::ALLOW).is_none());
+
+        let body = body(response).await;
+        assert_eq!(body["status"], 404);
+        for field in ["retry_after_seconds", "limit", "applied", "errors"] {
+            assert!(body.get(field).is_none(), "{}", field);
+        }
+    }
+}
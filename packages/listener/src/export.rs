@@ -0,0 +1,408 @@
+use crate::error::{AppError, AppResult};
+use crate::forwarder::HOP_BY_HOP_HEADERS;
+use crate::models::{
+    BodyEncoding, HarCache, HarContent, HarCreator, HarDocument, HarEntry, HarLog, HarNameValue,
+    HarPostData, HarRequest, HarResponse, HarTimings, WebhookRequest,
+};
+use actix_web::web::Bytes;
+
+/// CSV column headers, in row order
+const CSV_COLUMNS: &[&str] = &[
+    "request_id",
+    "timestamp",
+    "method",
+    "path",
+    "ip_address",
+    "user_agent",
+    "content_length",
+    "headers",
+    "body",
+];
+
+/// Encode a single CSV record (the `csv` crate handles quoting of commas, quotes and newlines)
+fn csv_record<I, T>(fields: I) -> AppResult<Bytes>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(fields)
+        .map_err(|e| AppError::Internal(format!("CSV encoding failed: {}", e)))?;
+    let data = writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("CSV encoding failed: {}", e)))?;
+    Ok(Bytes::from(data))
+}
+
+/// CSV header row
+pub fn csv_header() -> AppResult<Bytes> {
+    csv_record(CSV_COLUMNS)
+}
+
+/// CSV row for a captured request; headers are JSON-encoded into one column
+pub fn csv_row(request: &WebhookRequest) -> AppResult<Bytes> {
+    let headers = serde_json::to_string(&request.headers)?;
+    csv_record([
+        request.request_id.as_str(),
+        request.timestamp.as_str(),
+        request.method.as_str(),
+        request.path.as_str(),
+        request.ip_address.as_str(),
+        request.user_agent.as_str(),
+        request.content_length.to_string().as_str(),
+        headers.as_str(),
+        request.body.as_str(),
+    ])
+}
+
+/// One NDJSON line (a serialized request followed by `\n`)
+pub fn ndjson_line(request: &WebhookRequest) -> AppResult<Bytes> {
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    Ok(Bytes::from(line))
+}
+
+/// Sort name/value pairs so HAR output is stable regardless of map ordering
+fn har_pairs<'a>(pairs: impl IntoIterator<Item = (&'a String, &'a String)>) -> Vec<HarNameValue> {
+    let mut pairs: Vec<HarNameValue> = pairs
+        .into_iter()
+        .map(|(name, value)| HarNameValue {
+            name: name.clone(),
+            value: value.clone(),
+        })
+        .collect();
+    pairs.sort_by(|a, b| a.name.cmp(&b.name));
+    pairs
+}
+
+/// Map a captured request to a HAR entry; the response is synthesized from
+/// the capture acknowledgment since echohook has no upstream response
+fn har_entry(request: &WebhookRequest, base_url: &str) -> HarEntry {
+    let query_string = har_pairs(
+        request
+            .query_params
+            .iter()
+            .flat_map(|(name, values)| values.iter().map(move |value| (name, value))),
+    );
+    let url = if query_string.is_empty() {
+        format!("{}{}", base_url, request.path)
+    } else if !request.raw_query.is_empty() {
+        format!("{}{}?{}", base_url, request.path, request.raw_query)
+    } else {
+        let query = query_string
+            .iter()
+            .map(|p| {
+                if p.value.is_empty() {
+                    p.name.clone()
+                } else {
+                    format!("{}={}", p.name, p.value)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("{}{}?{}", base_url, request.path, query)
+    };
+
+    let post_data = (!request.body.is_empty()).then(|| HarPostData {
+        mime_type: request.content_type.clone(),
+        text: request.body.clone(),
+    });
+
+    HarEntry {
+        started_date_time: request.timestamp.clone(),
+        time: 0.0,
+        request: HarRequest {
+            method: request.method.clone(),
+            url,
+            http_version: "HTTP/1.1".to_string(),
+            cookies: Vec::new(),
+            headers: har_pairs(&request.headers),
+            query_string,
+            post_data,
+            headers_size: -1,
+            body_size: request.content_length as i64,
+        },
+        response: HarResponse {
+            status: 200,
+            status_text: "OK".to_string(),
+            http_version: "HTTP/1.1".to_string(),
+            cookies: Vec::new(),
+            headers: Vec::new(),
+            content: HarContent {
+                size: 0,
+                mime_type: "application/json".to_string(),
+            },
+            redirect_url: String::new(),
+            headers_size: -1,
+            body_size: -1,
+        },
+        cache: HarCache {},
+        timings: HarTimings {
+            send: 0.0,
+            wait: 0.0,
+            receive: 0.0,
+        },
+    }
+}
+
+/// Build a HAR 1.2 document from captured requests (given in chronological order)
+pub fn har_document(requests: &[WebhookRequest], base_url: &str) -> HarDocument {
+    HarDocument {
+        log: HarLog {
+            version: "1.2".to_string(),
+            creator: HarCreator {
+                name: "echohook".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            entries: requests.iter().map(|r| har_entry(r, base_url)).collect(),
+        },
+    }
+}
+
+/// Quote a value for POSIX shells: wrap in single quotes, closing and
+/// reopening them around embedded single quotes
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Build a `curl` command that re-sends a captured request to its original
+/// ingestion URL (under `base_url`). Hop-by-hop headers are skipped, as is
+/// `Content-Encoding` when the stored body was decompressed. Binary bodies
+/// are piped in from base64.
+pub fn curl_command(request: &WebhookRequest, base_url: &str) -> String {
+    let url = if request.raw_query.is_empty() {
+        format!("{}{}", base_url, request.path)
+    } else {
+        format!("{}{}?{}", base_url, request.path, request.raw_query)
+    };
+
+    let mut headers: Vec<(&String, &String)> = request
+        .headers
+        .iter()
+        .filter(|(name, _)| !HOP_BY_HOP_HEADERS.contains(&name.as_str()))
+        .filter(|(name, _)| {
+            request.original_encoding.is_none() || name.as_str() != "content-encoding"
+        })
+        .collect();
+    headers.sort();
+
+    // Methods are usually plain words; quote only the unusual ones
+    let method = if request.method.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        request.method.clone()
+    } else {
+        shell_quote(&request.method)
+    };
+    // One line per flag and its value
+    let mut lines = vec![format!("curl -X {}", method)];
+    for (name, value) in headers {
+        lines.push(format!("-H {}", shell_quote(&format!("{}: {}", name, value))));
+    }
+
+    let mut prefix = String::new();
+    if !request.body.is_empty() {
+        match request.body_encoding {
+            BodyEncoding::Utf8 => {
+                lines.push(format!("--data-raw {}", shell_quote(&request.body)));
+            }
+            BodyEncoding::Base64 => {
+                prefix = format!("printf '%s' {} | base64 -d | ", shell_quote(&request.body));
+                lines.push("--data-binary @-".to_string());
+            }
+        }
+    }
+    lines.push(shell_quote(&url));
+
+    format!("{}{}\n", prefix, lines.join(" \\\n  "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// The parts of the HAR 1.2 schema (required members and their types)
+    /// that echohook produces
+    fn har_schema() -> serde_json::Value {
+        let pairs = json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["name", "value"],
+                "properties": {"name": {"type": "string"}, "value": {"type": "string"}}
+            }
+        });
+        json!({
+            "type": "object",
+            "required": ["log"],
+            "properties": {"log": {
+                "type": "object",
+                "required": ["version", "creator", "entries"],
+                "properties": {
+                    "version": {"type": "string"},
+                    "creator": {
+                        "type": "object",
+                        "required": ["name", "version"]
+                    },
+                    "entries": {"type": "array", "items": {
+                        "type": "object",
+                        "required": [
+                            "startedDateTime", "time", "request", "response", "cache", "timings"
+                        ],
+                        "properties": {
+                            "startedDateTime": {"type": "string", "minLength": 1},
+                            "time": {"type": "number"},
+                            "request": {
+                                "type": "object",
+                                "required": [
+                                    "method", "url", "httpVersion", "cookies", "headers",
+                                    "queryString", "headersSize", "bodySize"
+                                ],
+                                "properties": {
+                                    "method": {"type": "string"},
+                                    "url": {"type": "string"},
+                                    "httpVersion": {"type": "string"},
+                                    "cookies": pairs,
+                                    "headers": pairs,
+                                    "queryString": pairs,
+                                    "postData": {
+                                        "type": "object",
+                                        "required": ["mimeType", "text"]
+                                    },
+                                    "headersSize": {"type": "integer"},
+                                    "bodySize": {"type": "integer"}
+                                }
+                            },
+                            "response": {
+                                "type": "object",
+                                "required": [
+                                    "status", "statusText", "httpVersion", "cookies", "headers",
+                                    "content", "redirectURL", "headersSize", "bodySize"
+                                ],
+                                "properties": {
+                                    "status": {"type": "integer"},
+                                    "headers": pairs,
+                                    "content": {
+                                        "type": "object",
+                                        "required": ["size", "mimeType"]
+                                    },
+                                    "redirectURL": {"type": "string"}
+                                }
+                            },
+                            "cache": {"type": "object"},
+                            "timings": {
+                                "type": "object",
+                                "required": ["send", "wait", "receive"]
+                            }
+                        }
+                    }}
+                }
+            }}
+        })
+    }
+
+    fn sample_requests() -> Vec<WebhookRequest> {
+        let json_post = WebhookRequest {
+            request_id: "first".to_string(),
+            method: "POST".to_string(),
+            path: "/i/abc".to_string(),
+            query_params: HashMap::from([("source".to_string(), vec!["github".to_string()])]),
+            raw_query: "source=github".to_string(),
+            headers: HashMap::from([
+                ("content-type".to_string(), "application/json".to_string()),
+                ("x-github-event".to_string(), "push".to_string()),
+            ]),
+            body: r#"{"ref":"main"}"#.to_string(),
+            content_type: "application/json".to_string(),
+            timestamp: "2024-01-01T00:00:00+00:00".to_string(),
+            content_length: 14,
+            ..Default::default()
+        };
+        let bare_get = WebhookRequest {
+            request_id: "second".to_string(),
+            method: "GET".to_string(),
+            path: "/i/abc/health".to_string(),
+            timestamp: "2024-01-01T00:00:01+00:00".to_string(),
+            ..Default::default()
+        };
+        vec![json_post, bare_get]
+    }
+
+    #[test]
+    fn every_ndjson_line_is_one_json_request() {
+        let mut tricky = sample_requests().remove(0);
+        tricky.body = "line one\nline two\r\n\"quoted\"".to_string();
+        let requests = [tricky, sample_requests().remove(1)];
+
+        let output: Vec<u8> = requests
+            .iter()
+            .flat_map(|request| ndjson_line(request).unwrap().to_vec())
+            .collect();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.ends_with('\n'));
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), requests.len());
+        for (line, request) in lines.iter().zip(&requests) {
+            let parsed: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(parsed["request_id"], request.request_id.as_str());
+            assert_eq!(parsed["body"], request.body.as_str());
+        }
+    }
+
+    #[test]
+    fn har_document_matches_the_har_schema() {
+        let document = serde_json::to_value(har_document(
+            &sample_requests(),
+            "https://hooks.example.com",
+        ))
+        .unwrap();
+        let validator = jsonschema::validator_for(&har_schema()).unwrap();
+        let errors: Vec<String> = validator.iter_errors(&document).map(|e| e.to_string()).collect();
+        assert!(errors.is_empty(), "invalid HAR: {:?}", errors);
+
+        let entries = &document["log"]["entries"];
+        assert_eq!(entries.as_array().unwrap().len(), 2);
+        let first = &entries[0]["request"];
+        assert_eq!(entries[0]["startedDateTime"], "2024-01-01T00:00:00+00:00");
+        assert_eq!(first["url"], "https://hooks.example.com/i/abc?source=github");
+        assert_eq!(first["postData"]["text"], r#"{"ref":"main"}"#);
+        assert_eq!(first["queryString"][0]["name"], "source");
+        assert_eq!(first["headers"][0]["name"], "content-type");
+        assert!(entries[1]["request"].get("postData").is_none());
+    }
+
+    #[test]
+    fn curl_commands_quote_headers_and_the_body() {
+        let mut request = sample_requests().remove(0);
+        request.headers.insert("connection".to_string(), "keep-alive".to_string());
+        request.headers.insert("x-note".to_string(), "it's $HOME".to_string());
+        request.body = r#"{"msg":"don't"}"#.to_string();
+
+        let command = curl_command(&request, "https://hooks.example.com");
+
+        assert_eq!(
+            command,
+            "curl -X POST \\\n  \
+             -H 'content-type: application/json' \\\n  \
+             -H 'x-github-event: push' \\\n  \
+             -H 'x-note: it'\\''s $HOME' \\\n  \
+             --data-raw '{\"msg\":\"don'\\''t\"}' \\\n  \
+             'https://hooks.example.com/i/abc?source=github'\n"
+        );
+    }
+
+    #[test]
+    fn binary_bodies_are_piped_in_and_odd_methods_quoted() {
+        let mut request = sample_requests().remove(1);
+        request.method = "M-SEARCH".to_string();
+        request.body = "AP8=".to_string();
+        request.body_encoding = BodyEncoding::Base64;
+
+        let command = curl_command(&request, "http://h");
+
+        assert!(command.starts_with("printf '%s' 'AP8=' | base64 -d | curl -X 'M-SEARCH'"));
+        assert!(command.ends_with("--data-binary @- \\\n  'http://h/i/abc/health'\n"));
+    }
+}
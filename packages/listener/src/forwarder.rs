@@ -0,0 +1,326 @@
+use crate::models::WebhookRequest;
+use actix_web::web::Bytes;
+use reqwest::{redirect, Client, Method, Url};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Timeout for a single forwarding attempt
+pub const FORWARD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most bytes of an upstream response body kept as `forward_response`
+pub const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// Headers that describe the inbound connection and must not be replayed
+pub const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "host",
+    "connection",
+    "content-length",
+    "transfer-encoding",
+    "keep-alive",
+    "upgrade",
+    "te",
+    "trailer",
+    "proxy-connection",
+];
+
+/// Result of relaying a captured request upstream
+#[derive(Debug, Clone)]
+pub struct ForwardOutcome {
+    /// Upstream HTTP status (absent if the request never got a response)
+    pub status: Option<u16>,
+    /// Upstream response body, or a description of the failure
+    pub response: String,
+}
+
+/// Build the shared HTTP client used for forwarding, replay and
+/// notifications. Redirects are not followed: a redirect to a private
+/// address would bypass `check_public_target`, so 3xx responses are
+/// returned as-is.
+pub fn build_client() -> anyhow::Result<Client> {
+    Ok(Client::builder()
+        .timeout(FORWARD_TIMEOUT)
+        .redirect(redirect::Policy::none())
+        .build()?)
+}
+
+/// Validate a user-supplied target URL (must be absolute http/https);
+/// `field` names the offending parameter in error messages
+pub fn validate_target_url(field: &str, url: &str) -> Result<Url, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid {}: {}", field, e))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed),
+        scheme => Err(format!("Unsupported {} scheme: {}", field, scheme)),
+    }
+}
+
+/// Reject obvious SSRF targets: localhost names, cloud metadata hosts and
+/// loopback, private, link-local or unspecified IP literals.
+///
+/// Hostnames are not resolved, so this is a guard against mistakes rather
+/// than a complete defense against DNS tricks.
+pub fn check_public_target(url: &Url) -> Result<(), String> {
+    let host = url
+        .host_str()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+
+    let blocked = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        Ok(IpAddr::V6(ip)) => {
+            let mapped = ip.to_ipv4_mapped();
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local (fc00::/7) and link-local (fe80::/10)
+                || (ip.segments()[0] & 0xfe00) == 0xfc00
+                || (ip.segments()[0] & 0xffc0) == 0xfe80
+                || mapped
+                    .is_some_and(|v4| v4.is_loopback() || v4.is_private() || v4.is_link_local())
+        }
+        Err(_) => {
+            host.is_empty()
+                || host == "localhost"
+                || host.ends_with(".localhost")
+                || host == "metadata.google.internal"
+        }
+    };
+
+    if blocked {
+        Err(format!("Target {} is a private or local address", host))
+    } else {
+        Ok(())
+    }
+}
+
+/// The parts of a captured request that are replayed upstream
+pub struct Outbound<'a> {
+    pub request_id: &'a str,
+    pub method: &'a str,
+    pub headers: &'a HashMap<String, String>,
+    pub query_string: &'a str,
+}
+
+/// Replay a captured request (method, headers, query string and body) to `target`
+pub async fn forward_request(
+    client: &Client,
+    target: &str,
+    request: &WebhookRequest,
+    query_string: &str,
+    body: Bytes,
+    allow_private_targets: bool,
+) -> ForwardOutcome {
+    let outbound = Outbound {
+        request_id: &request.request_id,
+        method: &request.method,
+        headers: &request.headers,
+        query_string,
+    };
+    forward(client, target, &outbound, body, allow_private_targets).await
+}
+
+/// Replay a request from its parts, as kept for queued retries. Private and
+/// local targets are refused unless `allow_private_targets` is set.
+pub async fn forward(
+    client: &Client,
+    target: &str,
+    outbound: &Outbound<'_>,
+    body: Bytes,
+    allow_private_targets: bool,
+) -> ForwardOutcome {
+    let request_id = outbound.request_id;
+    match send(client, target, outbound, body, allow_private_targets).await {
+        Ok(outcome) => {
+            debug!(
+                request_id = %request_id,
+                target = %target,
+                status = ?outcome.status,
+                "Forwarded webhook request"
+            );
+            outcome
+        }
+        Err(e) => {
+            warn!(
+                request_id = %request_id,
+                target = %target,
+                error = %e,
+                "Failed to forward webhook request"
+            );
+            ForwardOutcome {
+                status: None,
+                response: format!("Forwarding failed: {}", e),
+            }
+        }
+    }
+}
+
+async fn send(
+    client: &Client,
+    target: &str,
+    outbound: &Outbound<'_>,
+    body: Bytes,
+    allow_private_targets: bool,
+) -> anyhow::Result<ForwardOutcome> {
+    let mut url = Url::parse(target)?;
+    if !allow_private_targets {
+        check_public_target(&url).map_err(anyhow::Error::msg)?;
+    }
+    let query_string = outbound.query_string;
+    if !query_string.is_empty() {
+        let query = match url.query() {
+            Some(existing) if !existing.is_empty() => format!("{}&{}", existing, query_string),
+            _ => query_string.to_string(),
+        };
+        url.set_query(Some(&query));
+    }
+
+    let method = Method::from_bytes(outbound.method.as_bytes())?;
+    let mut builder = client.request(method, url).body(body);
+    for (name, value) in outbound.headers {
+        if !HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    let mut response = builder.send().await?;
+    let status = response.status().as_u16();
+
+    // Keep only the start of large bodies rather than buffering all of them
+    let mut kept = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let room = MAX_RESPONSE_BYTES - kept.len();
+        if chunk.len() >= room {
+            kept.extend_from_slice(&chunk[..room]);
+            break;
+        }
+        kept.extend_from_slice(&chunk);
+    }
+
+    Ok(ForwardOutcome {
+        status: Some(status),
+        response: String::from_utf8_lossy(&kept).into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `response` (raw HTTP) to every connection; returns the base URL
+    async fn serve(response: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket.write_all(&response).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn outbound(headers: &HashMap<String, String>) -> Outbound<'_> {
+        Outbound {
+            request_id: "test",
+            method: "POST",
+            headers,
+            query_string: "",
+        }
+    }
+
+    #[test]
+    fn rejects_private_and_metadata_targets() {
+        for target in [
+            "http://127.0.0.1:6379/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.1/",
+            "http://[::1]/",
+            "http://localhost/",
+            "http://metadata.google.internal/",
+        ] {
+            let url = Url::parse(target).unwrap();
+            assert!(check_public_target(&url).is_err(), "{}", target);
+        }
+        let url = Url::parse("https://example.com/hook").unwrap();
+        assert!(check_public_target(&url).is_ok());
+    }
+
+    #[tokio::test]
+    async fn forward_refuses_private_targets() {
+        let client = build_client().unwrap();
+        let headers = HashMap::new();
+        let outcome = forward(
+            &client,
+            "http://169.254.169.254/latest/meta-data",
+            &outbound(&headers),
+            Bytes::new(),
+            false,
+        )
+        .await;
+        assert_eq!(outcome.status, None);
+        assert!(outcome.response.contains("private or local"), "{}", outcome.response);
+    }
+
+    #[tokio::test]
+    async fn replay_never_reaches_a_redirect_target() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        // The "internal" host counts every connection it receives
+        let internal = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let internal_addr = internal.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = internal.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let response = b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nsecret";
+                let _ = socket.write_all(response).await;
+            }
+        });
+        let target = serve(
+            format!(
+                "HTTP/1.1 302 Found\r\nLocation: http://{}/admin\r\nContent-Length: 0\r\n\r\n",
+                internal_addr
+            )
+            .into_bytes(),
+        )
+        .await;
+
+        let client = build_client().unwrap();
+        let headers = HashMap::new();
+        let outcome = forward(&client, &target, &outbound(&headers), Bytes::new(), true).await;
+
+        assert_eq!(outcome.status, Some(302));
+        assert!(!outcome.response.contains("secret"));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn forward_caps_the_stored_response() {
+        let body = "a".repeat(MAX_RESPONSE_BYTES * 2);
+        let mut response =
+            format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+        response.extend_from_slice(body.as_bytes());
+        let target = serve(response).await;
+
+        let client = build_client().unwrap();
+        let headers = HashMap::new();
+        let outcome = forward(&client, &target, &outbound(&headers), Bytes::new(), true).await;
+        assert_eq!(outcome.status, Some(200));
+        assert_eq!(outcome.response.len(), MAX_RESPONSE_BYTES);
+    }
+}
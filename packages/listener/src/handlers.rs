@@ -1,17 +1,58 @@
+use crate::audit::AuditEntry;
+use crate::badge;
+use crate::body;
+use crate::config::{HeaderLimitStrategy, ServerSettings, SessionIdFormat, SessionSettings};
+use crate::diff;
 use crate::error::{AppError, AppResult};
+use crate::export;
+use crate::forwarder;
+use crate::metrics::SseConnectionGuard;
 use crate::models::{
-    CaptureResponse, CreateSessionResponse, FetchRequestsQuery, HealthResponse, RequestsResponse,
-    WebhookRequest,
+    parse_ip_network, BadgeQuery, BodyEncoding, BulkCreateSessionRequest, CaptureResponse,
+    ClearRequestsResponse, CreateSessionRequest, CreateSessionResponse, DiffRequestsQuery,
+    DuplicateCaptureResponse, ExtendSessionRequest, ExtendSessionResponse, FetchRequestsQuery,
+    HealthResponse, ListSessionsQuery, NdjsonStreamQuery, PauseSessionResponse,
+    PausedCaptureResponse, Receipt, ReplayRequest, ReplayResponse, RequestCountResponse,
+    RequestsResponse, Session, SessionInfoResponse, SessionsResponse, StreamRequestsQuery,
+    StreamStatsResponse, SubscriberCountResponse, WebhookRequest, REDIRECT_STATUSES,
 };
-use crate::sse::SseStream;
+use crate::ndjson;
+use crate::negotiate;
+use crate::notify;
+use crate::proto;
+use crate::redact;
+use crate::retry::{self, ForwardJob};
+use crate::session_id;
+use crate::signature;
+use crate::sse::{SseOptions, SseStream};
+use crate::template::{self, TemplateValues};
+use crate::ws;
 use crate::AppState;
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::{
+    dev::RequestHead,
+    http::{header, Method, StatusCode},
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chrono::Utc;
+use futures::stream::{self, StreamExt};
+use percent_encoding::percent_decode_str;
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::{info, instrument};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{debug, info, instrument, warn};
 use uuid::Uuid;
 
+/// Header carrying a session's ingest token
+const INGEST_TOKEN_HEADER: &str = "x-echohook-token";
+
+/// Query parameter carrying a session's ingest token
+const INGEST_TOKEN_PARAM: &str = "echohook_token";
+
+/// Requests fetched from Redis per page when streaming an NDJSON export
+const EXPORT_PAGE_SIZE: usize = 100;
+
 /// Extract real client IP address from request headers
 fn extract_ip_address(req: &HttpRequest) -> String {
     // Priority: X-Real-IP > X-Forwarded-For (first IP) > peer address
@@ -34,6 +75,57 @@ fn extract_ip_address(req: &HttpRequest) -> String {
         .unwrap_or_else(|| "unknown".to_string())
 }
 
+/// Headers set by TLS-terminating proxies (nginx, HAProxy, Traefik, Envoy)
+const TLS_VERSION_HEADERS: &[&str] =
+    &["x-ssl-protocol", "x-tls-version", "x-forwarded-tls-version"];
+const CLIENT_VERIFY_HEADERS: &[&str] = &["x-ssl-client-verify", "x-client-verify"];
+const CLIENT_SUBJECT_HEADERS: &[&str] =
+    &["x-ssl-client-s-dn", "x-ssl-client-subject", "x-client-cert-subject"];
+
+/// Connection metadata for a captured request. TLS details are only known
+/// when a TLS-terminating proxy forwards them as headers.
+struct ConnectionMetadata {
+    scheme: String,
+    http_version: String,
+    peer_address: Option<String>,
+    tls_version: Option<String>,
+    client_cert_verify: Option<String>,
+    client_cert_subject: Option<String>,
+}
+
+fn extract_connection_metadata(req: &HttpRequest) -> ConnectionMetadata {
+    let first_header = |names: &[&str]| {
+        names.iter().find_map(|name| {
+            req.headers()
+                .get(*name)
+                .and_then(|h| h.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+        })
+    };
+
+    // Envoy carries the subject inside `X-Forwarded-Client-Cert` (`Subject="..."`)
+    let xfcc_subject = || {
+        let xfcc = req.headers().get("x-forwarded-client-cert")?.to_str().ok()?;
+        xfcc.split(';').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            key.trim()
+                .eq_ignore_ascii_case("subject")
+                .then(|| value.trim().trim_matches('"').to_string())
+        })
+    };
+
+    ConnectionMetadata {
+        scheme: req.connection_info().scheme().to_string(),
+        http_version: format!("{:?}", req.version()),
+        peer_address: req.peer_addr().map(|addr| addr.to_string()),
+        tls_version: first_header(TLS_VERSION_HEADERS),
+        client_cert_verify: first_header(CLIENT_VERIFY_HEADERS),
+        client_cert_subject: first_header(CLIENT_SUBJECT_HEADERS).or_else(xfcc_subject),
+    }
+}
+
 /// Extract User-Agent header
 fn get_user_agent(req: &HttpRequest) -> String {
     req.headers()
@@ -43,21 +135,350 @@ fn get_user_agent(req: &HttpRequest) -> String {
         .to_string()
 }
 
+/// Extract Content-Type header (empty if absent)
+fn get_content_type(req: &HttpRequest) -> String {
+    req.headers()
+        .get("Content-Type")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// Require `Authorization: Bearer <key>` when an API key is configured
+fn check_api_key(req: &HttpRequest, api_key: Option<&str>) -> AppResult<()> {
+    let Some(api_key) = api_key else {
+        return Ok(());
+    };
+
+    let provided = req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::Unauthorized("Missing API key".to_string()))?;
+
+    if !signature::constant_time_eq(provided.trim().as_bytes(), api_key.as_bytes()) {
+        return Err(AppError::Unauthorized("Invalid API key".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Extract the ingest token from the header or query string
+fn get_ingest_token(req: &HttpRequest) -> Option<String> {
+    if let Some(token) = req
+        .headers()
+        .get(INGEST_TOKEN_HEADER)
+        .and_then(|h| h.to_str().ok())
+    {
+        return Some(token.to_string());
+    }
+
+    // Tokens may contain any character, so compare the decoded value
+    form_urlencoded::parse(req.query_string().as_bytes())
+        .find(|(key, _)| key == INGEST_TOKEN_PARAM)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Split a query string into its parameters. Handles `?a=b`, `?a=` and `?a`;
+/// repeated keys keep every value in order.
+fn query_params(query_string: &str) -> HashMap<String, Vec<String>> {
+    query_string
+        .split('&')
+        .filter(|s| !s.is_empty())
+        .map(|param| {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            (key.to_string(), value.to_string())
+        })
+        .fold(HashMap::<String, Vec<String>>::new(), |mut params, (key, value)| {
+            params.entry(key).or_default().push(value);
+            params
+        })
+}
+
+/// Require the session's ingest token, if it has one
+fn check_ingest_token(req: &HttpRequest, session: &Session) -> AppResult<()> {
+    let Some(expected) = &session.ingest_token else {
+        return Ok(());
+    };
+
+    let valid = get_ingest_token(req)
+        .is_some_and(|token| signature::constant_time_eq(token.as_bytes(), expected.as_bytes()));
+    if !valid {
+        return Err(AppError::InvalidIngestToken);
+    }
+
+    Ok(())
+}
+
+/// Remove the ingest token parameter so it is never stored or forwarded
+fn strip_ingest_token(query_string: &str) -> String {
+    query_string
+        .split('&')
+        .filter(|param| {
+            form_urlencoded::parse(param.as_bytes())
+                .next()
+                .is_none_or(|(key, _)| key != INGEST_TOKEN_PARAM)
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Count an ingest against the session's fixed window, failing once the
+/// configured per-session limit is exceeded
+async fn check_session_rate_limit(state: &AppState, session_id: &str) -> AppResult<()> {
+    let limits = &state.settings.rate_limit;
+    if limits.per_session == 0 {
+        return Ok(());
+    }
+
+    let window = limits.session_window_seconds.max(1);
+    let (count, retry_after) = state.redis.hit_session_rate_limit(session_id, window).await?;
+    if count > limits.per_session {
+        return Err(AppError::RateLimitExceeded {
+            message: format!(
+                "Maximum {} requests per {} seconds for this session exceeded",
+                limits.per_session, window
+            ),
+            retry_after_seconds: Some(retry_after),
+            limit: limits.per_session,
+        });
+    }
+
+    Ok(())
+}
+
+/// Hash identifying a delivery for deduplication: method, path and raw body
+fn request_fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut data = Vec::with_capacity(method.len() + path.len() + body.len() + 2);
+    data.extend_from_slice(method.as_bytes());
+    data.push(b'\n');
+    data.extend_from_slice(path.as_bytes());
+    data.push(b'\n');
+    data.extend_from_slice(body);
+    signature::sha256_hex(&data)
+}
+
+/// The request-target exactly as received (origin or absolute form), minus
+/// the ingest token; `query_string` is the already stripped query
+fn raw_request_target(req: &HttpRequest, query_string: &str) -> String {
+    let uri = req.uri();
+    let mut target = match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) => format!("{}://{}", scheme, authority),
+        _ => String::new(),
+    };
+    target.push_str(uri.path());
+    if uri.query().is_some() {
+        target.push('?');
+        target.push_str(query_string);
+    }
+    target
+}
+
+/// Session ID named by the leftmost `Host` label under `domain`, e.g. `abc123`
+/// for `abc123.hooks.example.com` with domain `hooks.example.com`. Only a
+/// single label is accepted, and the bare domain names no session.
+pub fn subdomain_session_id(head: &RequestHead, domain: &str) -> Option<String> {
+    let host = head
+        .headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| head.uri.host())?;
+    let host = host.split(':').next().unwrap_or(host).to_ascii_lowercase();
+    let domain = domain.trim().trim_matches('.').to_ascii_lowercase();
+
+    let label = host.strip_suffix(domain.as_str())?.strip_suffix('.')?;
+    (!label.is_empty() && !label.contains('.')).then(|| label.to_string())
+}
+
+/// Tags from the configured query parameter (one per value) and header
+/// (comma-separated), without blanks or duplicates
+fn request_tags(
+    req: &HttpRequest,
+    query_params: &HashMap<String, Vec<String>>,
+    settings: &ServerSettings,
+) -> Vec<String> {
+    let from_query = query_params
+        .get(&settings.tag_query_param)
+        .into_iter()
+        .flatten()
+        .map(String::as_str);
+    let from_header = req
+        .headers()
+        .get_all(settings.tag_header.as_str())
+        .filter(|_| !settings.tag_header.is_empty())
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in from_query.chain(from_header).map(str::trim) {
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Longest ingest sub-path (after the session ID) accepted, in bytes
+const MAX_SUBPATH_BYTES: usize = 2048;
+
+/// Normalize the ingest path after the session ID for storage: empty and `.`
+/// segments are dropped, `..` is resolved without climbing above the
+/// session, and control characters are replaced so the value is safe to
+/// display. Sub-paths over `MAX_SUBPATH_BYTES` are rejected.
+fn sanitize_subpath(tail: &str) -> AppResult<String> {
+    if tail.len() > MAX_SUBPATH_BYTES {
+        return Err(AppError::SubpathTooLong {
+            size: tail.len(),
+            limit: MAX_SUBPATH_BYTES,
+        });
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in tail.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Ok(segments
+        .join("/")
+        .chars()
+        .map(|c| if c.is_control() { char::REPLACEMENT_CHARACTER } else { c })
+        .collect())
+}
+
+/// Trailer field names announced by the client in the `Trailer` header
+fn declared_trailers(req: &HttpRequest) -> Vec<String> {
+    req.headers()
+        .get_all(header::TRAILER)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Count an ingest against the client's fixed window, failing once the
+/// configured per-IP limit is exceeded
+async fn check_ip_rate_limit(state: &AppState, ip_address: &str) -> AppResult<()> {
+    let limits = &state.settings.rate_limit;
+    if limits.per_ip == 0 {
+        return Ok(());
+    }
+
+    let window = limits.window_seconds.max(1);
+    let (count, retry_after) = state.redis.hit_ip_rate_limit(ip_address, window).await?;
+    if count > limits.per_ip {
+        return Err(AppError::RateLimitExceeded {
+            message: format!(
+                "Maximum {} requests per {} seconds from this IP exceeded",
+                limits.per_ip, window
+            ),
+            retry_after_seconds: Some(retry_after),
+            limit: limits.per_ip,
+        });
+    }
+
+    Ok(())
+}
+
+/// Empty `204` answering a CORS preflight, echoing the requested origin and headers
+fn preflight_response(req: &HttpRequest) -> HttpResponse {
+    let header_value = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string)
+    };
+
+    HttpResponse::NoContent()
+        .insert_header((
+            "Access-Control-Allow-Origin",
+            header_value("Origin").unwrap_or_else(|| "*".to_string()),
+        ))
+        .insert_header((
+            "Access-Control-Allow-Methods",
+            "GET, POST, PUT, PATCH, DELETE, HEAD, OPTIONS",
+        ))
+        .insert_header((
+            "Access-Control-Allow-Headers",
+            header_value("Access-Control-Request-Headers").unwrap_or_else(|| "*".to_string()),
+        ))
+        .insert_header(("Access-Control-Max-Age", "3600"))
+        .insert_header(("Vary", "Origin"))
+        .finish()
+}
+
 /// Validate UUID format
-fn validate_uuid(session_id: &str) -> AppResult<Uuid> {
-    Uuid::parse_str(session_id).map_err(|_| AppError::InvalidUuid(session_id.to_string()))
+fn validate_uuid(id: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(id).map_err(|_| AppError::InvalidUuid(id.to_string()))
+}
+
+/// Validate a session ID against the configured ID format
+fn validate_session_id(session_id: &str, settings: &SessionSettings) -> AppResult<()> {
+    if session_id::is_valid(session_id, settings) {
+        return Ok(());
+    }
+    Err(match settings.id_format {
+        SessionIdFormat::Uuid => AppError::InvalidUuid(session_id.to_string()),
+        SessionIdFormat::Short => AppError::InvalidSessionId(session_id.to_string()),
+    })
+}
+
+/// Create a session with the default TTL and options (first-ingest and
+/// dead-letter sessions)
+async fn create_default_session(state: &AppState, session_id: &str) -> AppResult<Session> {
+    state
+        .redis
+        .create_session(
+            session_id,
+            state.settings.session.ttl_seconds,
+            &CreateSessionRequest::default(),
+            state.settings.session.max_sessions,
+        )
+        .await
+}
+
+/// Attempts at drawing an unused short session ID before giving up
+const SESSION_ID_ATTEMPTS: usize = 5;
+
+/// Generate a session ID, retrying short IDs that collide with a live session
+async fn new_session_id(state: &AppState) -> AppResult<String> {
+    let settings = &state.settings.session;
+    if settings.id_format == SessionIdFormat::Uuid {
+        return Ok(session_id::generate(settings));
+    }
+    for _ in 0..SESSION_ID_ATTEMPTS {
+        let session_id = session_id::generate(settings);
+        if !state.redis.session_exists(&session_id).await? {
+            return Ok(session_id);
+        }
+        warn!(session_id = %session_id, "Short session ID collided, retrying");
+    }
+    Err(AppError::Internal(
+        "Could not generate an unused session ID; increase SESSION_SHORT_ID_LENGTH".to_string(),
+    ))
 }
 
 /// Health check endpoint
 #[instrument(skip(state))]
 pub async fn health_check_handler(state: web::Data<AppState>) -> AppResult<HttpResponse> {
+    let ping_started = Instant::now();
     let redis_healthy = state.redis.health_check().await.unwrap_or(false);
+    let redis_latency_ms =
+        redis_healthy.then(|| ping_started.elapsed().as_secs_f64() * 1000.0);
     let sse_channels = state.redis.get_sse_channel_count().await;
+    let active_sessions = if redis_healthy {
+        state.redis.count_active_sessions().await.ok()
+    } else {
+        None
+    };
 
-    let uptime = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    let uptime = state.start_time.elapsed().as_secs();
 
     let response = HealthResponse {
         status: if redis_healthy { "healthy" } else { "degraded" }.to_string(),
@@ -65,176 +486,1272 @@ pub async fn health_check_handler(state: web::Data<AppState>) -> AppResult<HttpR
         version: env!("CARGO_PKG_VERSION").to_string(),
         uptime_seconds: uptime,
         sse_channels,
+        redis_latency_ms,
+        active_sessions,
     };
 
     Ok(HttpResponse::Ok().json(response))
 }
 
-/// Create a new webhook session
+/// Hand-written OpenAPI 3.0 document; update it with every route change in `main`
+const OPENAPI_SPEC: &str = include_str!("openapi.json");
+
+/// Serve the OpenAPI description of this API
+pub async fn openapi_handler() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(OPENAPI_SPEC)
+}
+
+/// Prometheus metrics endpoint
 #[instrument(skip(state))]
-pub async fn create_session_handler(state: web::Data<AppState>) -> AppResult<HttpResponse> {
-    let session_id = Uuid::now_v7().to_string();
-    let ttl = state.settings.session.ttl_seconds;
+pub async fn metrics_handler(state: web::Data<AppState>) -> AppResult<HttpResponse> {
+    let metrics = &state.metrics;
 
-    let session = state.redis.create_session(&session_id, ttl).await?;
+    metrics
+        .sse_channels
+        .set(state.redis.get_sse_channel_count().await as i64);
+    metrics
+        .active_sessions
+        .set(state.redis.count_active_sessions().await? as i64);
 
-    let base_url = &state.settings.server.listen_url;
-    let response = CreateSessionResponse {
-        session_id: session.session_id.clone(),
-        ingestion_url: format!("{}/i/{}", base_url, session.session_id),
-        stream_url: format!("{}/s/{}", base_url, session.session_id),
-        requests_url: format!("{}/r/{}", base_url, session.session_id),
-        expires_at: session.expires_at,
-    };
+    let body = metrics
+        .render()
+        .map_err(|e| AppError::Internal(format!("Failed to encode metrics: {}", e)))?;
 
-    info!(session_id = %session.session_id, "Created new session");
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body))
+}
 
-    Ok(HttpResponse::Created().json(response))
+/// Decode an optional JSON request body: an empty body means "no options",
+/// anything else must decode as `T` (unknown fields included) or it is a 400,
+/// so a malformed option is never silently replaced by the default
+fn optional_json<T: DeserializeOwned + Default>(body: &[u8]) -> AppResult<T> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid request body: {}", e)))
 }
 
-/// Ingest a webhook request (base path without tail)
-#[instrument(skip(state, req, body), fields(method = %req.method(), path = %req.path()))]
-pub async fn ingest_webhook_handler_base(
-    path: web::Path<String>,
+/// Create a new webhook session
+#[instrument(skip(state, req, body))]
+pub async fn create_session_handler(
     req: HttpRequest,
     body: web::Bytes,
     state: web::Data<AppState>,
 ) -> AppResult<HttpResponse> {
-    let session_id = path.into_inner();
-    ingest_webhook_impl(session_id, req, body, state).await
+    check_api_key(&req, state.settings.server.api_key.as_deref())?;
+
+    let options: CreateSessionRequest = optional_json(&body)?;
+
+    validate_session_options(&options, &state)?;
+
+    let session_id = new_session_id(&state).await?;
+    let ttl = state.settings.session.ttl_seconds;
+
+    let session = state
+        .redis
+        .create_session(&session_id, ttl, &options, state.settings.session.max_sessions)
+        .await?;
+
+    let response = session_urls(&state.settings.server.listen_url, &session, true);
+
+    info!(session_id = %session.session_id, "Created new session");
+
+    Ok(HttpResponse::Created().json(response))
 }
 
-/// Ingest a webhook request (with tail path)
-#[instrument(skip(state, req, body), fields(method = %req.method(), path = %req.path()))]
-pub async fn ingest_webhook_handler(
-    path: web::Path<(String, String)>,
+/// Most sessions a single bulk create may return
+const MAX_BULK_SESSIONS: usize = 100;
+
+/// Create several sessions with the same options in one call
+#[instrument(skip(req, body, state))]
+pub async fn bulk_create_sessions_handler(
     req: HttpRequest,
-    body: web::Bytes,
+    body: web::Json<BulkCreateSessionRequest>,
     state: web::Data<AppState>,
 ) -> AppResult<HttpResponse> {
-    let (session_id, _tail) = path.into_inner();
-    ingest_webhook_impl(session_id, req, body, state).await
+    check_api_key(&req, state.settings.server.api_key.as_deref())?;
+
+    let BulkCreateSessionRequest { count, options } = body.into_inner();
+    if count == 0 || count > MAX_BULK_SESSIONS {
+        return Err(AppError::BadRequest(format!(
+            "count must be between 1 and {}",
+            MAX_BULK_SESSIONS
+        )));
+    }
+
+    validate_session_options(&options, &state)?;
+
+    let mut session_ids: Vec<String> = Vec::with_capacity(count);
+    while session_ids.len() < count {
+        let session_id = new_session_id(&state).await?;
+        if !session_ids.contains(&session_id) {
+            session_ids.push(session_id);
+        }
+    }
+
+    let sessions = state
+        .redis
+        .create_sessions(
+            &session_ids,
+            state.settings.session.ttl_seconds,
+            &options,
+            state.settings.session.max_sessions,
+        )
+        .await?;
+
+    let base_url = &state.settings.server.listen_url;
+    let response: Vec<CreateSessionResponse> =
+        sessions.iter().map(|session| session_urls(base_url, session, true)).collect();
+
+    info!(count = response.len(), "Created sessions in bulk");
+
+    Ok(HttpResponse::Created().json(response))
 }
 
-/// Internal implementation for webhook ingestion
-async fn ingest_webhook_impl(
-    session_id: String,
-    req: HttpRequest,
-    body: web::Bytes,
-    state: web::Data<AppState>,
-) -> AppResult<HttpResponse> {
+/// Validate a URL the server will send requests to: absolute http(s), and
+/// not a private or local address unless `ALLOW_PRIVATE_TARGETS` is set
+fn check_outbound_target(
+    field: &str,
+    url: &str,
+    settings: &ServerSettings,
+) -> AppResult<reqwest::Url> {
+    let target = forwarder::validate_target_url(field, url).map_err(AppError::BadRequest)?;
+    if !settings.allow_private_targets {
+        forwarder::check_public_target(&target).map_err(AppError::BadRequest)?;
+    }
+    Ok(target)
+}
 
-    // Validate UUID format
-    validate_uuid(&session_id)?;
+/// Reject create options that are malformed or unsupported by this server
+fn validate_session_options(options: &CreateSessionRequest, state: &AppState) -> AppResult<()> {
+    if options.signed_receipt && state.settings.server.receipt_signing_key.is_none() {
+        return Err(AppError::BadRequest(
+            "Signed receipts are not enabled on this server".to_string(),
+        ));
+    }
 
-    // Check body size
-    let max_size = state.settings.server.max_body_size;
-    if body.len() > max_size {
-        return Err(AppError::PayloadTooLarge {
-            size: body.len(),
-            limit: max_size,
-        });
+    if let Some(forward_url) = &options.forward_url {
+        check_outbound_target("forward_url", forward_url, &state.settings.server)?;
     }
 
-    // Check if session exists
-    if !state.redis.session_exists(&session_id).await? {
-        return Err(AppError::SessionNotFound);
+    if options.ingest_token.as_deref().is_some_and(str::is_empty) {
+        return Err(AppError::BadRequest("ingest_token must not be empty".to_string()));
     }
 
-    // Check rate limit (max requests per session)
-    let current_count = state.redis.get_request_count(&session_id).await?;
-    if current_count >= state.settings.session.max_requests_per_session {
-        return Err(AppError::RateLimitExceeded(format!(
-            "Maximum {} requests per session exceeded",
-            state.settings.session.max_requests_per_session
-        )));
+    if options.signing_secret.as_deref().is_some_and(str::is_empty) {
+        return Err(AppError::BadRequest("signing_secret must not be empty".to_string()));
     }
 
-    // Build request data
-    let method = req.method().to_string();
-    let path = req.path().to_string();
-    let query_params = req.query_string().to_string();
+    if let Some(header) = &options.signature_header {
+        if actix_web::http::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+            return Err(AppError::BadRequest(format!(
+                "Invalid signature_header: {}",
+                header
+            )));
+        }
+    }
 
-    let mut headers = HashMap::new();
-    for (key, value) in req.headers() {
-        if let Ok(v) = value.to_str() {
-            headers.insert(key.as_str().to_string(), v.to_string());
+    if let Some(methods) = &options.allowed_methods {
+        if methods.is_empty() {
+            return Err(AppError::BadRequest("allowed_methods must not be empty".to_string()));
+        }
+        if let Some(invalid) = methods.iter().find(|m| Method::from_bytes(m.as_bytes()).is_err()) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid method in allowed_methods: {}",
+                invalid
+            )));
         }
     }
 
-    let body_str = String::from_utf8_lossy(&body).to_string();
-    let ip_address = extract_ip_address(&req);
-    let user_agent = get_user_agent(&req);
-    let timestamp = Utc::now().to_rfc3339();
-    let request_id = Uuid::now_v7().to_string();
+    let header_lists = [
+        ("store_headers_allowlist", &options.store_headers_allowlist),
+        ("store_headers_blocklist", &options.store_headers_blocklist),
+    ];
+    for (option, names) in header_lists {
+        let Some(names) = names else {
+            continue;
+        };
+        if names.is_empty() {
+            return Err(AppError::BadRequest(format!("{} must not be empty", option)));
+        }
+        if let Some(invalid) = names
+            .iter()
+            .find(|name| header::HeaderName::from_bytes(name.as_bytes()).is_err())
+        {
+            return Err(AppError::BadRequest(format!(
+                "Invalid header name in {}: {}",
+                option, invalid
+            )));
+        }
+    }
 
-    // handle ?a=b , ?a= and ?a
-    let query_params = query_params
-        .split('&')
-        .filter(|s| !s.is_empty())
-        .map(|param| {
-            let (key, value) = param.split_once('=').unwrap_or((param, ""));
-            (key.to_string(), value.to_string())
-        })
-        .collect::<HashMap<String, String>>();
+    if let Some(networks) = &options.allowed_ips {
+        if networks.is_empty() {
+            return Err(AppError::BadRequest("allowed_ips must not be empty".to_string()));
+        }
+        if let Some(invalid) = networks.iter().find(|n| parse_ip_network(n).is_none()) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid CIDR in allowed_ips: {}",
+                invalid
+            )));
+        }
+    }
 
-    let webhook_request = WebhookRequest {
-        request_id: request_id.clone(),
-        method,
-        path,
-        query_params,
-        headers,
-        body: body_str,
-        ip_address,
-        user_agent,
-        timestamp,
-        content_length: body.len(),
-    };
+    if let Some(schema) = &options.body_schema {
+        jsonschema::validator_for(schema)
+            .map_err(|e| AppError::BadRequest(format!("Invalid body_schema: {}", e)))?;
+    } else if options.reject_invalid_body {
+        return Err(AppError::BadRequest("reject_invalid_body requires body_schema".to_string()));
+    }
 
-    // Save to Redis
-    let ttl = state.settings.session.ttl_seconds;
-    state
-        .redis
-        .save_request(&session_id, &webhook_request, ttl)
-        .await?;
+    if let Some(patch) = &options.body_transform {
+        serde_json::from_value::<json_patch::Patch>(patch.clone())
+            .map_err(|e| AppError::BadRequest(format!("Invalid body_transform: {}", e)))?;
+    }
 
-    Ok(HttpResponse::Ok().json(CaptureResponse {
-        status: "captured".to_string(),
-        request_id,
-    }))
-}
+    match (&options.proto_descriptor, &options.proto_message_type) {
+        (Some(descriptor), Some(message_type)) => {
+            proto::message_descriptor(descriptor, message_type)
+                .map_err(|e| AppError::BadRequest(format!("Invalid proto_descriptor: {}", e)))?;
+        }
+        (None, None) => {}
+        _ => {
+            return Err(AppError::BadRequest(
+                "proto_descriptor and proto_message_type must be set together".to_string(),
+            ));
+        }
+    }
 
-/// Stream requests via SSE
-#[instrument(skip(state, req))]
-pub async fn stream_requests_handler(
-    path: web::Path<String>,
-    req: HttpRequest,
-    state: web::Data<AppState>,
-) -> AppResult<HttpResponse> {
-    let session_id = path.into_inner();
+    if options.dedupe_window_seconds == Some(0) {
+        return Err(AppError::BadRequest("dedupe_window_seconds must be positive".to_string()));
+    }
 
-    // Validate UUID format
-    validate_uuid(&session_id)?;
+    if let Some(template) = &options.response_template {
+        template::validate_template(template).map_err(AppError::BadRequest)?;
+    }
 
-    // Check if session exists
-    if !state.redis.session_exists(&session_id).await? {
-        return Err(AppError::SessionNotFound);
+    if let Some(headers) = &options.response_headers {
+        for (name, value) in headers {
+            let name = header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| AppError::BadRequest(format!("Invalid response header: {}", name)))?;
+            if forwarder::HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+                return Err(AppError::BadRequest(format!(
+                    "Response header cannot be overridden: {}",
+                    name
+                )));
+            }
+            if header::HeaderValue::from_str(value).is_err() {
+                return Err(AppError::BadRequest(format!(
+                    "Invalid value for response header {}",
+                    name
+                )));
+            }
+            template::validate_template(value).map_err(AppError::BadRequest)?;
+        }
     }
 
-    info!(session_id = %session_id, "Client connected to SSE stream");
+    if let Some(redirect_url) = &options.response_redirect_url {
+        if options.response_template.is_some() {
+            return Err(AppError::BadRequest(
+                "response_redirect_url and response_template are mutually exclusive".to_string(),
+            ));
+        }
+        forwarder::validate_target_url("response_redirect_url", redirect_url)
+            .map_err(AppError::BadRequest)?;
+    }
 
-    // Get the SSE receiver before creating the stream
-    info!(session_id = %session_id, "Requesting SSE channel from Redis client");
-    let receiver = state.redis.get_sse_channel(&session_id).await;
-    info!(
-        session_id = %session_id,
-        receiver_len = receiver.len(),
-        "Got SSE receiver, creating stream"
-    );
-    
-    // Create SSE stream with the initialized receiver
-    let sse_stream = SseStream::new(receiver, session_id.clone());
-    info!(session_id = %session_id, "SSE stream created, starting to serve events");
+    if options.echo_body
+        && (options.response_template.is_some() || options.response_redirect_url.is_some())
+    {
+        return Err(AppError::BadRequest(
+            "echo_body is mutually exclusive with response_template and response_redirect_url"
+                .to_string(),
+        ));
+    }
+
+    if let Some(status) = options.response_redirect_status {
+        if options.response_redirect_url.is_none() {
+            return Err(AppError::BadRequest(
+                "response_redirect_status requires response_redirect_url".to_string(),
+            ));
+        }
+        if !REDIRECT_STATUSES.contains(&status) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid response_redirect_status: {} (expected one of 301, 302, 303, 307, 308)",
+                status
+            )));
+        }
+    }
+
+    if let Some(notify_url) = &options.notify_webhook_url {
+        check_outbound_target("notify_webhook_url", notify_url, &state.settings.server)?;
+    }
+
+    if let Some(methods) = &options.notify_on {
+        if options.notify_webhook_url.is_none() {
+            return Err(AppError::BadRequest("notify_on requires notify_webhook_url".to_string()));
+        }
+        if let Some(invalid) = methods.iter().find(|m| Method::from_bytes(m.as_bytes()).is_err()) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid method in notify_on: {}",
+                invalid
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Build a session's public URLs; the ingest token is only put into
+/// `ingestion_url` when `include_token` is set
+fn session_urls(base_url: &str, session: &Session, include_token: bool) -> CreateSessionResponse {
+    let ingestion_url = match session.ingest_token.as_ref().filter(|_| include_token) {
+        Some(token) => format!(
+            "{}/i/{}?{}",
+            base_url,
+            session.session_id,
+            form_urlencoded::Serializer::new(String::new())
+                .append_pair(INGEST_TOKEN_PARAM, token)
+                .finish()
+        ),
+        None => format!("{}/i/{}", base_url, session.session_id),
+    };
+    CreateSessionResponse {
+        session_id: session.session_id.clone(),
+        ingestion_url,
+        stream_url: format!("{}/s/{}", base_url, session.session_id),
+        requests_url: format!("{}/r/{}", base_url, session.session_id),
+        expires_at: session.expires_at.clone(),
+    }
+}
+
+/// Recover an existing session's URLs and request count (requires the API key
+/// when configured). The session ID is public, so the ingest token is only
+/// included in the ingestion URL when an API key was presented and checked.
+#[instrument(skip(req, state))]
+pub async fn get_session_handler(
+    req: HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    check_api_key(&req, state.settings.server.api_key.as_deref())?;
+
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    let session = state
+        .redis
+        .get_session(&session_id)
+        .await?
+        .ok_or(AppError::SessionNotFound)?;
+    let request_count = state.redis.get_request_count(&session_id).await?;
+
+    let authenticated = state.settings.server.api_key.is_some();
+    Ok(HttpResponse::Ok().json(SessionInfoResponse {
+        session: session_urls(&state.settings.server.listen_url, &session, authenticated),
+        request_count,
+        paused: session.paused,
+    }))
+}
+
+/// List active sessions with metadata (requires the API key when configured)
+#[instrument(skip(req, state))]
+pub async fn list_sessions_handler(
+    req: HttpRequest,
+    query: web::Query<ListSessionsQuery>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    check_api_key(&req, state.settings.server.api_key.as_deref())?;
+
+    let (sessions, total) = state
+        .redis
+        .list_sessions(query.validated_limit(), query.offset)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(SessionsResponse {
+        total_sessions: total,
+        sessions,
+    }))
+}
+
+/// Clean up tracking and index entries left behind by expired keys
+/// (requires the API key when configured)
+#[instrument(skip(req, state))]
+pub async fn garbage_collect_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    check_api_key(&req, state.settings.server.api_key.as_deref())?;
+
+    let report = state.redis.garbage_collect().await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Extend a session's TTL
+#[instrument(skip(state, body))]
+pub async fn extend_session_handler(
+    path: web::Path<String>,
+    body: web::Bytes,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    let options: ExtendSessionRequest = optional_json(&body)?;
+    let settings = &state.settings.session;
+    let ttl = options
+        .ttl_seconds
+        .unwrap_or(settings.ttl_seconds)
+        .clamp(1, settings.max_ttl_seconds.max(settings.ttl_seconds));
+
+    let expires_at = state
+        .redis
+        .extend_session(&session_id, ttl, settings.request_ttl_seconds == 0)
+        .await?
+        .ok_or(AppError::SessionNotFound)?;
+
+    info!(session_id = %session_id, expires_at = %expires_at, "Extended session");
+
+    Ok(HttpResponse::Ok().json(ExtendSessionResponse {
+        session_id,
+        expires_at,
+    }))
+}
+
+/// Stop capturing into a session without deleting it
+#[instrument(skip(state))]
+pub async fn pause_session_handler(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    set_session_paused(path.into_inner(), true, state).await
+}
+
+/// Resume capturing into a paused session
+#[instrument(skip(state))]
+pub async fn resume_session_handler(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    set_session_paused(path.into_inner(), false, state).await
+}
+
+async fn set_session_paused(
+    session_id: String,
+    paused: bool,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    if !state.redis.set_session_paused(&session_id, paused).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    info!(session_id = %session_id, paused = paused, "Updated session capture state");
+
+    Ok(HttpResponse::Ok().json(PauseSessionResponse { session_id, paused }))
+}
+
+/// Ingest a webhook request (base path without tail)
+#[instrument(skip(state, req, body), fields(method = %req.method(), path = %req.path()))]
+pub async fn ingest_webhook_handler_base(
+    path: web::Path<String>,
+    req: HttpRequest,
+    body: web::Payload,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+    ingest_webhook(session_id, String::new(), req, body, state).await
+}
+
+/// Ingest a webhook request (with tail path)
+#[instrument(skip(state, req, body), fields(method = %req.method(), path = %req.path()))]
+pub async fn ingest_webhook_handler(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    body: web::Payload,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let (session_id, tail) = path.into_inner();
+    ingest_webhook(session_id, tail, req, body, state).await
+}
+
+/// Ingest a request addressed to `{session_id}.{SUBDOMAIN_ROUTING_DOMAIN}`,
+/// whatever its path
+#[instrument(skip(state, req, body), fields(method = %req.method(), path = %req.path()))]
+pub async fn ingest_webhook_subdomain_handler(
+    req: HttpRequest,
+    body: web::Payload,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = state
+        .settings
+        .server
+        .subdomain_routing_domain
+        .as_deref()
+        .and_then(|domain| subdomain_session_id(req.head(), domain))
+        .ok_or(AppError::SessionNotFound)?;
+    let tail = req.path().to_string();
+    ingest_webhook(session_id, tail, req, body, state).await
+}
+
+/// Ingest a webhook request and record the attempt, whatever its outcome,
+/// in the audit log
+async fn ingest_webhook(
+    session_id: String,
+    tail: String,
+    req: HttpRequest,
+    payload: web::Payload,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let mut audit = AuditEntry {
+        timestamp: Utc::now().to_rfc3339(),
+        session_id: session_id.clone(),
+        request_id: None,
+        ip: extract_ip_address(&req),
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+        content_length: 0,
+        status: 0,
+        outcome: "captured",
+    };
+
+    let result =
+        ingest_webhook_impl(session_id, &tail, req, payload, state.clone(), &mut audit).await;
+    match &result {
+        Ok(response) => audit.status = response.status().as_u16(),
+        Err(e) => {
+            audit.status = e.status_code().as_u16();
+            audit.outcome = e.error_code();
+        }
+    }
+    state.audit.record(&audit);
+
+    result
+}
+
+/// Internal implementation for webhook ingestion. `tail` is the path after
+/// the session ID; `audit` is filled in with what is learned about the
+/// request along the way.
+async fn ingest_webhook_impl(
+    mut session_id: String,
+    tail: &str,
+    req: HttpRequest,
+    payload: web::Payload,
+    state: web::Data<AppState>,
+    audit: &mut AuditEntry,
+) -> AppResult<HttpResponse> {
+    let _timer = state.metrics.ingest_duration.start_timer();
+    let received_at = audit.timestamp.clone();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+    let subpath = sanitize_subpath(tail)?;
+
+    // Per-client-IP rate limit, checked before any session data is touched
+    let ip_address = extract_ip_address(&req);
+    check_ip_rate_limit(&state, &ip_address).await?;
+
+    // Read the raw (still encoded) body, enforcing the size limit for its
+    // content type (or the global one) as it streams in
+    let (max_size, applied_limit) = state
+        .settings
+        .server
+        .body_size_limit(&get_content_type(&req));
+    let declared_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<usize>().ok());
+    let body = body::read_payload(payload, declared_length, max_size, applied_limit).await?;
+    audit.content_length = body.len();
+
+    // Check if session exists, creating it on first use if configured, or
+    // capturing into the dead-letter session instead
+    let mut original_session_id = None;
+    let session = match state.redis.get_session(&session_id).await? {
+        Some(session) => session,
+        None if state.settings.session.auto_create_sessions => {
+            let session = create_default_session(&state, &session_id).await?;
+            info!(session_id = %session_id, "Created session on first ingest");
+            session
+        }
+        None => match &state.settings.session.dead_letter_session {
+            Some(dead_letter) if *dead_letter != session_id => {
+                let session = match state.redis.get_session(dead_letter).await? {
+                    Some(session) => session,
+                    None => {
+                        let session = create_default_session(&state, dead_letter).await?;
+                        info!(session_id = %dead_letter, "Created dead-letter session");
+                        session
+                    }
+                };
+                info!(
+                    session_id = %session_id,
+                    dead_letter = %dead_letter,
+                    "Unknown session, capturing into the dead-letter session"
+                );
+                original_session_id = Some(std::mem::replace(&mut session_id, dead_letter.clone()));
+                session
+            }
+            _ => return Err(AppError::SessionNotFound),
+        },
+    };
+
+    // Only accept clients from the session's allowed networks, if configured
+    if !session.allowed_ips.is_empty() {
+        let allowed = ip_address
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| session.allowed_ips.iter().any(|network| network.contains(&ip)));
+        if !allowed {
+            return Err(AppError::IpNotAllowed(ip_address));
+        }
+    }
+
+    // Answer OPTIONS as a CORS preflight rather than capturing noise, unless
+    // the session opted in. Preflights can't carry the ingest token, so this
+    // runs before the token check.
+    if req.method() == Method::OPTIONS && !session.capture_options_requests {
+        audit.outcome = "preflight";
+        return Ok(preflight_response(&req));
+    }
+
+    if !session.allowed_methods.is_empty()
+        && !session.allowed_methods.iter().any(|m| m == req.method().as_str())
+    {
+        return Err(AppError::MethodNotAllowed {
+            method: req.method().to_string(),
+            allowed: session.allowed_methods,
+        });
+    }
+
+    check_ingest_token(&req, &session)?;
+
+    // Acknowledge but don't store or broadcast while capture is paused
+    if session.paused {
+        audit.outcome = "paused";
+        return Ok(negotiate::ack(
+            HttpResponse::Accepted(),
+            &req,
+            &PausedCaptureResponse {
+                status: "paused".to_string(),
+            },
+        ));
+    }
+
+    // Verify the provider signature over the raw body, if configured
+    let signature_valid = session.signing_secret.as_deref().map(|secret| {
+        req.headers()
+            .get(session.signature_header.as_str())
+            .and_then(|h| h.to_str().ok())
+            .is_some_and(|value| signature::verify_webhook_signature(secret, value, &body))
+    });
+    if session.reject_invalid_signatures && signature_valid == Some(false) {
+        return Err(AppError::InvalidSignature);
+    }
+
+    check_session_rate_limit(&state, &session_id).await?;
+
+    // Check the lifetime cap (max requests per session)
+    let max_requests = state.settings.session.max_requests_per_session;
+    if max_requests > 0 && state.redis.get_request_count(&session_id).await? >= max_requests {
+        return Err(AppError::RateLimitExceeded {
+            message: format!("Maximum {} requests per session exceeded", max_requests),
+            // The cap only lifts once requests are deleted or the session expires
+            retry_after_seconds: None,
+            limit: max_requests as u64,
+        });
+    }
+
+    // Build request data
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let query_string = strip_ingest_token(req.query_string());
+    let raw_target = raw_request_target(&req, &query_string);
+
+    let (headers, headers_truncated) = collect_headers(&req, &state.settings.server)?;
+
+    // Undo Content-Encoding (gzip/deflate/br) so the stored body is readable
+    let content_encoding = req
+        .headers()
+        .get("Content-Encoding")
+        .and_then(|h| h.to_str().ok())
+        .map(str::trim)
+        .filter(|e| !e.is_empty() && !e.eq_ignore_ascii_case("identity"));
+    let mut decode_failed = false;
+    let (decoded_body, original_encoding) = match content_encoding {
+        Some(encoding) => match body::decode_content_encoding(encoding, &body, max_size) {
+            Ok(decoded) => (Some(decoded), Some(encoding.to_ascii_lowercase())),
+            Err(e) => {
+                warn!(
+                    session_id = %session_id,
+                    content_encoding = %encoding,
+                    error = %e,
+                    "Failed to decode request body, storing raw bytes"
+                );
+                decode_failed = true;
+                (None, None)
+            }
+        },
+        None => (None, None),
+    };
+    let full_body: &[u8] = decoded_body.as_deref().unwrap_or(&body);
+
+    // Keep only a prefix of bodies over the truncation threshold
+    let truncate_bytes = state.settings.server.body_truncate_bytes;
+    let stored_body = match truncate_bytes {
+        0 => full_body,
+        limit => body::truncate_body(full_body, limit),
+    };
+    let truncated = stored_body.len() < full_body.len();
+
+    let content_type = get_content_type(&req);
+    let is_protobuf = proto::is_protobuf_content_type(&content_type);
+    // Without a Content-Type, guess one from the body and parse accordingly;
+    // the stored `content_type` stays as sent
+    let detected_content_type = match content_type.trim() {
+        "" => body::sniff_content_type(full_body),
+        _ => None,
+    };
+    let effective_content_type = detected_content_type.unwrap_or(&content_type);
+
+    // Keep text bodies readable; preserve binary payloads (including
+    // protobuf that happens to be valid UTF-8) losslessly as base64
+    let (body_str, body_encoding) = match std::str::from_utf8(stored_body) {
+        Ok(text) if !decode_failed && !is_protobuf => (text.to_string(), BodyEncoding::Utf8),
+        _ => (BASE64.encode(stored_body), BodyEncoding::Base64),
+    };
+    let decoded_length = full_body.len();
+    // Text metrics cover the full decoded body, even when storage truncates it
+    let (char_length, line_count) = match body_encoding {
+        BodyEncoding::Utf8 => std::str::from_utf8(full_body)
+            .map(|text| (Some(text.chars().count()), Some(text.lines().count())))
+            .unwrap_or_default(),
+        BodyEncoding::Base64 => (None, None),
+    };
+    let user_agent = get_user_agent(&req);
+    let mut body_json = match body_encoding {
+        BodyEncoding::Utf8 => body::parse_json_body(effective_content_type, &body_str),
+        BodyEncoding::Base64 => None,
+    };
+    let body_valid_json = body_json.is_some();
+
+    // Decode protobuf bodies with the session's descriptor into `body_json`,
+    // so schema validation and transforms apply to them too
+    let mut proto_error = None;
+    if let (true, Some(descriptor), Some(message_type)) =
+        (is_protobuf, &session.proto_descriptor, &session.proto_message_type)
+    {
+        match proto::message_descriptor(descriptor, message_type)
+            .and_then(|descriptor| proto::decode_body(&content_type, &descriptor, full_body))
+        {
+            Ok(decoded) => body_json = Some(decoded),
+            Err(e) => {
+                debug!(session_id = %session_id, error = %e, "Failed to decode protobuf body");
+                proto_error = Some(e);
+            }
+        }
+    }
+    // Normalize JSON bodies with the session's patch; the original is kept
+    let (body_transformed, transform_error) = match (&session.body_transform, &body_json) {
+        (Some(patch), Some(body_json)) => match body::apply_transform(patch, body_json) {
+            Ok(transformed) => (Some(transformed), None),
+            Err(e) => (None, Some(e)),
+        },
+        _ => (None, None),
+    };
+
+    let form_fields = body::parse_form_body(effective_content_type, full_body);
+
+    // Parsed from the full body so part sizes are accurate even when truncated
+    let multipart_parts = body::parse_multipart(&content_type, full_body).await;
+
+    // Validate against the session's JSON Schema, if configured
+    let (schema_valid, schema_errors) = match &session.body_schema {
+        Some(schema) => match body::validate_body_schema(schema, body_json.as_ref()) {
+            Ok((valid, errors)) => (Some(valid), errors),
+            Err(e) => {
+                warn!(session_id = %session_id, error = %e, "Stored body_schema failed to compile");
+                (None, Vec::new())
+            }
+        },
+        None => (None, Vec::new()),
+    };
+    if session.reject_invalid_body && schema_valid == Some(false) {
+        return Err(AppError::SchemaValidationFailed(schema_errors));
+    }
+
+    let timestamp = Utc::now().to_rfc3339();
+    let request_id = Uuid::now_v7().to_string();
+    let connection = extract_connection_metadata(&req);
+
+    let query_params = query_params(&query_string);
+
+    let tags = request_tags(&req, &query_params, &state.settings.server);
+
+    let receipt = if session.signed_receipt {
+        state
+            .settings
+            .server
+            .receipt_signing_key
+            .as_deref()
+            .map(|key| build_receipt(key, &request_id, &body, &timestamp))
+    } else {
+        None
+    };
+
+    let mut webhook_request = WebhookRequest {
+        request_id: request_id.clone(),
+        // Assigned when the request is saved
+        sequence: 0,
+        method,
+        path,
+        query_params,
+        raw_query: query_string,
+        raw_target,
+        subpath,
+        original_session_id,
+        headers,
+        declared_trailers: declared_trailers(&req),
+        tags,
+        headers_truncated,
+        body: body_str,
+        body_encoding,
+        body_json,
+        body_valid_json,
+        body_transformed,
+        transform_error,
+        proto_error,
+        form_fields,
+        multipart_parts,
+        // Set below, once the storage allowlist and blocklist are known to allow it
+        cookies: None,
+        ip_address,
+        user_agent,
+        content_type,
+        detected_content_type: detected_content_type.map(str::to_string),
+        timestamp: timestamp.clone(),
+        received_at,
+        content_length: body.len(),
+        original_encoding,
+        decoded_length,
+        byte_length: body.len(),
+        char_length,
+        line_count,
+        forward_status: None,
+        forward_response: None,
+        delivery_attempts: Vec::new(),
+        signature_valid,
+        scheme: Some(connection.scheme),
+        http_version: Some(connection.http_version),
+        peer_address: connection.peer_address,
+        tls_version: connection.tls_version,
+        client_cert_verify: connection.client_cert_verify,
+        client_cert_subject: connection.client_cert_subject,
+        schema_valid,
+        schema_errors,
+        truncated,
+        original_content_length: truncated.then_some(decoded_length),
+    };
+
+    // Collapse repeated deliveries of the same event. The key is claimed
+    // before saving so concurrent duplicates can't both be stored, and is
+    // released again if the save fails.
+    let mut dedupe_fingerprint = None;
+    if session.dedupe_window_seconds > 0 {
+        let fingerprint = request_fingerprint(&webhook_request.method, req.path(), &body);
+        if let Some(original) = state
+            .redis
+            .claim_dedupe_key(
+                &session_id,
+                &fingerprint,
+                &request_id,
+                session.dedupe_window_seconds,
+            )
+            .await?
+        {
+            debug!(
+                session_id = %session_id,
+                duplicate_of = %original,
+                "Skipping duplicate webhook request"
+            );
+            audit.outcome = "duplicate";
+            audit.request_id = Some(original.clone());
+            return Ok(negotiate::ack(
+                HttpResponse::Ok(),
+                &req,
+                &DuplicateCaptureResponse {
+                    status: "duplicate".to_string(),
+                    request_id: original,
+                },
+            ));
+        }
+        dedupe_fingerprint = Some(fingerprint);
+    }
+
+    // Redact configured secrets before the request is stored, broadcast or
+    // logged; forwarding still relays the original headers
+    let forward_headers = session
+        .forward_url
+        .is_some()
+        .then(|| webhook_request.headers.clone());
+    webhook_request
+        .headers
+        .retain(|name, _| stores_header(&session, name));
+    if session.parse_cookies && stores_header(&session, header::COOKIE.as_str()) {
+        webhook_request.cookies = Some(parse_cookies(&req));
+    }
+    redact::redact_request(&mut webhook_request, &state.settings.redact);
+
+    // Save to Redis
+    let session_settings = &state.settings.session;
+    let saved = state
+        .redis
+        .save_request(
+            &session_id,
+            &mut webhook_request,
+            session_settings.request_ttl(),
+            session_settings.ttl_seconds,
+        )
+        .await;
+    if let Err(e) = saved {
+        // Otherwise the provider's retry would be acknowledged as a
+        // duplicate of a request that was never stored
+        if let Some(fingerprint) = &dedupe_fingerprint {
+            if let Err(release_error) = state
+                .redis
+                .release_dedupe_key(&session_id, fingerprint, &request_id)
+                .await
+            {
+                warn!(
+                    session_id = %session_id,
+                    error = %release_error,
+                    "Failed to release dedupe key after a failed save"
+                );
+            }
+        }
+        return Err(e);
+    }
+    audit.request_id = Some(webhook_request.request_id.clone());
+    state.archiver.archive(&session_id, &webhook_request);
+
+    state
+        .metrics
+        .webhooks_ingested
+        .with_label_values(&[webhook_request.method.as_str()])
+        .inc();
+
+    if let Some(notify_url) = &session.notify_webhook_url {
+        let notify_method = session.notify_on.is_empty()
+            || session.notify_on.contains(&webhook_request.method.to_ascii_uppercase());
+        if notify_method {
+            let client = state.http_client.clone();
+            let notify_url = notify_url.clone();
+            let session_id = session_id.clone();
+            let request = webhook_request.clone();
+            tokio::spawn(async move {
+                notify::notify(&client, &notify_url, &session_id, &request).await;
+            });
+        }
+    }
+
+    // Bytes exactly as received (still Content-Encoded), for `echo_body`
+    let echoed_body = session.echo_body.then(|| body.clone());
+
+    // Relay upstream in the background; failures never affect the capture
+    // and are retried per `FORWARD_MAX_ATTEMPTS`
+    if let Some(forward_url) = session.forward_url {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let job = ForwardJob {
+                session_id,
+                request_id: webhook_request.request_id.clone(),
+                target: forward_url,
+                method: webhook_request.method.clone(),
+                headers: forward_headers.unwrap_or_default(),
+                raw_query: webhook_request.raw_query.clone(),
+                body: BASE64.encode(&body),
+                attempt: 0,
+            };
+            retry::deliver(&state.http_client, &state.redis, &state.settings, job).await;
+        });
+    }
+
+    let template_values = TemplateValues {
+        request_id: &request_id,
+        session_id: &session.session_id,
+        timestamp: &timestamp,
+    };
+
+    let mut response = match &session.response_redirect_url {
+        Some(redirect_url) => {
+            let status = StatusCode::from_u16(session.response_redirect_status)
+                .unwrap_or(StatusCode::FOUND);
+            let mut response = HttpResponse::build(status);
+            response.insert_header((header::LOCATION, redirect_url.as_str()));
+            response
+        }
+        None => HttpResponse::Ok(),
+    };
+    // Values were validated (including placeholders) at session creation
+    for (name, value) in &session.response_headers {
+        response.insert_header((
+            name.as_str(),
+            template::render_template(value, &template_values),
+        ));
+    }
+
+    if session.response_redirect_url.is_some() {
+        return Ok(response.finish());
+    }
+
+    if let Some(echoed_body) = echoed_body {
+        for name in [header::CONTENT_TYPE, header::CONTENT_ENCODING] {
+            if let Some(value) = req.headers().get(&name) {
+                response.insert_header((name, value.clone()));
+            }
+        }
+        return Ok(response.body(echoed_body));
+    }
+
+    Ok(capture_ack(
+        response,
+        &req,
+        session.response_template.as_deref(),
+        &template_values,
+        receipt,
+    ))
+}
+
+/// Acknowledge a capture with the session's rendered `response_template`,
+/// or by default with a `CaptureResponse` in the negotiated format
+fn capture_ack(
+    mut response: actix_web::HttpResponseBuilder,
+    req: &HttpRequest,
+    response_template: Option<&str>,
+    values: &TemplateValues,
+    receipt: Option<Receipt>,
+) -> HttpResponse {
+    if let Some(response_template) = response_template {
+        let rendered = template::render_template(response_template, values);
+        let content_type = if serde_json::from_str::<serde_json::Value>(&rendered).is_ok() {
+            "application/json"
+        } else {
+            "text/plain; charset=utf-8"
+        };
+        return response.content_type(content_type).body(rendered);
+    }
+
+    negotiate::ack(
+        response,
+        req,
+        &CaptureResponse {
+            status: "captured".to_string(),
+            request_id: values.request_id.to_string(),
+            receipt,
+        },
+    )
+}
+
+/// Whether a header passes the session's storage allowlist and blocklist
+fn stores_header(session: &Session, name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    (session.store_headers_allowlist.is_empty() || session.store_headers_allowlist.contains(&name))
+        && !session.store_headers_blocklist.contains(&name)
+}
+
+/// Cookies from every `Cookie` header, URL-decoded and with surrounding
+/// quotes removed; the first of several same-named cookies wins
+fn parse_cookies(req: &HttpRequest) -> HashMap<String, String> {
+    let mut cookies = HashMap::new();
+    let pairs = req
+        .headers()
+        .get_all(header::COOKIE)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='));
+    for (name, value) in pairs {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        cookies
+            .entry(percent_decode_str(name).decode_utf8_lossy().into_owned())
+            .or_insert_with(|| percent_decode_str(value).decode_utf8_lossy().into_owned());
+    }
+    cookies
+}
+
+/// Collect the request headers to store (minus the ingest token), applying
+/// the configured count and value size limits. Returns whether anything was
+/// truncated; with the `reject` strategy an exceeded limit is an error instead.
+fn collect_headers(
+    req: &HttpRequest,
+    settings: &ServerSettings,
+) -> AppResult<(HashMap<String, String>, bool)> {
+    let reject = settings.header_limit_strategy == HeaderLimitStrategy::Reject;
+    let mut headers = HashMap::new();
+    let mut truncated = false;
+
+    for (key, value) in req.headers() {
+        if key.as_str() == INGEST_TOKEN_HEADER {
+            continue;
+        }
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+
+        if settings.max_headers > 0
+            && headers.len() >= settings.max_headers
+            && !headers.contains_key(key.as_str())
+        {
+            if reject {
+                return Err(AppError::HeadersTooLarge(format!(
+                    "more than {} headers",
+                    settings.max_headers
+                )));
+            }
+            truncated = true;
+            continue;
+        }
+
+        let limit = settings.max_header_value_bytes;
+        let value = if limit > 0 && value.len() > limit {
+            if reject {
+                return Err(AppError::HeadersTooLarge(format!(
+                    "value of {} exceeds {} bytes",
+                    key, limit
+                )));
+            }
+            truncated = true;
+            // Header values are visible ASCII, so any byte index is a char boundary
+            &value[..limit]
+        } else {
+            value
+        };
+
+        headers.insert(key.as_str().to_string(), value.to_string());
+    }
+
+    Ok((headers, truncated))
+}
+
+/// Build a receipt whose signature covers the request ID, body digest and timestamp
+fn build_receipt(key: &str, request_id: &str, body: &[u8], timestamp: &str) -> Receipt {
+    let body_sha256 = signature::sha256_hex(body);
+    let payload = format!("{}.{}.{}", request_id, body_sha256, timestamp);
+    let signature = signature::hmac_sha256_hex(key.as_bytes(), payload.as_bytes());
+
+    Receipt {
+        request_id: request_id.to_string(),
+        body_sha256,
+        timestamp: timestamp.to_string(),
+        signature,
+    }
+}
+
+/// Seconds a client turned away by the per-session stream limit is asked to wait
+const STREAM_LIMIT_RETRY_AFTER_SECONDS: u64 = 5;
+
+/// Subscribe a live stream client (SSE, WebSocket or NDJSON) to a session,
+/// enforcing `SSE_MAX_SUBSCRIBERS_PER_SESSION`
+async fn subscribe_stream(
+    state: &AppState,
+    session_id: &str,
+) -> AppResult<broadcast::Receiver<WebhookRequest>> {
+    let limit = state.settings.sse.max_subscribers_per_session;
+    state
+        .redis
+        .get_sse_channel(session_id, limit)
+        .await
+        .ok_or_else(|| AppError::RateLimitExceeded {
+            message: format!("session already has {} stream subscribers", limit),
+            retry_after_seconds: Some(STREAM_LIMIT_RETRY_AFTER_SECONDS),
+            limit: limit as u64,
+        })
+}
+
+/// Stream requests via SSE
+#[instrument(skip(state, req))]
+pub async fn stream_requests_handler(
+    path: web::Path<String>,
+    query: web::Query<StreamRequestsQuery>,
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    info!(session_id = %session_id, "Client connected to SSE stream");
+
+    // Get the SSE receiver before creating the stream
+    info!(session_id = %session_id, "Requesting SSE channel from Redis client");
+    let receiver = subscribe_stream(&state, &session_id).await?;
+    info!(
+        session_id = %session_id,
+        receiver_len = receiver.len(),
+        "Got SSE receiver, creating stream"
+    );
+    
+    state
+        .metrics
+        .sse_channels
+        .set(state.redis.get_sse_channel_count().await as i64);
+    let connection_guard = SseConnectionGuard::new(state.metrics.sse_connections.clone());
+
+    // Replay anything the client missed since its last received event
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|h| h.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty());
+    let replay = match last_event_id {
+        Some(last_event_id) => {
+            state
+                .redis
+                .get_requests_after(&session_id, last_event_id)
+                .await?
+        }
+        None => Vec::new(),
+    };
+
+    // Create SSE stream with the initialized receiver
+    let sse_stream = SseStream::new(
+        receiver,
+        session_id.clone(),
+        replay,
+        connection_guard,
+        state.redis.clone(),
+        SseOptions {
+            expiry_warning: Duration::from_secs(state.settings.session.expiry_warning_seconds),
+            lag_strategy: state.settings.sse.lag_strategy,
+            filter: query.body_filter(),
+            typed_events: query.typed_events,
+            shutdown: state.shutdown.subscribe(),
+            max_lifetime: match state.settings.sse.max_lifetime_seconds {
+                0 => None,
+                seconds => Some(Duration::from_secs(seconds)),
+            },
+        },
+    );
+    info!(session_id = %session_id, "SSE stream created, starting to serve events");
 
     // Get origin from request for CORS
     let origin = req.headers()
@@ -242,47 +1759,1671 @@ pub async fn stream_requests_handler(
         .and_then(|h| h.to_str().ok())
         .unwrap_or("*");
 
-    Ok(HttpResponse::Ok()
-        .content_type("text/event-stream")
-        .insert_header(("Cache-Control", "no-cache, no-transform"))
-        .insert_header(("Connection", "keep-alive"))
-        .insert_header(("X-Accel-Buffering", "no")) // Disable nginx buffering
-        .insert_header(("Access-Control-Allow-Origin", origin))
-        .insert_header(("Access-Control-Allow-Credentials", "true"))
-        .insert_header(("Access-Control-Allow-Headers", "Cache-Control"))
-        .insert_header(("Access-Control-Expose-Headers", "Content-Type"))
-        .streaming(sse_stream))
+    Ok(streaming_response("text/event-stream")
+        .insert_header(("Connection", "keep-alive"))
+        .insert_header(("Access-Control-Allow-Origin", origin))
+        .insert_header(("Access-Control-Allow-Credentials", "true"))
+        .insert_header(("Access-Control-Allow-Headers", "Cache-Control"))
+        .insert_header(("Access-Control-Expose-Headers", "Content-Type"))
+        .streaming(sse_stream))
+}
+
+/// Stream requests as NDJSON over a chunked response: recent requests
+/// first, then each new capture as it arrives
+#[instrument(skip(state))]
+pub async fn stream_requests_ndjson_handler(
+    path: web::Path<String>,
+    query: web::Query<NdjsonStreamQuery>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    // Subscribe before loading the dump so nothing captured in between is missed
+    let receiver = subscribe_stream(&state, &session_id).await?;
+    let mut recent = match query.limit {
+        0 => Vec::new(),
+        limit => state.redis.get_requests(&session_id, limit, 0).await?,
+    };
+    recent.reverse();
+
+    let stream = ndjson::live_stream(
+        receiver,
+        session_id,
+        recent,
+        state.redis.clone(),
+        state.shutdown.subscribe(),
+    );
+
+    Ok(streaming_response("application/x-ndjson").streaming(stream))
+}
+
+/// Start a long-lived streaming response. Proxies are told not to buffer or
+/// transform it, and the explicit identity encoding keeps the Compress
+/// middleware from buffering it (every live stream must be built this way).
+fn streaming_response(content_type: &str) -> actix_web::HttpResponseBuilder {
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(content_type)
+        .insert_header(("Cache-Control", "no-cache, no-transform"))
+        .insert_header(("X-Accel-Buffering", "no")) // Disable nginx buffering
+        .insert_header((header::CONTENT_ENCODING, "identity"));
+    response
+}
+
+/// Stream requests via WebSocket (same JSON payload as SSE `data:`)
+#[instrument(skip(state, req, payload))]
+pub async fn stream_requests_ws_handler(
+    path: web::Path<String>,
+    req: HttpRequest,
+    payload: web::Payload,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let receiver = subscribe_stream(&state, &session_id).await?;
+    let (response, ws_session, messages) = actix_ws::handle(&req, payload)
+        .map_err(|e| AppError::BadRequest(format!("WebSocket handshake failed: {}", e)))?;
+
+    actix_web::rt::spawn(ws::run(
+        ws_session,
+        messages,
+        receiver,
+        state.redis.clone(),
+        session_id,
+    ));
+
+    Ok(response)
+}
+
+/// Fetch historical requests
+#[instrument(skip(state))]
+pub async fn fetch_requests_handler(
+    path: web::Path<String>,
+    query: web::Query<FetchRequestsQuery>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    if query.header_value.is_some() && query.header_key.is_none() {
+        return Err(AppError::BadRequest("header_value requires header_key".to_string()));
+    }
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(AppError::BadRequest("from must not be after to".to_string()));
+        }
+    }
+
+    let limit = query.validated_limit();
+    let offset = query.offset;
+
+    let (from_ms, to_ms) = query.time_range_millis();
+    let (requests, total) = if query.has_filters() {
+        // Filter the full set (or the time range) before paginating so totals
+        // reflect the matches
+        let candidates = if query.has_time_range() {
+            state
+                .redis
+                .get_requests_by_time_range(&session_id, from_ms, to_ms, None)
+                .await?
+                .0
+        } else {
+            state.redis.get_all_requests(&session_id).await?
+        };
+        let matching: Vec<WebhookRequest> =
+            candidates.into_iter().filter(|r| query.matches(r)).collect();
+        let total = matching.len();
+        let page = matching.into_iter().skip(offset).take(limit).collect();
+        (page, total)
+    } else if query.has_time_range() {
+        state
+            .redis
+            .get_requests_by_time_range(&session_id, from_ms, to_ms, Some((offset, limit)))
+            .await?
+    } else {
+        let requests = state.redis.get_requests(&session_id, limit, offset).await?;
+        let total = state.redis.get_request_count(&session_id).await?;
+        (requests, total)
+    };
+
+    let response = RequestsResponse::page(session_id, requests, total, limit, offset);
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Count the SSE and WebSocket clients currently watching a session
+#[instrument(skip(state))]
+pub async fn subscriber_count_handler(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let subscribers = state.redis.subscriber_count(&session_id).await;
+
+    Ok(HttpResponse::Ok().json(SubscriberCountResponse {
+        session_id,
+        subscribers,
+    }))
+}
+
+/// Live stream statistics, including messages dropped by lagging clients
+#[instrument(skip(state))]
+pub async fn stream_stats_handler(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let subscribers = state.redis.subscriber_count(&session_id).await;
+    let (lag_events, dropped_messages) = state.redis.stream_lag(&session_id).await?;
+
+    Ok(HttpResponse::Ok().json(StreamStatsResponse {
+        session_id,
+        subscribers,
+        lag_events,
+        dropped_messages,
+    }))
+}
+
+/// Request count as a shields.io-style SVG badge
+#[instrument(skip(state))]
+pub async fn request_count_badge_handler(
+    path: web::Path<String>,
+    query: web::Query<BadgeQuery>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    let label = query.label.as_deref().unwrap_or(badge::DEFAULT_LABEL);
+    if label.chars().count() > badge::MAX_LABEL_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "label must be at most {} characters",
+            badge::MAX_LABEL_CHARS
+        )));
+    }
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let total_requests = state.redis.get_request_count(&session_id).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .insert_header((header::CACHE_CONTROL, "max-age=10"))
+        .body(badge::render(label, &total_requests.to_string())))
+}
+
+/// Count a session's captured requests without loading them
+#[instrument(skip(state))]
+pub async fn count_requests_handler(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let total_requests = state.redis.get_request_count(&session_id).await?;
+
+    Ok(HttpResponse::Ok().json(RequestCountResponse {
+        session_id,
+        total_requests,
+    }))
+}
+
+/// Compare two captured requests of a session
+#[instrument(skip(state))]
+pub async fn diff_requests_handler(
+    path: web::Path<String>,
+    query: web::Query<DiffRequestsQuery>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+    let DiffRequestsQuery { a, b } = query.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+    validate_uuid(&a)?;
+    validate_uuid(&b)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let a = state
+        .redis
+        .get_request(&session_id, &a)
+        .await?
+        .ok_or(AppError::RequestNotFound)?;
+    let b = state
+        .redis
+        .get_request(&session_id, &b)
+        .await?
+        .ok_or(AppError::RequestNotFound)?;
+
+    Ok(HttpResponse::Ok().json(diff::diff_requests(&session_id, &a, &b)))
+}
+
+/// Fetch a single captured request
+#[instrument(skip(state))]
+pub async fn fetch_request_handler(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let (session_id, request_id) = path.into_inner();
+
+    // Validate ID formats
+    validate_session_id(&session_id, &state.settings.session)?;
+    validate_uuid(&request_id)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let request = state
+        .redis
+        .get_request(&session_id, &request_id)
+        .await?
+        .ok_or(AppError::RequestNotFound)?;
+
+    Ok(HttpResponse::Ok().json(request))
+}
+
+/// Download a captured request's body as raw bytes, served with its original
+/// `Content-Type`. The body is attacker-controlled, so the browser is told not
+/// to sniff or run it, and types that can carry script are only downloaded.
+#[instrument(skip(state))]
+pub async fn fetch_request_body_handler(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let (session_id, request_id) = path.into_inner();
+
+    // Validate ID formats
+    validate_session_id(&session_id, &state.settings.session)?;
+    validate_uuid(&request_id)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let request = state
+        .redis
+        .get_request(&session_id, &request_id)
+        .await?
+        .ok_or(AppError::RequestNotFound)?;
+
+    let content_type = if request.content_type.is_empty() {
+        "application/octet-stream"
+    } else {
+        request.content_type.as_str()
+    };
+
+    let disposition = if is_active_content_type(content_type) {
+        "attachment"
+    } else {
+        "inline"
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::CONTENT_DISPOSITION, disposition))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header((header::CONTENT_SECURITY_POLICY, "sandbox"))
+        .body(stored_body_bytes(&request)?))
+}
+
+/// Whether a browser would render a media type as a document that can run
+/// script (HTML, SVG, XML and XSLT, JavaScript)
+fn is_active_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    mime.contains("html")
+        || mime.contains("javascript")
+        || mime.contains("ecmascript")
+        || mime.ends_with("/xml")
+        || mime.ends_with("+xml")
+        || mime.ends_with("/xsl")
+        || mime.ends_with("/xslt")
+}
+
+/// Render a stored request as a `curl` command that reproduces it
+#[instrument(skip(state))]
+pub async fn fetch_request_curl_handler(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let (session_id, request_id) = path.into_inner();
+
+    // Validate ID formats
+    validate_session_id(&session_id, &state.settings.session)?;
+    validate_uuid(&request_id)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let request = state
+        .redis
+        .get_request(&session_id, &request_id)
+        .await?
+        .ok_or(AppError::RequestNotFound)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(export::curl_command(&request, &state.settings.server.listen_url)))
+}
+
+/// Raw bytes of a stored body, undoing the base64 used for binary payloads
+fn stored_body_bytes(request: &WebhookRequest) -> AppResult<Vec<u8>> {
+    match request.body_encoding {
+        BodyEncoding::Utf8 => Ok(request.body.clone().into_bytes()),
+        BodyEncoding::Base64 => BASE64
+            .decode(&request.body)
+            .map_err(|e| AppError::Internal(format!("Stored body is not valid base64: {}", e))),
+    }
+}
+
+/// Re-send a stored request to a target URL and return the upstream response
+/// (the target is the only hop: redirects are returned, not followed)
+#[instrument(skip(state, body))]
+pub async fn replay_request_handler(
+    path: web::Path<(String, String)>,
+    body: web::Json<ReplayRequest>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let (session_id, request_id) = path.into_inner();
+
+    // Validate ID formats
+    validate_session_id(&session_id, &state.settings.session)?;
+    validate_uuid(&request_id)?;
+
+    let target = check_outbound_target("target_url", &body.target_url, &state.settings.server)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let mut request = state
+        .redis
+        .get_request(&session_id, &request_id)
+        .await?
+        .ok_or(AppError::RequestNotFound)?;
+
+    let payload = stored_body_bytes(&request)?;
+    // The stored body was decompressed on capture, so it no longer matches
+    // the original Content-Encoding
+    if request.original_encoding.is_some() {
+        request
+            .headers
+            .retain(|name, _| !name.eq_ignore_ascii_case("content-encoding"));
+    }
+
+    let outcome = forwarder::forward_request(
+        &state.http_client,
+        target.as_str(),
+        &request,
+        &request.raw_query,
+        payload.into(),
+        state.settings.server.allow_private_targets,
+    )
+    .await;
+
+    info!(
+        session_id = %session_id,
+        request_id = %request_id,
+        target_url = %target,
+        status = ?outcome.status,
+        "Replayed captured request"
+    );
+
+    Ok(HttpResponse::Ok().json(ReplayResponse {
+        request_id,
+        target_url: target.to_string(),
+        status: outcome.status,
+        response: outcome.response,
+    }))
+}
+
+/// Delete a single captured request
+#[instrument(skip(state))]
+pub async fn delete_request_handler(
+    path: web::Path<(String, String)>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let (session_id, request_id) = path.into_inner();
+
+    // Validate ID formats
+    validate_session_id(&session_id, &state.settings.session)?;
+    validate_uuid(&request_id)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    if !state.redis.delete_request(&session_id, &request_id).await? {
+        return Err(AppError::RequestNotFound);
+    }
+
+    info!(session_id = %session_id, request_id = %request_id, "Deleted captured request");
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
-/// Fetch historical requests
+/// Delete all captured requests in a session, keeping the session alive
 #[instrument(skip(state))]
-pub async fn fetch_requests_handler(
+pub async fn clear_requests_handler(
     path: web::Path<String>,
-    query: web::Query<FetchRequestsQuery>,
     state: web::Data<AppState>,
 ) -> AppResult<HttpResponse> {
     let session_id = path.into_inner();
 
-    // Validate UUID format
-    validate_uuid(&session_id)?;
+    validate_session_id(&session_id, &state.settings.session)?;
 
     // Check if session exists
     if !state.redis.session_exists(&session_id).await? {
         return Err(AppError::SessionNotFound);
     }
 
-    let limit = query.validated_limit();
-    let offset = query.offset;
+    // SSE/WebSocket subscribers are left connected; they simply see no
+    // further events for the cleared requests
+    let deleted = state.redis.clear_requests(&session_id).await?;
 
-    let requests = state.redis.get_requests(&session_id, limit, offset).await?;
-    let total = state.redis.get_request_count(&session_id).await?;
+    info!(session_id = %session_id, deleted = deleted, "Cleared captured requests");
 
-    let response = RequestsResponse {
+    Ok(HttpResponse::Ok().json(ClearRequestsResponse {
         session_id,
-        total_requests: total,
-        requests,
-    };
+        deleted,
+    }))
+}
 
-    Ok(HttpResponse::Ok().json(response))
+/// Export all captured requests as CSV, streamed row by row
+#[instrument(skip(state))]
+pub async fn export_requests_csv_handler(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let request_ids = state.redis.get_request_ids(&session_id).await?;
+    let header = export::csv_header()?;
+
+    // Fetch each request lazily so large sessions are never fully buffered
+    let redis = state.redis.clone();
+    let stream_session_id = session_id.clone();
+    let rows = stream::iter(request_ids).filter_map(move |request_id| {
+        let redis = redis.clone();
+        let session_id = stream_session_id.clone();
+        async move {
+            match redis.get_request(&session_id, &request_id).await {
+                Ok(Some(request)) => Some(export::csv_row(&request)),
+                Ok(None) => None,
+                Err(e) => Some(Err(e)),
+            }
+        }
+    });
+    let body = stream::once(async move { Ok::<_, AppError>(header) }).chain(rows);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}.csv\"", session_id),
+        ))
+        .streaming(body))
+}
+
+/// Export all captured requests as NDJSON, one request per line, paging
+/// through the session index by score instead of loading every request at
+/// once
+#[instrument(skip(state))]
+pub async fn export_requests_ndjson_handler(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    let redis = state.redis.clone();
+    let stream_session_id = session_id.clone();
+    // `Some(cursor)` while pages remain; the first page has no cursor
+    let pages = stream::unfold(Some(None), move |cursor| {
+        let redis = redis.clone();
+        let session_id = stream_session_id.clone();
+        async move {
+            let cursor = cursor?;
+            match redis.get_requests_before(&session_id, cursor, EXPORT_PAGE_SIZE).await {
+                Ok((page, next)) => {
+                    let lines: Vec<_> = page.iter().map(export::ndjson_line).collect();
+                    Some((stream::iter(lines), next.map(Some)))
+                }
+                // Surface the error as the last item, then stop
+                Err(e) => Some((stream::iter(vec![Err(e)]), None)),
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}.ndjson\"", session_id),
+        ))
+        .streaming(pages.flatten()))
+}
+
+/// Export all captured requests as a HAR 1.2 archive
+#[instrument(skip(state))]
+pub async fn export_requests_har_handler(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> AppResult<HttpResponse> {
+    let session_id = path.into_inner();
+
+    validate_session_id(&session_id, &state.settings.session)?;
+
+    // Check if session exists
+    if !state.redis.session_exists(&session_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    // HAR entries are chronological; storage returns newest first
+    let mut requests = state.redis.get_all_requests(&session_id).await?;
+    requests.reverse();
+
+    let har = export::har_document(&requests, &state.settings.server.listen_url);
+
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}.har\"", session_id),
+        ))
+        .json(har))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optional_json_treats_an_empty_body_as_no_options() {
+        let options: CreateSessionRequest = optional_json(b"").unwrap();
+        assert!(options.allowed_ips.is_none());
+        let options: ExtendSessionRequest = optional_json(b"  \n").unwrap();
+        assert!(options.ttl_seconds.is_none());
+    }
+
+    #[test]
+    fn optional_json_rejects_malformed_options() {
+        for body in [
+            &b"{"[..],
+            br#"{"allowed_ips": "1.2.3.4"}"#,
+            br#"{"allowed_ip": ["1.2.3.4"]}"#,
+        ] {
+            let result = optional_json::<CreateSessionRequest>(body);
+            assert!(matches!(result, Err(AppError::BadRequest(_))), "{:?}", body);
+        }
+        let result = optional_json::<ExtendSessionRequest>(br#"{"ttl": 60}"#);
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn outbound_targets_must_be_public() {
+        let mut settings = ServerSettings::default();
+        for url in ["http://127.0.0.1:6379", "http://169.254.169.254/latest/meta-data"] {
+            let result = check_outbound_target("notify_webhook_url", url, &settings);
+            assert!(matches!(result, Err(AppError::BadRequest(_))), "{}", url);
+        }
+        assert!(
+            check_outbound_target("notify_webhook_url", "ftp://example.com", &settings).is_err()
+        );
+        assert!(
+            check_outbound_target("notify_webhook_url", "https://hooks.slack.com/x", &settings)
+                .is_ok()
+        );
+
+        settings.allow_private_targets = true;
+        assert!(check_outbound_target("forward_url", "http://127.0.0.1:8080", &settings).is_ok());
+    }
+
+    fn bearer(value: &str) -> HttpRequest {
+        actix_web::test::TestRequest::post()
+            .insert_header(("Authorization", value))
+            .to_http_request()
+    }
+
+    #[test]
+    fn api_key_accepts_the_configured_bearer_token() {
+        assert!(check_api_key(&bearer("Bearer s3cret"), Some("s3cret")).is_ok());
+    }
+
+    #[test]
+    fn api_key_rejects_missing_and_wrong_keys() {
+        let missing = actix_web::test::TestRequest::post().to_http_request();
+        for req in [missing, bearer("Bearer wrong"), bearer("s3cret"), bearer("Basic s3cret")] {
+            let result = check_api_key(&req, Some("s3cret"));
+            assert!(matches!(result, Err(AppError::Unauthorized(_))), "{:?}", req.headers());
+        }
+    }
+
+    #[test]
+    fn api_key_is_not_required_when_unset() {
+        let anonymous = actix_web::test::TestRequest::post().to_http_request();
+        assert!(check_api_key(&anonymous, None).is_ok());
+        assert!(check_api_key(&bearer("Bearer anything"), None).is_ok());
+    }
+
+    fn session_with_token(token: &str) -> Session {
+        let options = CreateSessionRequest {
+            ingest_token: Some(token.to_string()),
+            ..Default::default()
+        };
+        let now = Utc::now();
+        crate::redis_client::new_session("abc", now, now, &options)
+    }
+
+    #[test]
+    fn session_urls_only_carry_the_token_when_asked() {
+        let session = session_with_token("secret");
+        let with = session_urls("http://h", &session, true);
+        assert_eq!(with.ingestion_url, "http://h/i/abc?echohook_token=secret");
+        let without = session_urls("http://h", &session, false);
+        assert_eq!(without.ingestion_url, "http://h/i/abc");
+    }
+
+    #[test]
+    fn active_body_types_are_downloaded() {
+        for content_type in [
+            "text/html; charset=utf-8",
+            "application/xhtml+xml",
+            "image/svg+xml",
+            "text/xml",
+            "application/xml",
+            "TEXT/JAVASCRIPT",
+            "application/xslt+xml",
+        ] {
+            assert!(is_active_content_type(content_type), "{}", content_type);
+        }
+        let passive = ["application/json", "text/plain", "image/png", "application/octet-stream"];
+        for content_type in passive {
+            assert!(!is_active_content_type(content_type), "{}", content_type);
+        }
+    }
+
+    #[test]
+    fn ingestion_accepts_the_session_token() {
+        let session = session_with_token("secret");
+        let by_header = actix_web::test::TestRequest::post()
+            .insert_header((INGEST_TOKEN_HEADER, "secret"))
+            .to_http_request();
+        assert!(check_ingest_token(&by_header, &session).is_ok());
+        let by_query = actix_web::test::TestRequest::post()
+            .uri("/i/abc?echohook_token=secret")
+            .to_http_request();
+        assert!(check_ingest_token(&by_query, &session).is_ok());
+    }
+
+    #[test]
+    fn ingestion_rejects_a_missing_or_wrong_token() {
+        let session = session_with_token("secret");
+        for uri in ["/i/abc", "/i/abc?echohook_token=nope", "/i/abc?echohook_token=secret2"] {
+            let req = actix_web::test::TestRequest::post().uri(uri).to_http_request();
+            let result = check_ingest_token(&req, &session);
+            assert!(matches!(result, Err(AppError::InvalidIngestToken)), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn ingestion_is_open_without_a_session_token() {
+        let now = Utc::now();
+        let session =
+            crate::redis_client::new_session("abc", now, now, &CreateSessionRequest::default());
+        let req = actix_web::test::TestRequest::post()
+            .uri("/i/abc?echohook_token=anything")
+            .to_http_request();
+        assert!(check_ingest_token(&req, &session).is_ok());
+    }
+
+    #[test]
+    fn duplicate_query_keys_keep_every_value_in_order() {
+        let raw = strip_ingest_token("a=1&b=x&a=2&flag&a=&echohook_token=t");
+        assert_eq!(raw, "a=1&b=x&a=2&flag&a=");
+
+        let params = query_params(&raw);
+        assert_eq!(params.len(), 3);
+        assert_eq!(params["a"], ["1", "2", ""]);
+        assert_eq!(params["b"], ["x"]);
+        assert_eq!(params["flag"], [""]);
+        assert!(query_params("").is_empty());
+    }
+
+    const VALUES: TemplateValues<'static> = TemplateValues {
+        request_id: "req-1",
+        session_id: "abc",
+        timestamp: "2024-01-01T00:00:00+00:00",
+    };
+
+    #[actix_web::test]
+    async fn capture_ack_renders_the_session_template() {
+        let req = actix_web::test::TestRequest::post().to_http_request();
+        let template = r#"{"ok":true,"id":"{{request_id}}","session":"{{ session_id }}"}"#;
+        let response = capture_ack(HttpResponse::Ok(), &req, Some(template), &VALUES, None);
+        assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"ok":true,"id":"req-1","session":"abc"}"#);
+
+        let template = Some("got {{ request_id }}");
+        let response = capture_ack(HttpResponse::Ok(), &req, template, &VALUES, None);
+        let content_type = response.headers().get(header::CONTENT_TYPE).unwrap();
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "got req-1");
+    }
+
+    #[actix_web::test]
+    async fn capture_ack_defaults_to_the_capture_response() {
+        let req = actix_web::test::TestRequest::post().to_http_request();
+        let response = capture_ack(HttpResponse::Ok(), &req, None, &VALUES, None);
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({"status": "captured", "request_id": "req-1"}));
+    }
+
+    /// App state backed by an in-memory fake Redis
+    async fn test_state(settings: crate::config::Settings) -> web::Data<AppState> {
+        let (shutdown, _) = tokio::sync::broadcast::channel(1);
+        let audit_log_path = settings.server.audit_log_path.as_deref();
+        let audit = crate::audit::AuditLog::start(audit_log_path).await.unwrap();
+        web::Data::new(AppState {
+            redis: std::sync::Arc::new(crate::redis_client::fake_redis::memory().await),
+            settings: std::sync::Arc::new(settings),
+            metrics: std::sync::Arc::new(crate::metrics::Metrics::new().unwrap()),
+            http_client: forwarder::build_client().unwrap(),
+            start_time: Instant::now(),
+            archiver: crate::archive::Archiver::start(&Default::default()).await.unwrap(),
+            audit,
+            shutdown,
+        })
+    }
+
+    /// Create a session with default options in the state's Redis
+    async fn create_test_session(state: &AppState) -> String {
+        let session_id = uuid::Uuid::now_v7().to_string();
+        let options = CreateSessionRequest::default();
+        state.redis.create_session(&session_id, 3600, &options, 0).await.unwrap();
+        session_id
+    }
+
+    /// Store a captured POST in a session, returning its ID
+    async fn capture(state: &AppState, session_id: &str) -> String {
+        let mut request = WebhookRequest {
+            request_id: uuid::Uuid::now_v7().to_string(),
+            method: "POST".to_string(),
+            path: format!("/i/{}", session_id),
+            timestamp: Utc::now().to_rfc3339(),
+            ..Default::default()
+        };
+        state.redis.save_request(session_id, &mut request, 3600, 3600).await.unwrap();
+        request.request_id
+    }
+
+    #[actix_web::test]
+    async fn deleting_a_request_removes_only_that_one() {
+        use actix_web::test::{call_service, init_service, TestRequest};
+        use actix_web::App;
+
+        let state = test_state(crate::config::Settings::default()).await;
+        let session_id = create_test_session(&state).await;
+        let doomed = capture(&state, &session_id).await;
+        let kept = capture(&state, &session_id).await;
+        let redis = state.redis.clone();
+        let app = App::new().app_data(state).route(
+            "/r/{session_id}/{request_id}",
+            web::delete().to(delete_request_handler),
+        );
+        let app = init_service(app).await;
+
+        let uri = format!("/r/{}/{}", session_id, doomed);
+        let response = call_service(&app, TestRequest::delete().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(redis.get_request_count(&session_id).await.unwrap(), 1);
+        assert!(redis.get_request(&session_id, &doomed).await.unwrap().is_none());
+        assert!(redis.get_request(&session_id, &kept).await.unwrap().is_some());
+
+        // Already gone
+        let response = call_service(&app, TestRequest::delete().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(redis.get_request_count(&session_id).await.unwrap(), 1);
+    }
+
+    #[actix_web::test]
+    async fn single_requests_are_fetched_by_id() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let state = test_state(crate::config::Settings::default()).await;
+        let session_id = create_test_session(&state).await;
+        let request_id = capture(&state, &session_id).await;
+        let app = App::new().app_data(state).route(
+            "/r/{session_id}/{request_id}",
+            web::get().to(fetch_request_handler),
+        );
+        let app = init_service(app).await;
+
+        let uri = format!("/r/{}/{}", session_id, request_id);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["request_id"], request_id);
+        assert_eq!(body["method"], "POST");
+
+        let uri = format!("/r/{}/{}", session_id, uuid::Uuid::now_v7());
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["error"], "request_not_found");
+
+        let uri = format!("/r/{}/not-a-uuid", session_id);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn extending_a_session_moves_its_expiry_forward() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let state = test_state(crate::config::Settings::default()).await;
+        let session_id = create_test_session(&state).await;
+        let redis = state.redis.clone();
+        let before = redis.get_session(&session_id).await.unwrap().unwrap().expires_at;
+        let app = App::new()
+            .app_data(state)
+            .route("/c/{session_id}/extend", web::post().to(extend_session_handler));
+        let app = init_service(app).await;
+
+        let uri = format!("/c/{}/extend", session_id);
+        let req = TestRequest::post().uri(&uri).set_payload(r#"{"ttl_seconds": 7200}"#);
+        let response = call_service(&app, req.to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        let parse = |at: &str| chrono::DateTime::parse_from_rfc3339(at).unwrap();
+        let after = body["expires_at"].as_str().unwrap();
+        assert!(parse(after) > parse(&before), "{} <= {}", after, before);
+        assert_eq!(redis.get_session(&session_id).await.unwrap().unwrap().expires_at, after);
+        let ttl = redis.get_session_ttl(&session_id).await.unwrap().unwrap();
+        assert!(ttl > 3600 && ttl <= 7200, "{}", ttl);
+
+        // Custom TTLs are capped at the configured maximum
+        let req = TestRequest::post().uri(&uri).set_payload(r#"{"ttl_seconds": 999999999}"#);
+        assert_eq!(call_service(&app, req.to_request()).await.status(), StatusCode::OK);
+        let ttl = redis.get_session_ttl(&session_id).await.unwrap().unwrap();
+        assert!(ttl > 7200 && ttl <= 86400, "{}", ttl);
+
+        let uri = format!("/c/{}/extend", uuid::Uuid::now_v7());
+        let response = call_service(&app, TestRequest::post().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn session_creation_stops_at_the_global_limit() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let mut settings = crate::config::Settings::default();
+        settings.session.max_sessions = 2;
+        let state = test_state(settings).await;
+        let redis = state.redis.clone();
+        let app = App::new()
+            .app_data(state)
+            .route("/c", web::post().to(create_session_handler));
+        let app = init_service(app).await;
+
+        for _ in 0..2 {
+            let response = call_service(&app, TestRequest::post().uri("/c").to_request()).await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+        let response = call_service(&app, TestRequest::post().uri("/c").to_request()).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["error"], "session_limit_exceeded");
+
+        // The rejected session's reserved slot was given back
+        assert_eq!(redis.count_active_sessions().await.unwrap(), 2);
+    }
+
+    #[actix_web::test]
+    async fn ingests_over_the_per_ip_limit_are_rejected_with_retry_after() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let mut settings = crate::config::Settings::default();
+        settings.rate_limit.per_ip = 2;
+        settings.rate_limit.window_seconds = 3600;
+        let state = test_state(settings).await;
+        let session_id = create_test_session(&state).await;
+        let app = App::new()
+            .app_data(state)
+            .route("/i/{session_id}", web::post().to(ingest_webhook_handler_base));
+        let app = init_service(app).await;
+        let ingest = |ip: &str| {
+            TestRequest::post()
+                .uri(&format!("/i/{}", session_id))
+                .insert_header(("X-Real-IP", ip))
+                .to_request()
+        };
+
+        for _ in 0..2 {
+            let response = call_service(&app, ingest("192.0.2.1")).await;
+            assert!(response.status().is_success(), "{}", response.status());
+        }
+        let response = call_service(&app, ingest("192.0.2.1")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after = response.headers().get(header::RETRY_AFTER).unwrap();
+        let retry_after: u64 = retry_after.to_str().unwrap().parse().unwrap();
+        assert!((1..=3600).contains(&retry_after), "{}", retry_after);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["error"], "rate_limit_exceeded");
+
+        // Other clients have their own window
+        let response = call_service(&app, ingest("192.0.2.2")).await;
+        assert!(response.status().is_success(), "{}", response.status());
+    }
+
+    #[actix_web::test]
+    async fn clearing_requests_keeps_the_session_alive() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let state = test_state(crate::config::Settings::default()).await;
+        let session_id = create_test_session(&state).await;
+        for _ in 0..3 {
+            capture(&state, &session_id).await;
+        }
+        let redis = state.redis.clone();
+        let app = App::new()
+            .app_data(state)
+            .route("/r/{session_id}", web::delete().to(clear_requests_handler));
+        let app = init_service(app).await;
+
+        let uri = format!("/r/{}", session_id);
+        let response = call_service(&app, TestRequest::delete().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["deleted"], 3);
+        assert_eq!(redis.get_request_count(&session_id).await.unwrap(), 0);
+        assert!(redis.session_exists(&session_id).await.unwrap());
+        assert!(redis.get_session_ttl(&session_id).await.unwrap().unwrap() > 0);
+
+        let response = call_service(&app, TestRequest::delete().uri(&uri).to_request()).await;
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["deleted"], 0);
+
+        let uri = format!("/r/{}", uuid::Uuid::now_v7());
+        let response = call_service(&app, TestRequest::delete().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn uptime_counts_from_server_start() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let app = App::new()
+            .app_data(test_state(crate::config::Settings::default()).await)
+            .route("/health", web::get().to(health_check_handler));
+        let app = init_service(app).await;
+        let uptime = || async {
+            let response = call_service(&app, TestRequest::get().uri("/health").to_request());
+            let body: serde_json::Value =
+                serde_json::from_slice(&read_body(response.await).await).unwrap();
+            assert_eq!(body["status"], "healthy");
+            body["uptime_seconds"].as_u64().unwrap()
+        };
+
+        let started = uptime().await;
+        assert!(started < 5, "{}", started);
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let later = uptime().await;
+        assert!(later > started && later < 10, "{} -> {}", started, later);
+    }
+
+    #[actix_web::test]
+    async fn options_requests_are_preflights_unless_the_session_captures_them() {
+        use actix_web::test::{call_service, init_service, TestRequest};
+        use actix_web::App;
+
+        let state = test_state(crate::config::Settings::default()).await;
+        let default = create_test_session(&state).await;
+        let capturing = uuid::Uuid::now_v7().to_string();
+        let options = CreateSessionRequest {
+            capture_options_requests: true,
+            ..Default::default()
+        };
+        state.redis.create_session(&capturing, 3600, &options, 0).await.unwrap();
+        let redis = state.redis.clone();
+        let app = App::new()
+            .app_data(state)
+            .route("/i/{session_id}", web::method(Method::OPTIONS).to(ingest_webhook_handler_base));
+        let app = init_service(app).await;
+        let preflight = |session_id: &str| {
+            TestRequest::default()
+                .method(Method::OPTIONS)
+                .uri(&format!("/i/{}", session_id))
+                .insert_header(("Origin", "https://app.example"))
+                .insert_header(("Access-Control-Request-Headers", "content-type"))
+                .to_request()
+        };
+
+        let response = call_service(&app, preflight(&default)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(headers.get("Access-Control-Allow-Origin").unwrap(), "https://app.example");
+        assert_eq!(headers.get("Access-Control-Allow-Headers").unwrap(), "content-type");
+        assert_eq!(redis.get_request_count(&default).await.unwrap(), 0);
+
+        let response = call_service(&app, preflight(&capturing)).await;
+        assert!(response.status().is_success(), "{}", response.status());
+        assert!(response.headers().get("Access-Control-Allow-Origin").is_none());
+        let requests = redis.get_all_requests(&capturing).await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "OPTIONS");
+    }
+
+    #[actix_web::test]
+    async fn every_ingest_attempt_is_appended_to_the_audit_log() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let path = std::env::temp_dir().join(format!("echohook-audit-{}.jsonl", Uuid::now_v7()));
+        let mut settings = crate::config::Settings::default();
+        settings.server.audit_log_path = Some(path.to_string_lossy().into_owned());
+        let state = test_state(settings).await;
+        let session_id = create_test_session(&state).await;
+        let app = App::new()
+            .app_data(state)
+            .route("/i/{session_id}", web::post().to(ingest_webhook_handler_base));
+        let app = init_service(app).await;
+        let ingest = |session_id: &str| {
+            TestRequest::post()
+                .uri(&format!("/i/{}", session_id))
+                .insert_header(("X-Real-IP", "192.0.2.1"))
+                .set_payload("hello")
+                .to_request()
+        };
+
+        let response = call_service(&app, ingest(&session_id)).await;
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        let request_id = body["request_id"].as_str().unwrap().to_string();
+        let missing = Uuid::now_v7().to_string();
+        call_service(&app, ingest(&missing)).await;
+
+        // The writer runs in the background
+        let mut lines: Vec<serde_json::Value> = Vec::new();
+        for _ in 0..100 {
+            let log = tokio::fs::read_to_string(&path).await.unwrap();
+            lines = log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+            if lines.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(lines.len(), 2, "{:?}", lines);
+        assert_eq!(lines[0]["session_id"], session_id);
+        assert_eq!(lines[0]["request_id"], request_id);
+        assert_eq!(lines[0]["ip"], "192.0.2.1");
+        assert_eq!(lines[0]["method"], "POST");
+        assert_eq!(lines[0]["path"], format!("/i/{}", session_id));
+        assert_eq!(lines[0]["content_length"], 5);
+        assert_eq!(lines[0]["outcome"], "captured");
+        assert!(lines[0]["timestamp"].is_string());
+        assert_eq!(lines[1]["session_id"], missing);
+        assert_eq!(lines[1]["request_id"], serde_json::Value::Null);
+        assert_eq!(lines[1]["status"], 404);
+        assert_eq!(lines[1]["outcome"], "session_not_found");
+    }
+
+    #[test]
+    fn session_ids_come_from_the_leftmost_subdomain_label() {
+        let head = |host: &str| {
+            actix_web::test::TestRequest::post()
+                .insert_header((header::HOST, host))
+                .to_http_request()
+                .head()
+                .clone()
+        };
+        let domain = ".Hooks.Example.com";
+        assert_eq!(
+            subdomain_session_id(&head("abc123.hooks.example.com:8080"), domain).as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(subdomain_session_id(&head("hooks.example.com"), domain), None);
+        assert_eq!(subdomain_session_id(&head("a.b.hooks.example.com"), domain), None);
+        assert_eq!(subdomain_session_id(&head("abc123.example.com"), domain), None);
+        assert_eq!(subdomain_session_id(&head("abchooks.example.com"), domain), None);
+    }
+
+    #[actix_web::test]
+    async fn subdomain_ingests_land_in_the_named_session() {
+        use actix_web::test::{call_service, init_service, TestRequest};
+        use actix_web::{guard, App};
+
+        let mut settings = crate::config::Settings::default();
+        settings.server.subdomain_routing_domain = Some("hooks.example.com".to_string());
+        let state = test_state(settings).await;
+        let session_id = create_test_session(&state).await;
+        let redis = state.redis.clone();
+        // Routed as in `main`
+        let app = App::new()
+            .app_data(state)
+            .service(
+                web::scope("")
+                    .guard(guard::fn_guard(|ctx| {
+                        subdomain_session_id(ctx.head(), "hooks.example.com").is_some()
+                    }))
+                    .default_service(web::to(ingest_webhook_subdomain_handler)),
+            )
+            .route("/i/{session_id}", web::post().to(ingest_webhook_handler_base));
+        let app = init_service(app).await;
+
+        let req = TestRequest::put()
+            .uri("/github/push?x=1")
+            .insert_header((header::HOST, format!("{}.hooks.example.com", session_id)));
+        let response = call_service(&app, req.to_request()).await;
+        assert!(response.status().is_success(), "{}", response.status());
+        let requests = redis.get_all_requests(&session_id).await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "PUT");
+        assert!(requests[0].path.ends_with("/github/push"), "{}", requests[0].path);
+
+        // Path-based ingestion keeps working on the bare domain
+        let req = TestRequest::post()
+            .uri(&format!("/i/{}", session_id))
+            .insert_header((header::HOST, "hooks.example.com"));
+        let response = call_service(&app, req.to_request()).await;
+        assert!(response.status().is_success(), "{}", response.status());
+        assert_eq!(redis.get_request_count(&session_id).await.unwrap(), 2);
+
+        // The label must still be a valid session ID
+        let req = TestRequest::post()
+            .uri("/")
+            .insert_header((header::HOST, "not-a-session.hooks.example.com"));
+        let response = call_service(&app, req.to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn captured_bodies_record_byte_char_and_line_counts() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let state = test_state(crate::config::Settings::default()).await;
+        let session_id = create_test_session(&state).await;
+        let redis = state.redis.clone();
+        let app = App::new()
+            .app_data(state)
+            .route("/i/{session_id}", web::post().to(ingest_webhook_handler_base));
+        let app = init_service(app).await;
+        let mut captured = Vec::new();
+        for body in ["héllo\nwörld\n✓".as_bytes(), b"\x00\xFF\xFE"] {
+            let req = TestRequest::post()
+                .uri(&format!("/i/{}", session_id))
+                .insert_header((header::CONTENT_TYPE, "text/plain"))
+                .set_payload(body);
+            let response = call_service(&app, req.to_request()).await;
+            let body: serde_json::Value =
+                serde_json::from_slice(&read_body(response).await).unwrap();
+            let request_id = body["request_id"].as_str().unwrap();
+            captured.push(redis.get_request(&session_id, request_id).await.unwrap().unwrap());
+        }
+
+        let (text, binary) = (&captured[0], &captured[1]);
+        assert_eq!((text.content_length, text.byte_length), (17, 17));
+        assert_eq!(text.char_length, Some(13));
+        assert_eq!(text.line_count, Some(3));
+        assert_eq!(binary.byte_length, 3);
+        assert_eq!((binary.char_length, binary.line_count), (None, None));
+    }
+
+    #[actix_web::test]
+    async fn diffs_need_both_requests_to_exist() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let state = test_state(crate::config::Settings::default()).await;
+        let session_id = create_test_session(&state).await;
+        let a = capture(&state, &session_id).await;
+        let b = capture(&state, &session_id).await;
+        let app = App::new()
+            .app_data(state)
+            .route("/r/{session_id}/diff", web::get().to(diff_requests_handler));
+        let app = init_service(app).await;
+        let diff = |a: &str, b: &str| {
+            let uri = format!("/r/{}/diff?a={}&b={}", session_id, a, b);
+            TestRequest::get().uri(&uri).to_request()
+        };
+
+        let response = call_service(&app, diff(&a, &b)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["a"], a);
+        assert_eq!(body["b"], b);
+        assert_eq!(body["identical"], true);
+
+        let missing = uuid::Uuid::now_v7().to_string();
+        for (a, b) in [(&a, &missing), (&missing, &b)] {
+            let response = call_service(&app, diff(a, b)).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[actix_web::test]
+    async fn metrics_count_each_ingest_by_method() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let state = test_state(crate::config::Settings::default()).await;
+        let session_id = create_test_session(&state).await;
+        let app = App::new()
+            .app_data(state)
+            .route("/metrics", web::get().to(metrics_handler))
+            .route("/i/{session_id}", web::post().to(ingest_webhook_handler_base));
+        let app = init_service(app).await;
+
+        let counter = r#"echohook_webhooks_ingested_total{method="POST"}"#;
+        for ingested in 0..=2 {
+            if ingested > 0 {
+                let req = TestRequest::post().uri(&format!("/i/{}", session_id)).to_request();
+                assert!(call_service(&app, req).await.status().is_success());
+            }
+            let req = TestRequest::get().uri("/metrics").to_request();
+            let body = read_body(call_service(&app, req).await).await;
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            match ingested {
+                0 => assert!(!body.contains(counter), "{}", body),
+                n => assert!(body.contains(&format!("{} {}", counter, n)), "{}", body),
+            }
+            assert!(body.contains("echohook_active_sessions 1"), "{}", body);
+        }
+    }
+
+    #[actix_web::test]
+    async fn generated_short_ids_round_trip_through_the_ingestion_route() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let mut settings = crate::config::Settings::default();
+        settings.session.id_format = SessionIdFormat::Short;
+        let id = session_id::generate(&settings.session);
+
+        let app = App::new()
+            .app_data(test_state(settings).await)
+            .route("/i/{session_id}", web::post().to(ingest_webhook_handler_base));
+        let app = init_service(app).await;
+
+        // The ID passes validation and is looked up (Redis is empty)
+        let req = TestRequest::post().uri(&format!("/i/{}", id)).to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["error"], "session_not_found");
+
+        let req = TestRequest::post().uri("/i/not-a-session").to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn oversized_bodies_report_the_limit_for_their_type() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let mut settings = crate::config::Settings::default();
+        settings.server.max_body_size_by_type =
+            HashMap::from([("multipart/form-data".to_string(), 10)]);
+        let app = App::new()
+            .app_data(test_state(settings).await)
+            .route("/i/{session_id}", web::post().to(ingest_webhook_handler_base));
+        let app = init_service(app).await;
+        let id = uuid::Uuid::now_v7();
+
+        let req = TestRequest::post()
+            .uri(&format!("/i/{}", id))
+            .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=x"))
+            .set_payload("x".repeat(20))
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: serde_json::Value =
+            serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(body["applied"], "multipart/form-data");
+        assert_eq!(body["limit"], 10);
+
+        // Other types fall back to the global limit and reach the session lookup
+        let req = TestRequest::post()
+            .uri(&format!("/i/{}", id))
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload("x".repeat(20))
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn the_ingest_route_tail_is_extracted_and_sanitized() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        async fn ingest(path: web::Path<(String, String)>) -> AppResult<HttpResponse> {
+            let (_, tail) = path.into_inner();
+            Ok(HttpResponse::Ok().body(sanitize_subpath(&tail)?))
+        }
+
+        let app = App::new().route("/i/{session_id}/{tail:.*}", web::post().to(ingest));
+        let app = init_service(app).await;
+        for (uri, subpath) in [
+            ("/i/abc/a/b/c", "a/b/c"),
+            ("/i/abc/a//b/./c/", "a/b/c"),
+            ("/i/abc/a/../../b", "b"),
+        ] {
+            let response = call_service(&app, TestRequest::post().uri(uri).to_request()).await;
+            assert_eq!(read_body(response).await, subpath, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn subpaths_are_sanitized_and_capped() {
+        assert_eq!(sanitize_subpath("").unwrap(), "");
+        assert_eq!(sanitize_subpath("a/\u{7}b").unwrap(), "a/\u{FFFD}b");
+        let long = "a".repeat(MAX_SUBPATH_BYTES + 1);
+        let result = sanitize_subpath(&long);
+        assert!(matches!(result, Err(AppError::SubpathTooLong { .. })));
+        assert!(sanitize_subpath(&long[1..]).is_ok());
+    }
+
+    fn header_limits(strategy: HeaderLimitStrategy) -> ServerSettings {
+        ServerSettings {
+            max_headers: 2,
+            max_header_value_bytes: 4,
+            header_limit_strategy: strategy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn headers_within_the_limits_are_kept_whole() {
+        let req = actix_web::test::TestRequest::post()
+            .insert_header(("a", "1234"))
+            .insert_header((INGEST_TOKEN_HEADER, "secret"))
+            .to_http_request();
+        let settings = header_limits(HeaderLimitStrategy::Reject);
+
+        let (headers, truncated) = collect_headers(&req, &settings).unwrap();
+        assert_eq!(headers, HashMap::from([("a".to_string(), "1234".to_string())]));
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn truncated_headers_are_flagged() {
+        let too_many = actix_web::test::TestRequest::post()
+            .insert_header(("a", "1"))
+            .insert_header(("b", "2"))
+            .insert_header(("c", "3"))
+            .to_http_request();
+        let too_long = actix_web::test::TestRequest::post()
+            .insert_header(("a", "123456"))
+            .to_http_request();
+        let settings = header_limits(HeaderLimitStrategy::Truncate);
+
+        let (headers, truncated) = collect_headers(&too_many, &settings).unwrap();
+        assert!(truncated);
+        assert_eq!(headers.len(), 2);
+        let (headers, truncated) = collect_headers(&too_long, &settings).unwrap();
+        assert!(truncated);
+        assert_eq!(headers, HashMap::from([("a".to_string(), "1234".to_string())]));
+    }
+
+    #[test]
+    fn rejected_headers_are_a_431() {
+        let too_many = actix_web::test::TestRequest::post()
+            .insert_header(("a", "1"))
+            .insert_header(("b", "2"))
+            .insert_header(("c", "3"))
+            .to_http_request();
+        let too_long = actix_web::test::TestRequest::post()
+            .insert_header(("a", "123456"))
+            .to_http_request();
+        let settings = header_limits(HeaderLimitStrategy::Reject);
+
+        for req in [too_many, too_long] {
+            let error = collect_headers(&req, &settings).unwrap_err();
+            assert!(matches!(error, AppError::HeadersTooLarge(_)));
+            assert_eq!(error.status_code(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+        }
+    }
+
+    #[test]
+    fn cookies_are_parsed_into_a_map() {
+        let req = actix_web::test::TestRequest::post()
+            .insert_header((header::COOKIE, "a=1; b=2"))
+            .to_http_request();
+        let expected = HashMap::from([
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), "2".to_string()),
+        ]);
+        assert_eq!(parse_cookies(&req), expected);
+    }
+
+    #[test]
+    fn cookie_values_are_decoded_and_the_first_duplicate_wins() {
+        let req = actix_web::test::TestRequest::post()
+            .append_header((header::COOKIE, r#"name="Ada%20L"; a=1; a=2"#))
+            .append_header((header::COOKIE, "caf%C3%A9=%E2%9C%93;flag; =x"))
+            .to_http_request();
+        let cookies = parse_cookies(&req);
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies["name"], "Ada L");
+        assert_eq!(cookies["a"], "1");
+        assert_eq!(cookies["café"], "✓");
+    }
+
+    #[test]
+    fn ingest_tokens_round_trip_through_the_ingestion_url() {
+        let token = "a&b+c #%d=é";
+        let session = session_with_token(token);
+        let url = session_urls("http://h", &session, true).ingestion_url;
+        let query = url.split_once('?').unwrap().1;
+        assert!(!query.contains(' ') && !query.contains('#'), "{}", query);
+
+        let req = actix_web::test::TestRequest::with_uri(&format!("/i/abc?x=1&{}", query))
+            .to_http_request();
+        assert_eq!(get_ingest_token(&req).as_deref(), Some(token));
+        assert_eq!(strip_ingest_token(req.query_string()), "x=1");
+    }
+
+    /// Over a real HTTP/1 connection: announced trailer names are recorded
+    /// from the `Trailer` header, while a body that actually carries a trailer
+    /// section is rejected by actix-web's chunked decoder before any handler
+    #[actix_web::test]
+    async fn chunked_trailers_record_only_the_announced_names() {
+        use actix_web::{App, HttpServer};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        async fn echo(req: HttpRequest, body: web::Bytes) -> HttpResponse {
+            HttpResponse::Ok().body(format!(
+                "{}|{}",
+                declared_trailers(&req).join(","),
+                String::from_utf8_lossy(&body)
+            ))
+        }
+
+        async fn send(addr: std::net::SocketAddr, trailer_section: &str) -> String {
+            let mut socket = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let request = format!(
+                "POST / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\
+                 Transfer-Encoding: chunked\r\nTrailer: X-Checksum, X-Other\r\n\r\n\
+                 5\r\nhello\r\n0\r\n{}\r\n",
+                trailer_section
+            );
+            socket.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            socket.read_to_string(&mut response).await.unwrap();
+            response
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer::new(|| App::new().route("/", web::post().to(echo)))
+            .listen(listener)
+            .unwrap()
+            .workers(1)
+            .run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let announced = send(addr, "").await;
+        let with_values = send(addr, "X-Checksum: abc123\r\n").await;
+        handle.stop(false).await;
+
+        assert!(announced.starts_with("HTTP/1.1 200"), "{}", announced);
+        assert!(announced.ends_with("x-checksum,x-other|hello"), "{}", announced);
+        assert!(with_values.starts_with("HTTP/1.1 400"), "{}", with_values);
+    }
+
+    /// With compression on, ordinary JSON responses (such as fetch) are
+    /// gzipped while live streams built by `streaming_response` pass through
+    #[actix_web::test]
+    async fn compression_skips_live_streams() {
+        use actix_web::middleware::{Compress, Condition};
+        use actix_web::{test, App};
+
+        let app = test::init_service(
+            App::new()
+                .wrap(Condition::new(true, Compress::default()))
+                .route(
+                    "/r/{session_id}",
+                    web::get().to(|| async {
+                        HttpResponse::Ok().json(serde_json::json!({ "requests": [] }))
+                    }),
+                )
+                .route(
+                    "/s/{session_id}",
+                    web::get().to(|| async {
+                        let events = stream::iter([Ok::<_, AppError>(web::Bytes::from_static(
+                            b"event: connected\ndata: {}\n\n",
+                        ))]);
+                        streaming_response("text/event-stream").streaming(events)
+                    }),
+                ),
+        )
+        .await;
+
+        let fetch = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/r/abc")
+                .insert_header((header::ACCEPT_ENCODING, "gzip"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(fetch.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+
+        let sse = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/s/abc")
+                .insert_header((header::ACCEPT_ENCODING, "gzip"))
+                .to_request(),
+        )
+        .await;
+        assert_ne!(sse.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+        let body = test::read_body(sse).await;
+        assert_eq!(&body[..], b"event: connected\ndata: {}\n\n");
+    }
+
+    #[test]
+    fn bulk_create_still_accepts_shared_options() {
+        let bulk: BulkCreateSessionRequest =
+            serde_json::from_str(r#"{"count": 2, "allowed_ips": ["10.0.0.0/8"]}"#).unwrap();
+        assert_eq!(bulk.count, 2);
+        assert_eq!(bulk.options.allowed_ips.unwrap(), vec!["10.0.0.0/8".to_string()]);
+    }
+}
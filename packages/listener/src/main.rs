@@ -1,32 +1,83 @@
+mod api_version;
+mod archive;
+mod audit;
+mod badge;
+mod body;
 mod config;
+mod correlation;
+mod cors;
+mod diff;
 mod error;
+mod export;
+mod forwarder;
 mod handlers;
+mod metrics;
 mod models;
+mod ndjson;
+mod negotiate;
+mod notify;
+mod proto;
+mod pubsub;
+mod redact;
 mod redis_client;
+mod retry;
+mod session_id;
+mod signature;
 mod sse;
+mod template;
+mod ws;
 
-use actix_cors::Cors;
-use actix_web::{http::Method, web, App, HttpServer};
+use actix_web::middleware::{from_fn, Compress, Condition};
+use actix_web::{guard, http::Method, web, App, HttpServer};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 use tracing::{debug, error, info, warn};
 use tracing_actix_web::TracingLogger;
 
+use crate::archive::Archiver;
+use crate::audit::AuditLog;
 use crate::config::Settings;
+use crate::error::AppError;
+use crate::correlation::CorrelationRootSpan;
+use crate::cors::CorsOrigins;
 use crate::handlers::{
-    create_session_handler, fetch_requests_handler, health_check_handler,
-    ingest_webhook_handler, ingest_webhook_handler_base, stream_requests_handler,
+    bulk_create_sessions_handler, clear_requests_handler, count_requests_handler,
+    create_session_handler, delete_request_handler, diff_requests_handler,
+    export_requests_csv_handler, export_requests_har_handler, export_requests_ndjson_handler,
+    extend_session_handler, fetch_request_body_handler, fetch_request_curl_handler,
+    fetch_request_handler, fetch_requests_handler, garbage_collect_handler, get_session_handler,
+    health_check_handler, ingest_webhook_handler, ingest_webhook_handler_base,
+    ingest_webhook_subdomain_handler, list_sessions_handler, metrics_handler, openapi_handler,
+    pause_session_handler, replay_request_handler, request_count_badge_handler,
+    resume_session_handler, stream_requests_handler, stream_requests_ndjson_handler,
+    stream_requests_ws_handler, stream_stats_handler, subscriber_count_handler,
 };
+use crate::metrics::Metrics;
 use crate::redis_client::RedisClient;
 
 /// Application state shared across all handlers
 pub struct AppState {
     pub redis: Arc<RedisClient>,
     pub settings: Arc<Settings>,
+    pub metrics: Arc<Metrics>,
+    /// Shared HTTP client for outbound requests (forwarding)
+    pub http_client: reqwest::Client,
+    /// When the server booted, for `/health` uptime
+    pub start_time: Instant,
+    /// Background writer for permanent copies of captured requests
+    pub archiver: Archiver,
+    /// Append-only log of every ingest attempt
+    pub audit: AuditLog,
+    /// Signalled once when the server starts shutting down, so SSE streams
+    /// can say goodbye before the connection drops
+    pub shutdown: broadcast::Sender<()>,
 }
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
+    let start_time = Instant::now();
+
     // Load environment variables from .env file (if exists)
     dotenvy::dotenv().ok();
 
@@ -54,7 +105,8 @@ async fn main() -> anyhow::Result<()> {
     const RETRY_DELAY: u64 = 2; // seconds
     
     while redis_client.is_none() && retries < MAX_RETRIES {
-        match RedisClient::new(&settings.redis).await {
+        let sse = &settings.sse;
+        match RedisClient::new(&settings.redis, sse.channel_capacity, sse.pubsub).await {
             Ok(client) => {
                 info!("Successfully connected to Redis");
                 redis_client = Some(client);
@@ -88,12 +140,50 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // Create shared application state
+    let metrics = Arc::new(Metrics::new()?);
+    let (shutdown, _) = broadcast::channel(1);
     let app_state = web::Data::new(AppState {
         redis: redis_client.clone(),
         settings: settings.clone(),
+        metrics,
+        http_client: forwarder::build_client()?,
+        start_time,
+        archiver: Archiver::start(&settings.archive).await?,
+        audit: AuditLog::start(settings.server.audit_log_path.as_deref()).await?,
+        shutdown: shutdown.clone(),
+    });
+
+    // Spawn background task that drops SSE channels nobody is subscribed to
+    let sweeper_redis = redis_client.clone();
+    let sweep_interval = Duration::from_secs(settings.sse.sweep_interval_seconds.max(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(sweep_interval);
+
+        loop {
+            interval.tick().await;
+
+            let removed = sweeper_redis.sweep_idle_channels().await;
+            let channel_count = sweeper_redis.get_sse_channel_count().await;
+            if removed > 0 {
+                info!(
+                    removed = removed,
+                    remaining = channel_count,
+                    "Swept idle SSE channels"
+                );
+            } else {
+                debug!(active_channels = channel_count, "SSE sweep found no idle channels");
+            }
+        }
     });
 
-    // Spawn background task for maintenance (SSE cleanup + Redis keepalive)
+    // Spawn background task that redelivers failed forwards as they come due
+    tokio::spawn(retry::run(
+        redis_client.clone(),
+        app_state.http_client.clone(),
+        settings.clone(),
+    ));
+
+    // Spawn background task for maintenance (Redis keepalive)
     let maintenance_redis = redis_client.clone();
     let maintenance_api_url = api_url.clone();
     tokio::spawn(async move {
@@ -103,15 +193,6 @@ async fn main() -> anyhow::Result<()> {
         loop {
             interval.tick().await;
             
-            // Clean up stale SSE channels
-            let removed = maintenance_redis.cleanup_stale_sse_channels().await;
-            let channel_count = maintenance_redis.get_sse_channel_count().await;
-            debug!(
-                removed = removed,
-                active_channels = channel_count,
-                "Maintenance: SSE channel cleanup completed"
-            );
-            
             // Redis keepalive ping (also verifies connection health)
             match maintenance_redis.health_check().await {
                 Ok(true) => {
@@ -141,27 +222,126 @@ async fn main() -> anyhow::Result<()> {
     // Start HTTP server
     let server_host = settings.server.host.clone();
     let server_port = settings.server.port;
-    let cors_origins = settings.server.cors_allowed_origins.clone();
+    let cors_static = settings.server.cors_allowed_origins.clone();
+    let cors_origins = CorsOrigins::new(&cors_static);
+    if settings.server.cors_refresh_seconds > 0 {
+        cors_origins.spawn_refresh(
+            redis_client.clone(),
+            Duration::from_secs(settings.server.cors_refresh_seconds),
+        );
+    }
 
     info!("Binding to {}:{}", server_host, server_port);
     
-    HttpServer::new(move || {
-        // Configure CORS
-        let cors = build_cors(&cors_origins);
+    let server = HttpServer::new(move || {
+        // Configure CORS (origins are checked against the live allowlist)
+        let cors = cors_origins.build_cors(&cors_static);
 
         App::new()
             .app_data(app_state.clone())
             .app_data(web::PayloadConfig::new(settings.server.max_body_size))
-            .wrap(TracingLogger::default())
+            // Malformed path parameters (e.g. non-UTF-8 session IDs) get a
+            // JSON 400 instead of actix's plain-text 404
+            .app_data(web::PathConfig::default().error_handler(|err, _| {
+                AppError::BadRequest(format!("Invalid path parameter: {}", err)).into()
+            }))
+            .wrap(Condition::new(
+                settings.server.enable_compression,
+                Compress::default(),
+            ))
+            .wrap(from_fn(api_version::middleware))
+            .wrap(TracingLogger::<CorrelationRootSpan>::new())
             .wrap(cors)
+            // Outermost, so every response (CORS preflights included) gets
+            // `X-Request-Id` and `Server-Timing`
+            .wrap(from_fn(correlation::middleware))
+            // Subdomain ingestion takes precedence over every path below
+            .service(
+                web::scope("")
+                    .guard(guard::fn_guard({
+                        let domain = settings.server.subdomain_routing_domain.clone();
+                        move |ctx| {
+                            domain.as_deref().is_some_and(|domain| {
+                                handlers::subdomain_session_id(ctx.head(), domain).is_some()
+                            })
+                        }
+                    }))
+                    .default_service(web::to(ingest_webhook_subdomain_handler)),
+            )
             // Health check endpoint
             .route("/health", web::get().to(health_check_handler))
+            // Prometheus metrics
+            .route("/metrics", web::get().to(metrics_handler))
+            // API description (src/openapi.json, keep in sync with these routes)
+            .route("/openapi.json", web::get().to(openapi_handler))
             // Session creation
             .route("/c", web::post().to(create_session_handler))
+            .route("/c/bulk", web::post().to(bulk_create_sessions_handler))
+            .route("/c/{session_id}", web::get().to(get_session_handler))
+            .route("/sessions", web::get().to(list_sessions_handler))
+            .route("/admin/gc", web::post().to(garbage_collect_handler))
+            .route("/c/{session_id}/extend", web::post().to(extend_session_handler))
+            .route("/c/{session_id}/pause", web::post().to(pause_session_handler))
+            .route("/c/{session_id}/resume", web::post().to(resume_session_handler))
             // SSE stream
             .route("/s/{session_id}", web::get().to(stream_requests_handler))
-            // Fetch historical requests
-            .route("/r/{session_id}", web::get().to(fetch_requests_handler))
+            .route(
+                "/s/{session_id}/subscribers",
+                web::get().to(subscriber_count_handler),
+            )
+            .route("/s/{session_id}/stats", web::get().to(stream_stats_handler))
+            // Live NDJSON stream (for clients without SSE or WebSocket)
+            .route(
+                "/stream/{session_id}.ndjson",
+                web::get().to(stream_requests_ndjson_handler),
+            )
+            // WebSocket stream (alternative to SSE)
+            .route("/ws/{session_id}", web::get().to(stream_requests_ws_handler))
+            // Fetch or clear historical requests
+            .service(
+                web::resource("/r/{session_id}")
+                    .route(web::get().to(fetch_requests_handler))
+                    .route(web::delete().to(clear_requests_handler)),
+            )
+            // Request count only (cheap polling for badges)
+            .route("/r/{session_id}/count", web::get().to(count_requests_handler))
+            .route(
+                "/r/{session_id}/badge.svg",
+                web::get().to(request_count_badge_handler),
+            )
+            // Compare two captured requests
+            .route("/r/{session_id}/diff", web::get().to(diff_requests_handler))
+            // Export captured requests
+            .route(
+                "/r/{session_id}/export.csv",
+                web::get().to(export_requests_csv_handler),
+            )
+            .route(
+                "/r/{session_id}/export.ndjson",
+                web::get().to(export_requests_ndjson_handler),
+            )
+            .route(
+                "/r/{session_id}/export.har",
+                web::get().to(export_requests_har_handler),
+            )
+            // Individual captured requests
+            .service(
+                web::resource("/r/{session_id}/{request_id}")
+                    .route(web::get().to(fetch_request_handler))
+                    .route(web::delete().to(delete_request_handler)),
+            )
+            .route(
+                "/r/{session_id}/{request_id}/body",
+                web::get().to(fetch_request_body_handler),
+            )
+            .route(
+                "/r/{session_id}/{request_id}/curl",
+                web::get().to(fetch_request_curl_handler),
+            )
+            .route(
+                "/r/{session_id}/{request_id}/replay",
+                web::post().to(replay_request_handler),
+            )
             // Webhook ingestion (all HTTP methods) - base path
             .service(
                 web::resource("/i/{session_id}")
@@ -192,9 +372,22 @@ async fn main() -> anyhow::Result<()> {
     })?
     .workers(num_cpus::get())
     .shutdown_timeout(30)
-    .run()
-    .await
-    .map_err(|e| {
+    // Signals are handled below so SSE clients are notified before workers stop
+    .disable_signals()
+    .run();
+
+    let server_handle = server.handle();
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        info!(
+            sse_streams = shutdown.receiver_count(),
+            "Shutdown signal received, draining SSE streams"
+        );
+        let _ = shutdown.send(());
+        server_handle.stop(true).await;
+    });
+
+    server.await.map_err(|e| {
         eprintln!("Server error: {}", e);
         anyhow::anyhow!("Server error: {}", e)
     })?;
@@ -203,6 +396,30 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Resolve on Ctrl-C or, on Unix, SIGTERM
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Failed to listen for Ctrl-C: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
 /// Initialize tracing/logging subscriber
 fn init_tracing() {
     use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
@@ -214,26 +431,3 @@ fn init_tracing() {
         .with(tracing_subscriber::fmt::layer().with_target(true))
         .init();
 }
-
-/// Build CORS configuration
-fn build_cors(allowed_origins: &str) -> Cors {
-    if allowed_origins == "*" {
-        Cors::permissive()
-    } else {
-        let origins: Vec<&str> = allowed_origins.split(',').map(|s| s.trim()).collect();
-        let mut cors = Cors::default()
-            .allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"])
-            .allowed_headers(vec![
-                actix_web::http::header::CONTENT_TYPE,
-                actix_web::http::header::AUTHORIZATION,
-                actix_web::http::header::ACCEPT,
-            ])
-            .max_age(3600);
-
-        for origin in origins {
-            cors = cors.allowed_origin(origin);
-        }
-
-        cors
-    }
-}
@@ -0,0 +1,80 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+/// Prometheus metrics exposed at `/metrics`
+pub struct Metrics {
+    registry: Registry,
+    /// Total ingested webhooks, labeled by HTTP method
+    pub webhooks_ingested: IntCounterVec,
+    /// Sessions currently alive in Redis (refreshed on scrape)
+    pub active_sessions: IntGauge,
+    /// Live SSE broadcast channels
+    pub sse_channels: IntGauge,
+    /// Currently connected SSE clients
+    pub sse_connections: IntGauge,
+    /// Ingest handler latency in seconds
+    pub ingest_duration: Histogram,
+}
+
+impl Metrics {
+    /// Create and register all metrics
+    pub fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(Some("echohook".to_string()), None)?;
+
+        let webhooks_ingested = IntCounterVec::new(
+            Opts::new(
+                "webhooks_ingested_total",
+                "Total number of ingested webhooks",
+            ),
+            &["method"],
+        )?;
+        let active_sessions = IntGauge::new("active_sessions", "Number of active sessions")?;
+        let sse_channels = IntGauge::new("sse_channels", "Number of live SSE broadcast channels")?;
+        let sse_connections = IntGauge::new("sse_connections", "Number of connected SSE clients")?;
+        let ingest_duration = Histogram::with_opts(HistogramOpts::new(
+            "ingest_duration_seconds",
+            "Webhook ingest handler latency in seconds",
+        ))?;
+
+        registry.register(Box::new(webhooks_ingested.clone()))?;
+        registry.register(Box::new(active_sessions.clone()))?;
+        registry.register(Box::new(sse_channels.clone()))?;
+        registry.register(Box::new(sse_connections.clone()))?;
+        registry.register(Box::new(ingest_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            webhooks_ingested,
+            active_sessions,
+            sse_channels,
+            sse_connections,
+            ingest_duration,
+        })
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> anyhow::Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// Guard that tracks a connected SSE client for as long as it is alive
+pub struct SseConnectionGuard {
+    gauge: IntGauge,
+}
+
+impl SseConnectionGuard {
+    pub fn new(gauge: IntGauge) -> Self {
+        gauge.inc();
+        Self { gauge }
+    }
+}
+
+impl Drop for SseConnectionGuard {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
@@ -1,27 +1,420 @@
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-/// Session data stored in Redis
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Session data stored in Redis. `Debug` redacts the ingest token and
+/// signing secret.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Session {
     pub session_id: String,
     pub created_at: String,
     pub expires_at: String,
+    pub signed_receipt: bool,
+    pub forward_url: Option<String>,
+    /// Secret required to ingest into this session (never serialized)
+    #[serde(skip_serializing)]
+    pub ingest_token: Option<String>,
+    /// HMAC secret used to verify provider signatures (never serialized)
+    #[serde(skip_serializing)]
+    pub signing_secret: Option<String>,
+    /// Header holding the provider signature (lowercase)
+    pub signature_header: String,
+    /// Reject requests whose signature fails verification
+    pub reject_invalid_signatures: bool,
+    /// Capture `OPTIONS` requests instead of answering them as CORS preflights
+    pub capture_options_requests: bool,
+    /// Custom ingestion response body with `{{placeholder}}` substitution
+    pub response_template: Option<String>,
+    /// Answer ingestion with a redirect here instead of a response body
+    pub response_redirect_url: Option<String>,
+    /// Redirect status code (301, 302, 303, 307 or 308)
+    pub response_redirect_status: u16,
+    /// Answer ingestion with the received body and `Content-Type`
+    pub echo_body: bool,
+    /// Extra headers on capture responses, with `{{placeholder}}` substitution
+    pub response_headers: HashMap<String, String>,
+    /// Uppercase methods accepted for ingestion (empty = any method)
+    pub allowed_methods: Vec<String>,
+    /// Client networks allowed to ingest (empty = any address)
+    pub allowed_ips: Vec<IpNet>,
+    /// Lowercase header names kept on captured requests (empty = all)
+    pub store_headers_allowlist: Vec<String>,
+    /// Lowercase header names dropped from captured requests
+    pub store_headers_blocklist: Vec<String>,
+    /// Parse the `Cookie` header into each request's `cookies`
+    pub parse_cookies: bool,
+    /// Ingestion is acknowledged with `202` but nothing is captured
+    pub paused: bool,
+    /// JSON Schema that captured bodies are validated against
+    pub body_schema: Option<serde_json::Value>,
+    /// Reject bodies failing `body_schema` with 422 instead of just recording it
+    pub reject_invalid_body: bool,
+    /// JSON Patch applied to JSON bodies, stored as `body_transformed`
+    pub body_transform: Option<json_patch::Patch>,
+    /// Base64 `FileDescriptorSet` used to decode protobuf bodies into `body_json`
+    pub proto_descriptor: Option<String>,
+    /// Fully qualified message type of protobuf bodies, e.g. `acme.v1.Event`
+    pub proto_message_type: Option<String>,
+    /// Collapse identical requests (method, path, body) within this many seconds (0 = off)
+    pub dedupe_window_seconds: u64,
+    /// Slack/Discord incoming webhook notified of every capture
+    pub notify_webhook_url: Option<String>,
+    /// Uppercase methods that trigger a notification (empty = any method)
+    pub notify_on: Vec<String>,
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session")
+            .field("session_id", &self.session_id)
+            .field("created_at", &self.created_at)
+            .field("expires_at", &self.expires_at)
+            .field("signed_receipt", &self.signed_receipt)
+            .field("forward_url", &self.forward_url)
+            .field("ingest_token", &redacted(&self.ingest_token))
+            .field("signing_secret", &redacted(&self.signing_secret))
+            .field("signature_header", &self.signature_header)
+            .field("reject_invalid_signatures", &self.reject_invalid_signatures)
+            .field("capture_options_requests", &self.capture_options_requests)
+            .field("response_template", &self.response_template)
+            .field("response_redirect_url", &self.response_redirect_url)
+            .field("response_redirect_status", &self.response_redirect_status)
+            .field("echo_body", &self.echo_body)
+            .field("response_headers", &self.response_headers)
+            .field("allowed_methods", &self.allowed_methods)
+            .field("allowed_ips", &self.allowed_ips)
+            .field("store_headers_allowlist", &self.store_headers_allowlist)
+            .field("store_headers_blocklist", &self.store_headers_blocklist)
+            .field("parse_cookies", &self.parse_cookies)
+            .field("paused", &self.paused)
+            .field("body_schema", &self.body_schema)
+            .field("reject_invalid_body", &self.reject_invalid_body)
+            .field("body_transform", &self.body_transform)
+            .field("proto_descriptor", &self.proto_descriptor)
+            .field("proto_message_type", &self.proto_message_type)
+            .field("dedupe_window_seconds", &self.dedupe_window_seconds)
+            .field("notify_webhook_url", &self.notify_webhook_url)
+            .field("notify_on", &self.notify_on)
+            .finish()
+    }
+}
+
+/// Optional settings supplied when creating a session. `Debug` redacts the
+/// ingest token and signing secret, since options are logged with request
+/// spans
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateSessionRequest {
+    /// Return a signed receipt for every captured request
+    #[serde(default)]
+    pub signed_receipt: bool,
+    /// Upstream URL that captured requests are relayed to
+    pub forward_url: Option<String>,
+    /// Secret that ingestion must present via `X-Echohook-Token` or `?echohook_token=`
+    pub ingest_token: Option<String>,
+    /// HMAC-SHA256 secret for verifying provider signatures
+    pub signing_secret: Option<String>,
+    /// Header carrying the signature (defaults to `X-Hub-Signature-256`)
+    pub signature_header: Option<String>,
+    /// Return 401 instead of just recording failed verification
+    #[serde(default)]
+    pub reject_invalid_signatures: bool,
+    /// Store `OPTIONS` requests like any other instead of answering them as preflights
+    #[serde(default)]
+    pub capture_options_requests: bool,
+    /// Ingestion response body replacing the default JSON, e.g.
+    /// `{"ok":true,"id":"{{request_id}}"}`; supports `{{request_id}}`,
+    /// `{{session_id}}` and `{{timestamp}}`
+    pub response_template: Option<String>,
+    /// Redirect the caller here after capture (exclusive with `response_template`)
+    pub response_redirect_url: Option<String>,
+    /// Status used for `response_redirect_url` (defaults to `302`)
+    pub response_redirect_status: Option<u16>,
+    /// Respond with the exact bytes received instead of the JSON ack
+    /// (exclusive with `response_template` and `response_redirect_url`)
+    #[serde(default)]
+    pub echo_body: bool,
+    /// Headers added to capture responses, e.g. `{"X-Ack-Id": "{{request_id}}"}`
+    pub response_headers: Option<HashMap<String, String>>,
+    /// Only accept these HTTP methods for ingestion; others get `405`
+    pub allowed_methods: Option<Vec<String>>,
+    /// Only accept ingestion from these CIDRs (or single addresses); others get `403`
+    pub allowed_ips: Option<Vec<String>>,
+    /// Only store these headers (case-insensitive) on captured requests
+    pub store_headers_allowlist: Option<Vec<String>>,
+    /// Never store these headers (case-insensitive), e.g. `["Authorization", "Cookie"]`
+    pub store_headers_blocklist: Option<Vec<String>>,
+    /// Parse the `Cookie` header into a `cookies` map on each request
+    #[serde(default)]
+    pub parse_cookies: bool,
+    /// JSON Schema document to validate captured bodies against
+    pub body_schema: Option<serde_json::Value>,
+    /// Return 422 instead of just recording failed schema validation
+    #[serde(default)]
+    pub reject_invalid_body: bool,
+    /// JSON Patch (RFC 6902) operations applied to JSON bodies before storage,
+    /// e.g. `[{"op": "remove", "path": "/metadata"}]`
+    pub body_transform: Option<serde_json::Value>,
+    /// Base64-encoded `FileDescriptorSet` for decoding protobuf and gRPC-Web bodies
+    pub proto_descriptor: Option<String>,
+    /// Message type in `proto_descriptor` that bodies are decoded as
+    pub proto_message_type: Option<String>,
+    /// Skip requests identical (method, path, body) to one captured this many seconds ago
+    pub dedupe_window_seconds: Option<u64>,
+    /// Slack or Discord incoming webhook URL to notify on each capture
+    pub notify_webhook_url: Option<String>,
+    /// Only notify for these HTTP methods
+    pub notify_on: Option<Vec<String>>,
 }
 
+impl std::fmt::Debug for CreateSessionRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CreateSessionRequest")
+            .field("signed_receipt", &self.signed_receipt)
+            .field("forward_url", &self.forward_url)
+            .field("ingest_token", &redacted(&self.ingest_token))
+            .field("signing_secret", &redacted(&self.signing_secret))
+            .field("signature_header", &self.signature_header)
+            .field("reject_invalid_signatures", &self.reject_invalid_signatures)
+            .field("capture_options_requests", &self.capture_options_requests)
+            .field("response_template", &self.response_template)
+            .field("response_redirect_url", &self.response_redirect_url)
+            .field("response_redirect_status", &self.response_redirect_status)
+            .field("echo_body", &self.echo_body)
+            .field("response_headers", &self.response_headers)
+            .field("allowed_methods", &self.allowed_methods)
+            .field("allowed_ips", &self.allowed_ips)
+            .field("store_headers_allowlist", &self.store_headers_allowlist)
+            .field("store_headers_blocklist", &self.store_headers_blocklist)
+            .field("parse_cookies", &self.parse_cookies)
+            .field("body_schema", &self.body_schema)
+            .field("reject_invalid_body", &self.reject_invalid_body)
+            .field("body_transform", &self.body_transform)
+            .field("proto_descriptor", &self.proto_descriptor)
+            .field("proto_message_type", &self.proto_message_type)
+            .field("dedupe_window_seconds", &self.dedupe_window_seconds)
+            .field("notify_webhook_url", &self.notify_webhook_url)
+            .field("notify_on", &self.notify_on)
+            .finish()
+    }
+}
+
+/// Stand-in for a secret in `Debug` output
+fn redacted(secret: &Option<String>) -> Option<&'static str> {
+    secret.as_ref().map(|_| crate::redact::REDACTED)
+}
+
+/// Body of a bulk session create: how many sessions to make, plus the
+/// create options shared by all of them
+#[derive(Debug, Deserialize)]
+pub struct BulkCreateSessionRequest {
+    pub count: usize,
+    #[serde(flatten)]
+    pub options: CreateSessionRequest,
+}
+
+/// Parse an allowlist entry: a CIDR (`192.30.252.0/22`, `2001:db8::/32`) or a
+/// single address, treated as a host route
+pub fn parse_ip_network(value: &str) -> Option<IpNet> {
+    let value = value.trim();
+    value
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| value.parse::<std::net::IpAddr>().ok().map(IpNet::from))
+}
+
+/// Status codes accepted for `response_redirect_status`
+pub const REDIRECT_STATUSES: &[u16] = &[301, 302, 303, 307, 308];
+
+/// Default status for `response_redirect_url`
+pub const DEFAULT_REDIRECT_STATUS: u16 = 302;
+
+/// Default header checked for provider signatures
+pub const DEFAULT_SIGNATURE_HEADER: &str = "x-hub-signature-256";
+
 /// Captured webhook request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WebhookRequest {
     pub request_id: String,
+    /// Per-session capture counter (1-based), a tiebreaker for requests
+    /// captured within the same millisecond
+    #[serde(default)]
+    pub sequence: u64,
     pub method: String,
     pub path: String,
-    pub query_params: HashMap<String, String>,
+    /// Every value of each query parameter, in the order received
+    pub query_params: HashMap<String, Vec<String>>,
+    /// Query string exactly as received (minus the ingest token)
+    #[serde(default)]
+    pub raw_query: String,
+    /// Request-target from the request line, before any path normalization
+    /// (minus the ingest token)
+    #[serde(default)]
+    pub raw_target: String,
+    /// Normalized path after the session ID, e.g. `a/b/c` for `/i/{id}/a/b/c`
+    /// (empty for the base URL)
+    #[serde(default)]
+    pub subpath: String,
+    /// Session the request was sent to, when it was captured by the
+    /// dead-letter session because that one did not exist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_session_id: Option<String>,
     pub headers: HashMap<String, String>,
+    /// Whether headers were dropped or shortened to fit the configured limits
+    #[serde(default)]
+    pub headers_truncated: bool,
+    /// Trailer fields announced via the `Trailer` header. This is not a map of
+    /// trailer values: actix-web's HTTP/1 decoder rejects chunked bodies that
+    /// carry a trailer section (`400`), so only the announced names exist
+    #[serde(default)]
+    pub declared_trailers: Vec<String>,
+    /// Labels from the tag query parameter and header, for filtering captures
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub body: String,
+    /// How `body` is encoded: plain UTF-8 text, or base64 for binary payloads
+    pub body_encoding: BodyEncoding,
+    /// Parsed body when the Content-Type is JSON and the body is well-formed
+    pub body_json: Option<serde_json::Value>,
+    /// Whether the body parsed as JSON under a JSON Content-Type
+    pub body_valid_json: bool,
+    /// `body_json` after the session's `body_transform` patch
+    pub body_transformed: Option<serde_json::Value>,
+    /// Why the `body_transform` patch could not be applied
+    pub transform_error: Option<String>,
+    /// Why a protobuf body could not be decoded with the session's descriptor
+    #[serde(default)]
+    pub proto_error: Option<String>,
+    /// URL-decoded fields of a form-urlencoded body (absent for other bodies)
+    #[serde(default)]
+    pub form_fields: Option<HashMap<String, Vec<String>>>,
+    /// Parts of a `multipart/form-data` body (absent for other bodies)
+    #[serde(default)]
+    pub multipart_parts: Option<Vec<MultipartPart>>,
+    /// URL-decoded cookies from the `Cookie` header, with the session's
+    /// `parse_cookies` (absent otherwise)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookies: Option<HashMap<String, String>>,
     pub ip_address: String,
     pub user_agent: String,
+    pub content_type: String,
+    /// Media type guessed from the body when no `Content-Type` was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detected_content_type: Option<String>,
+    /// When the request was processed and stored
     pub timestamp: String,
+    /// When the request arrived, before any Redis calls or body reads; the
+    /// gap to `timestamp` is the time spent on ingest
+    #[serde(default)]
+    pub received_at: String,
+    /// Body size on the wire (before any decompression)
     pub content_length: usize,
+    /// Content-Encoding that was decompressed before storage (e.g. `gzip`)
+    pub original_encoding: Option<String>,
+    /// Body size after decompression (equals `content_length` when not compressed)
+    pub decoded_length: usize,
+    /// Body size on the wire in bytes (same as `content_length`)
+    #[serde(default)]
+    pub byte_length: usize,
+    /// Characters in the decoded body (absent for binary bodies)
+    #[serde(default)]
+    pub char_length: Option<usize>,
+    /// Lines in the decoded body, counting a final line without a newline
+    /// (absent for binary bodies)
+    #[serde(default)]
+    pub line_count: Option<usize>,
+    /// Upstream status when the session forwards requests
+    pub forward_status: Option<u16>,
+    /// Upstream response body, or the failure reason if forwarding failed
+    pub forward_response: Option<String>,
+    /// Every forwarding attempt so far, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delivery_attempts: Vec<DeliveryAttempt>,
+    /// Signature verification result (absent when the session has no signing secret)
+    pub signature_valid: Option<bool>,
+    /// `http` or `https`, honoring `Forwarded`/`X-Forwarded-Proto` from a proxy
+    pub scheme: Option<String>,
+    /// Protocol version of the connection to echohook, e.g. `HTTP/1.1` or `HTTP/2.0`
+    #[serde(default)]
+    pub http_version: Option<String>,
+    /// Address of the directly connected peer (often the proxy), unlike `ip_address`
+    pub peer_address: Option<String>,
+    /// TLS protocol version reported by a TLS-terminating proxy
+    pub tls_version: Option<String>,
+    /// Client certificate verification result reported by the proxy (e.g. `SUCCESS`)
+    pub client_cert_verify: Option<String>,
+    /// Subject DN of the client certificate forwarded by the proxy
+    pub client_cert_subject: Option<String>,
+    /// Schema validation result (absent when the session has no `body_schema`)
+    pub schema_valid: Option<bool>,
+    /// Why the body failed schema validation
+    #[serde(default)]
+    pub schema_errors: Vec<String>,
+    /// Whether only a prefix of the body was stored (see `BODY_TRUNCATE_BYTES`)
+    #[serde(default)]
+    pub truncated: bool,
+    /// Full (decoded) body size when the stored body was truncated
+    pub original_content_length: Option<usize>,
+}
+
+/// One try at relaying a captured request to the session's `forward_url`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryAttempt {
+    /// 1 for the initial delivery, counting up with each retry
+    pub attempt: u32,
+    pub attempted_at: String,
+    /// Upstream status (absent if no response was received)
+    pub status: Option<u16>,
+    /// Why no response was received
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// When the next retry is due, if this attempt is to be retried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<String>,
+}
+
+/// One part of a `multipart/form-data` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultipartPart {
+    /// Form field name from `Content-Disposition`
+    pub name: Option<String>,
+    /// Uploaded file name, for file parts
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    /// Full size of the part in bytes
+    pub size: usize,
+    /// Leading bytes of the part, as text or base64 for binary data
+    pub preview: String,
+    pub preview_encoding: BodyEncoding,
+    /// Whether `preview` holds only a prefix of the part
+    pub preview_truncated: bool,
+}
+
+/// Encoding of a stored request body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+impl BodyEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BodyEncoding::Utf8 => "utf8",
+            BodyEncoding::Base64 => "base64",
+        }
+    }
+
+    /// Parse a stored value, treating anything unknown (or missing) as UTF-8
+    pub fn from_stored(value: &str) -> Self {
+        match value {
+            "base64" => BodyEncoding::Base64,
+            _ => BodyEncoding::Utf8,
+        }
+    }
 }
 
 /// Response for session creation
@@ -34,11 +427,68 @@ pub struct CreateSessionResponse {
     pub expires_at: String,
 }
 
+/// Response for fetching an existing session's metadata
+#[derive(Debug, Serialize)]
+pub struct SessionInfoResponse {
+    #[serde(flatten)]
+    pub session: CreateSessionResponse,
+    pub request_count: usize,
+    pub paused: bool,
+}
+
+/// Optional body for extending a session
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtendSessionRequest {
+    /// Custom TTL from now, capped at the configured maximum
+    pub ttl_seconds: Option<u64>,
+}
+
+/// Response for session extension
+#[derive(Debug, Serialize)]
+pub struct ExtendSessionResponse {
+    pub session_id: String,
+    pub expires_at: String,
+}
+
 /// Response for webhook capture
 #[derive(Debug, Serialize)]
 pub struct CaptureResponse {
     pub status: String,
     pub request_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<Receipt>,
+}
+
+/// Response for ingestion into a paused session (`202 Accepted`)
+#[derive(Debug, Serialize)]
+pub struct PausedCaptureResponse {
+    pub status: String,
+}
+
+/// Response for a request collapsed into an earlier identical one
+#[derive(Debug, Serialize)]
+pub struct DuplicateCaptureResponse {
+    pub status: String,
+    /// The capture this request duplicates
+    pub request_id: String,
+}
+
+/// Response for pausing or resuming capture
+#[derive(Debug, Serialize)]
+pub struct PauseSessionResponse {
+    pub session_id: String,
+    pub paused: bool,
+}
+
+/// Signed proof that a webhook was received
+#[derive(Debug, Serialize)]
+pub struct Receipt {
+    pub request_id: String,
+    pub body_sha256: String,
+    pub timestamp: String,
+    /// Hex HMAC-SHA256 over `{request_id}.{body_sha256}.{timestamp}`
+    pub signature: String,
 }
 
 /// Response for fetching requests
@@ -46,16 +496,242 @@ pub struct CaptureResponse {
 pub struct RequestsResponse {
     pub session_id: String,
     pub total_requests: usize,
+    /// Page size that was applied (after clamping)
+    pub limit: usize,
+    pub offset: usize,
+    /// Number of requests in this page
+    pub returned: usize,
+    /// Whether more requests exist beyond this page
+    pub has_more: bool,
     pub requests: Vec<WebhookRequest>,
 }
 
+impl RequestsResponse {
+    /// One page of `total` requests, with its navigation flags filled in
+    pub fn page(
+        session_id: String,
+        requests: Vec<WebhookRequest>,
+        total: usize,
+        limit: usize,
+        offset: usize,
+    ) -> Self {
+        let returned = requests.len();
+        Self {
+            session_id,
+            total_requests: total,
+            limit,
+            offset,
+            returned,
+            has_more: offset + returned < total,
+            requests,
+        }
+    }
+}
+
+/// Summary of an active session, as listed by `GET /sessions`
+#[derive(Debug, Serialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub created_at: String,
+    pub expires_at: String,
+    pub request_count: usize,
+}
+
+/// Response for listing active sessions
+#[derive(Debug, Serialize)]
+pub struct SessionsResponse {
+    pub total_sessions: usize,
+    pub sessions: Vec<SessionSummary>,
+}
+
+/// Query parameters for listing sessions
+#[derive(Debug, Deserialize)]
+pub struct ListSessionsQuery {
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+impl ListSessionsQuery {
+    pub fn validated_limit(&self) -> usize {
+        self.limit.clamp(1, 1000)
+    }
+}
+
+/// Request body for replaying a captured request
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    pub target_url: String,
+}
+
+/// Upstream result of a replay
+#[derive(Debug, Serialize)]
+pub struct ReplayResponse {
+    pub request_id: String,
+    pub target_url: String,
+    /// Upstream HTTP status (`null` if the target could not be reached)
+    pub status: Option<u16>,
+    /// Upstream response body, or a description of the failure
+    pub response: String,
+}
+
+/// Response for counting a session's live stream clients
+#[derive(Debug, Serialize)]
+pub struct SubscriberCountResponse {
+    pub session_id: String,
+    pub subscribers: usize,
+}
+
+/// Response for a session's live stream statistics
+#[derive(Debug, Serialize)]
+pub struct StreamStatsResponse {
+    pub session_id: String,
+    /// SSE and WebSocket clients connected to this instance
+    pub subscribers: usize,
+    /// Times a client fell behind the broadcast channel
+    pub lag_events: u64,
+    /// Broadcast messages skipped by lagging clients
+    pub dropped_messages: u64,
+}
+
+/// Query parameters for the request-count badge
+#[derive(Debug, Deserialize)]
+pub struct BadgeQuery {
+    /// Left-hand text (defaults to `requests`)
+    pub label: Option<String>,
+}
+
+/// Response for counting a session's requests
+#[derive(Debug, Serialize)]
+pub struct RequestCountResponse {
+    pub session_id: String,
+    pub total_requests: usize,
+}
+
+/// Query parameters for diffing two captured requests
+#[derive(Debug, Deserialize)]
+pub struct DiffRequestsQuery {
+    pub a: String,
+    pub b: String,
+}
+
+/// A value that differs between the two requests (absent on one side when
+/// it was added or removed)
+#[derive(Debug, Serialize)]
+pub struct ValueChange {
+    pub a: Option<serde_json::Value>,
+    pub b: Option<serde_json::Value>,
+}
+
+/// Key-by-key comparison of two maps (headers or query parameters)
+#[derive(Debug, Default, Serialize)]
+pub struct MapDiff {
+    /// Keys only in `b`, with their values
+    pub added: BTreeMap<String, serde_json::Value>,
+    /// Keys only in `a`, with their values
+    pub removed: BTreeMap<String, serde_json::Value>,
+    /// Keys in both whose values differ
+    pub changed: BTreeMap<String, ValueChange>,
+}
+
+impl MapDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Kind of change at one location in a JSON body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One difference between two JSON bodies
+#[derive(Debug, Serialize)]
+pub struct JsonChange {
+    /// JSON Pointer to the differing location, e.g. `/data/object/status`
+    pub path: String,
+    pub op: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub a: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub b: Option<serde_json::Value>,
+}
+
+/// Comparison of two request bodies: a structural diff when both are JSON,
+/// a unified line diff when both are text, and sizes only for binary bodies
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BodyDiff {
+    Identical,
+    Json { changes: Vec<JsonChange> },
+    Text { unified: String },
+    Binary { a_length: usize, b_length: usize },
+}
+
+/// Response for diffing two captured requests
+#[derive(Debug, Serialize)]
+pub struct RequestDiffResponse {
+    pub session_id: String,
+    pub a: String,
+    pub b: String,
+    /// Whether method, path, headers, query parameters and body all match
+    pub identical: bool,
+    pub method: Option<ValueChange>,
+    pub path: Option<ValueChange>,
+    pub headers: MapDiff,
+    pub query_params: MapDiff,
+    pub body: BodyDiff,
+}
+
+/// Response for clearing all requests in a session
+#[derive(Debug, Serialize)]
+pub struct ClearRequestsResponse {
+    pub session_id: String,
+    pub deleted: usize,
+}
+
+/// What `POST /admin/gc` cleaned up
+#[derive(Debug, Default, Serialize)]
+pub struct GcResponse {
+    /// Active-session entries whose session had expired
+    pub sessions_removed: usize,
+    /// Request indexes left behind by expired sessions
+    pub indexes_removed: usize,
+    /// Index entries whose request hash had expired
+    pub requests_pruned: usize,
+}
+
 /// Query parameters for fetching requests
+///
+/// Filters are applied to the full request set before `limit`/`offset`, so
+/// pagination (and `total_requests`) operate on the filtered results.
 #[derive(Debug, Deserialize)]
 pub struct FetchRequestsQuery {
     #[serde(default = "default_limit")]
     pub limit: usize,
     #[serde(default)]
     pub offset: usize,
+    /// Content-type prefix filter (e.g. `application/json`)
+    pub content_type: Option<String>,
+    /// HTTP method filter (case-insensitive)
+    pub method: Option<String>,
+    /// Path substring filter
+    pub path_contains: Option<String>,
+    /// Header name filter (case-insensitive); alone it matches on presence
+    pub header_key: Option<String>,
+    /// Exact header value, compared against `header_key`
+    pub header_value: Option<String>,
+    /// Only requests carrying this tag
+    pub tag: Option<String>,
+    /// Only requests captured at or after this RFC3339 time
+    pub from: Option<DateTime<Utc>>,
+    /// Only requests captured at or before this RFC3339 time
+    pub to: Option<DateTime<Utc>>,
 }
 
 fn default_limit() -> usize {
@@ -64,7 +740,147 @@ fn default_limit() -> usize {
 
 impl FetchRequestsQuery {
     pub fn validated_limit(&self) -> usize {
-        self.limit.min(1000).max(1)
+        self.limit.clamp(1, 1000)
+    }
+
+    /// Whether a time range is set; it is resolved against the index scores
+    /// in Redis rather than by scanning
+    pub fn has_time_range(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+
+    /// Inclusive time range bounds in epoch milliseconds (the index scores)
+    pub fn time_range_millis(&self) -> (Option<i64>, Option<i64>) {
+        (
+            self.from.map(|t| t.timestamp_millis()),
+            self.to.map(|t| t.timestamp_millis()),
+        )
+    }
+
+    /// Whether any filter is set, requiring the full request set to be scanned
+    pub fn has_filters(&self) -> bool {
+        self.content_type.is_some()
+            || self.method.is_some()
+            || self.path_contains.is_some()
+            || self.header_key.is_some()
+            || self.tag.is_some()
+    }
+
+    /// Check whether a captured request matches all configured filters
+    pub fn matches(&self, request: &WebhookRequest) -> bool {
+        if let Some(content_type) = &self.content_type {
+            if !request
+                .content_type
+                .to_ascii_lowercase()
+                .starts_with(&content_type.to_ascii_lowercase())
+            {
+                return false;
+            }
+        }
+
+        if let Some(method) = &self.method {
+            if !request.method.eq_ignore_ascii_case(method) {
+                return false;
+            }
+        }
+
+        if let Some(path_contains) = &self.path_contains {
+            if !request.path.contains(path_contains.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(header_key) = &self.header_key {
+            let matched = request.headers.iter().any(|(key, value)| {
+                key.eq_ignore_ascii_case(header_key)
+                    && self.header_value.as_ref().is_none_or(|expected| value == expected)
+            });
+            if !matched {
+                return false;
+            }
+        }
+
+        if let Some(tag) = &self.tag {
+            if !request.tags.contains(tag) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Query parameters for the live NDJSON stream
+#[derive(Debug, Deserialize)]
+pub struct NdjsonStreamQuery {
+    /// Recent requests sent before switching to live (0 = live only)
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+/// Query parameters for the SSE stream
+#[derive(Debug, Deserialize)]
+pub struct StreamRequestsQuery {
+    /// Dot-separated path into the JSON body, e.g. `type` or `data.object.status`
+    pub filter_field: Option<String>,
+    /// Value the field must equal
+    pub filter_value: Option<String>,
+    /// Set to `false` to send every request as a plain `request` event
+    #[serde(default = "default_typed_events")]
+    pub typed_events: bool,
+}
+
+fn default_typed_events() -> bool {
+    true
+}
+
+impl StreamRequestsQuery {
+    /// Build the body filter, if one was requested
+    pub fn body_filter(&self) -> Option<BodyFilter> {
+        let field = self.filter_field.as_deref().filter(|f| !f.is_empty())?;
+        Some(BodyFilter {
+            path: field.split('.').map(str::to_string).collect(),
+            value: self.filter_value.clone().unwrap_or_default(),
+        })
+    }
+}
+
+/// Match on a field of a request's parsed JSON body
+#[derive(Debug, Clone)]
+pub struct BodyFilter {
+    path: Vec<String>,
+    value: String,
+}
+
+impl BodyFilter {
+    /// Whether the body is JSON and the field equals the expected value.
+    /// Strings compare by content; other values against the expected value
+    /// parsed as JSON (`true`, `42`).
+    pub fn matches(&self, request: &WebhookRequest) -> bool {
+        let Some(mut current) = request.body_json.as_ref() else {
+            return false;
+        };
+        for segment in &self.path {
+            current = match current {
+                serde_json::Value::Object(map) => match map.get(segment) {
+                    Some(value) => value,
+                    None => return false,
+                },
+                serde_json::Value::Array(items) => {
+                    match segment.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                        Some(value) => value,
+                        None => return false,
+                    }
+                }
+                _ => return false,
+            };
+        }
+
+        match current {
+            serde_json::Value::String(text) => *text == self.value,
+            other => serde_json::from_str::<serde_json::Value>(&self.value)
+                .is_ok_and(|expected| *other == expected),
+        }
     }
 }
 
@@ -76,5 +892,309 @@ pub struct HealthResponse {
     pub version: String,
     pub uptime_seconds: u64,
     pub sse_channels: usize,
+    /// Round-trip time of the Redis PING (`null` when Redis is unreachable)
+    pub redis_latency_ms: Option<f64>,
+    /// Number of unexpired sessions (`null` when Redis is unreachable)
+    pub active_sessions: Option<usize>,
 }
 
+
+/// HAR 1.2 document (HTTP Archive)
+#[derive(Debug, Serialize)]
+pub struct HarDocument {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    pub cache: HarCache,
+    pub timings: HarTimings,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub cookies: Vec<HarNameValue>,
+    pub headers: Vec<HarNameValue>,
+    pub query_string: Vec<HarNameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    pub status_text: String,
+    pub http_version: String,
+    pub cookies: Vec<HarNameValue>,
+    pub headers: Vec<HarNameValue>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HarNameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPostData {
+    pub mime_type: String,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    pub size: i64,
+    pub mime_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HarCache {}
+
+#[derive(Debug, Serialize)]
+pub struct HarTimings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(query_string: &str) -> FetchRequestsQuery {
+        actix_web::web::Query::<FetchRequestsQuery>::from_query(query_string)
+            .unwrap()
+            .into_inner()
+    }
+
+    fn request(method: &str, path: &str) -> WebhookRequest {
+        WebhookRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn method_filter_ignores_case() {
+        let query = query("method=post");
+        assert!(query.has_filters());
+        assert!(query.matches(&request("POST", "/i/abc/orders")));
+        assert!(!query.matches(&request("GET", "/i/abc/orders")));
+    }
+
+    #[test]
+    fn path_filter_matches_substrings() {
+        let query = query("path_contains=orders");
+        assert!(query.matches(&request("POST", "/i/abc/orders/42")));
+        assert!(query.matches(&request("GET", "/i/abc/orders")));
+        assert!(!query.matches(&request("POST", "/i/abc/refunds")));
+    }
+
+    #[test]
+    fn method_and_path_filters_must_both_match() {
+        let query = query("method=POST&path_contains=orders");
+        assert!(query.matches(&request("POST", "/i/abc/orders")));
+        assert!(!query.matches(&request("GET", "/i/abc/orders")));
+        assert!(!query.matches(&request("POST", "/i/abc/refunds")));
+    }
+
+    fn with_header(name: &str, value: &str) -> WebhookRequest {
+        WebhookRequest {
+            headers: HashMap::from([(name.to_string(), value.to_string())]),
+            ..request("POST", "/i/abc")
+        }
+    }
+
+    #[test]
+    fn header_filter_matches_names_case_insensitively() {
+        let query = query("header_key=x-github-event");
+        assert!(query.has_filters());
+        assert!(query.matches(&with_header("X-GitHub-Event", "push")));
+        assert!(!query.matches(&with_header("X-Gitlab-Event", "push")));
+        assert!(!query.matches(&request("POST", "/i/abc")));
+    }
+
+    #[test]
+    fn header_values_must_match_exactly() {
+        let query = query("header_key=X-GitHub-Event&header_value=push");
+        assert!(query.matches(&with_header("x-github-event", "push")));
+        assert!(!query.matches(&with_header("x-github-event", "Push")));
+        assert!(!query.matches(&with_header("x-github-event", "pull_request")));
+    }
+
+    #[test]
+    fn a_header_value_alone_filters_nothing() {
+        let query = query("header_value=push");
+        assert!(!query.has_filters());
+        assert!(query.matches(&request("POST", "/i/abc")));
+    }
+
+    #[test]
+    fn tag_filter_needs_the_exact_tag() {
+        let query = query("tag=prod");
+        assert!(query.has_filters());
+        let tagged = |tags: &[&str]| WebhookRequest {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..request("POST", "/i/abc")
+        };
+        assert!(query.matches(&tagged(&["staging", "prod"])));
+        assert!(!query.matches(&tagged(&["production"])));
+        assert!(!query.matches(&tagged(&[])));
+    }
+
+    fn page(total: usize, limit: usize, offset: usize) -> RequestsResponse {
+        let returned = total.saturating_sub(offset).min(limit);
+        let requests = vec![WebhookRequest::default(); returned];
+        RequestsResponse::page("abc".to_string(), requests, total, limit, offset)
+    }
+
+    #[test]
+    fn has_more_is_set_until_the_last_page() {
+        let first = page(25, 10, 0);
+        assert_eq!((first.returned, first.has_more), (10, true));
+        let middle = page(25, 10, 10);
+        assert_eq!((middle.returned, middle.has_more), (10, true));
+        let last = page(25, 10, 20);
+        assert_eq!((last.returned, last.has_more), (5, false));
+    }
+
+    #[test]
+    fn a_full_last_page_or_an_empty_one_has_no_more() {
+        let full = page(20, 10, 10);
+        assert_eq!((full.returned, full.has_more), (10, false));
+        let past_the_end = page(20, 10, 30);
+        assert_eq!((past_the_end.returned, past_the_end.has_more), (0, false));
+        assert_eq!(past_the_end.total_requests, 20);
+    }
+
+    #[test]
+    fn time_range_keeps_both_bounds() {
+        let range = query("from=2024-01-01T00:00:00Z&to=2024-01-01T00:00:01.5Z");
+        assert!(range.has_time_range());
+        assert!(!range.has_filters());
+        assert_eq!(
+            range.time_range_millis(),
+            (Some(1_704_067_200_000), Some(1_704_067_201_500))
+        );
+        assert_eq!(query("to=2024-01-01T00:00:00Z").time_range_millis().0, None);
+    }
+
+    #[test]
+    fn sessions_never_debug_print_their_secrets() {
+        let options = CreateSessionRequest {
+            signing_secret: Some("whsec_hunter2".to_string()),
+            ingest_token: Some("tok_swordfish".to_string()),
+            forward_url: Some("https://example.com/hook".to_string()),
+            ..Default::default()
+        };
+        let printed = format!("{:?}", options);
+        assert!(!printed.contains("hunter2"), "{}", printed);
+        assert!(!printed.contains("swordfish"), "{}", printed);
+        assert!(printed.contains("https://example.com/hook"));
+        assert!(printed.contains(crate::redact::REDACTED));
+
+        let now = Utc::now();
+        let session = crate::redis_client::new_session("abc", now, now, &options);
+        let printed = format!("{:?}", session);
+        assert!(!printed.contains("hunter2") && !printed.contains("swordfish"), "{}", printed);
+        assert!(printed.contains("abc"));
+    }
+
+    #[test]
+    fn no_filters_match_everything() {
+        let query = query("");
+        assert!(!query.has_filters());
+        assert!(query.matches(&request("DELETE", "/i/abc")));
+    }
+
+    fn body_filter(field: &str, value: &str) -> BodyFilter {
+        StreamRequestsQuery {
+            filter_field: Some(field.to_string()),
+            filter_value: Some(value.to_string()),
+            typed_events: true,
+        }
+        .body_filter()
+        .unwrap()
+    }
+
+    fn with_body(body: serde_json::Value) -> WebhookRequest {
+        WebhookRequest {
+            body_json: Some(body),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn body_filters_follow_dotted_paths_and_array_indexes() {
+        let event = with_body(serde_json::json!({
+            "type": "push",
+            "data": {"object": {"status": "paid"}},
+            "commits": [{"id": "c1"}, {"id": "c2"}],
+        }));
+        assert!(body_filter("type", "push").matches(&event));
+        assert!(!body_filter("type", "pull").matches(&event));
+        assert!(body_filter("data.object.status", "paid").matches(&event));
+        assert!(body_filter("commits.1.id", "c2").matches(&event));
+        assert!(!body_filter("commits.2.id", "c2").matches(&event));
+        assert!(!body_filter("type.name", "push").matches(&event));
+    }
+
+    #[test]
+    fn body_filters_compare_non_strings_as_json() {
+        let event = with_body(serde_json::json!({"type": "push", "live": true, "amount": 42}));
+        assert!(body_filter("live", "true").matches(&event));
+        assert!(body_filter("amount", "42").matches(&event));
+        assert!(!body_filter("amount", "forty-two").matches(&event));
+        // Strings compare by content, not as JSON
+        assert!(!body_filter("type", r#""push""#).matches(&event));
+    }
+
+    #[test]
+    fn body_filters_skip_non_json_bodies() {
+        let request = WebhookRequest {
+            body: "type=push".to_string(),
+            ..Default::default()
+        };
+        assert!(!body_filter("type", "push").matches(&request));
+
+        let no_field = StreamRequestsQuery {
+            filter_field: Some(String::new()),
+            filter_value: Some("push".to_string()),
+            typed_events: true,
+        };
+        assert!(no_field.body_filter().is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::error::AppError;
+use crate::export;
+use crate::models::WebhookRequest;
+use crate::redis_client::RedisClient;
+use actix_web::web::Bytes;
+use futures::{Stream, StreamExt};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::interval;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
+use tracing::{debug, info, warn};
+
+/// Interval between blank keep-alive lines (matches the SSE ping cadence)
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Live NDJSON feed: the `recent` requests (oldest first), then one line per
+/// request broadcast to the session. Blank lines are sent as keep-alives and
+/// the stream ends when the server shuts down.
+pub fn live_stream(
+    receiver: broadcast::Receiver<WebhookRequest>,
+    session_id: String,
+    recent: Vec<WebhookRequest>,
+    redis: Arc<RedisClient>,
+    shutdown: broadcast::Receiver<()>,
+) -> impl Stream<Item = Result<Bytes, AppError>> + 'static {
+    info!(session_id = %session_id, recent = recent.len(), "Creating NDJSON stream");
+
+    // The receiver was subscribed before the dump was loaded, so skip live
+    // requests that the dump already covered. IDs are compared by identity,
+    // not order: concurrent ingests (and other instances) can save requests
+    // out of ID order.
+    let sent: HashSet<String> = recent.iter().map(|r| r.request_id.clone()).collect();
+    let sent = Arc::new(sent);
+    let recent_stream =
+        futures::stream::iter(recent.into_iter().map(|request| export::ndjson_line(&request)));
+
+    let live_requests = BroadcastStream::new(receiver).filter_map(move |result| {
+        let session_id = session_id.clone();
+        let sent = sent.clone();
+        let redis = redis.clone();
+        async move {
+            if let Err(BroadcastStreamRecvError::Lagged(count)) = &result {
+                if let Err(e) = redis.record_stream_lag(&session_id, *count).await {
+                    warn!(
+                        session_id = %session_id,
+                        error = %e,
+                        "Failed to record NDJSON lag"
+                    );
+                }
+            }
+            live_line(result, &sent, &session_id)
+        }
+    });
+
+    let keepalive = IntervalStream::new(interval(KEEPALIVE_INTERVAL))
+        .skip(1)
+        .map(|_| Ok(Bytes::from_static(b"\n")));
+
+    let live = futures::stream::select(live_requests, keepalive)
+        .take_until(BroadcastStream::new(shutdown).into_future());
+
+    recent_stream.chain(live)
+}
+
+/// The line for one broadcast item: a request not already in `sent`, or
+/// nothing for duplicates and lag notices
+fn live_line(
+    result: Result<WebhookRequest, BroadcastStreamRecvError>,
+    sent: &HashSet<String>,
+    session_id: &str,
+) -> Option<Result<Bytes, AppError>> {
+    match result {
+        Ok(request) => {
+            if sent.contains(&request.request_id) {
+                return None;
+            }
+            debug!(
+                session_id = %session_id,
+                request_id = %request.request_id,
+                "Sending request via NDJSON stream"
+            );
+            Some(export::ndjson_line(&request))
+        }
+        Err(BroadcastStreamRecvError::Lagged(count)) => {
+            warn!(
+                session_id = %session_id,
+                lagged = count,
+                "NDJSON receiver lagged, messages dropped"
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(request_id: &str) -> WebhookRequest {
+        WebhookRequest {
+            request_id: request_id.to_string(),
+            method: "POST".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Lines the live half of the stream produces for what was broadcast
+    async fn live_lines(
+        receiver: broadcast::Receiver<WebhookRequest>,
+        recent: &[WebhookRequest],
+    ) -> Vec<String> {
+        let sent: HashSet<String> = recent.iter().map(|r| r.request_id.clone()).collect();
+        BroadcastStream::new(receiver)
+            .filter_map(|result| async { live_line(result, &sent, "abc") })
+            .map(|line| String::from_utf8(line.unwrap().to_vec()).unwrap())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn a_request_captured_after_connecting_arrives_as_a_line() {
+        let (sender, receiver) = broadcast::channel(16);
+        let recent = vec![request("0191-b")];
+        sender.send(request("0191-c")).unwrap();
+        drop(sender);
+
+        let lines = live_lines(receiver, &recent).await;
+        assert_eq!(lines.len(), 1);
+        let parsed: serde_json::Value = serde_json::from_str(lines[0].trim_end()).unwrap();
+        assert_eq!(parsed["request_id"], "0191-c");
+        assert!(lines[0].ends_with('\n'));
+    }
+
+    #[tokio::test]
+    async fn only_requests_in_the_dump_are_skipped() {
+        let (sender, receiver) = broadcast::channel(16);
+        let recent = vec![request("0191-a"), request("0191-c")];
+        // Saved between subscribing and loading the dump
+        sender.send(request("0191-c")).unwrap();
+        // Created earlier but saved later, so missing from the dump
+        sender.send(request("0191-b")).unwrap();
+        drop(sender);
+
+        let lines = live_lines(receiver, &recent).await;
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("\"0191-b\""), "{}", lines[0]);
+    }
+}
@@ -0,0 +1,215 @@
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Formats the ingest acknowledgment can be rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckFormat {
+    Json,
+    Xml,
+    Text,
+}
+
+impl AckFormat {
+    /// Pick the format the client prefers from its `Accept` header, by
+    /// q-value and then header order. JSON is used when the header is
+    /// missing or names nothing supported, rather than failing with 406.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let Some(accept) = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Self::Json;
+        };
+
+        let mut ranges: Vec<(f32, Self)> = accept
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let format = Self::for_media_range(parts.next()?.trim())?;
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (quality > 0.0).then_some((quality, format))
+            })
+            .collect();
+        // Stable, so equally weighted ranges keep their header order
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.first().map_or(Self::Json, |(_, format)| *format)
+    }
+
+    fn for_media_range(range: &str) -> Option<Self> {
+        let range = range.to_ascii_lowercase();
+        match range.as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Self::Json),
+            "application/xml" | "text/xml" => Some(Self::Xml),
+            "text/plain" | "text/*" => Some(Self::Text),
+            _ if range.ends_with("+json") => Some(Self::Json),
+            _ if range.ends_with("+xml") => Some(Self::Xml),
+            _ => None,
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Xml => "application/xml; charset=utf-8",
+            Self::Text => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// Finish an acknowledgment in the format the client asked for
+pub fn ack<T: Serialize>(
+    mut response: HttpResponseBuilder,
+    req: &HttpRequest,
+    body: &T,
+) -> HttpResponse {
+    let format = AckFormat::from_request(req);
+    let value = serde_json::to_value(body).unwrap_or_default();
+    let rendered = match format {
+        AckFormat::Json => value.to_string(),
+        AckFormat::Xml => {
+            let mut xml = String::from("<response>");
+            write_xml(&value, &mut xml);
+            xml.push_str("</response>");
+            xml
+        }
+        AckFormat::Text => {
+            let mut lines = Vec::new();
+            write_text("", &value, &mut lines);
+            lines.join("\n") + "\n"
+        }
+    };
+    response.content_type(format.content_type()).body(rendered)
+}
+
+/// Write an object's fields as child elements (arrays repeat the element)
+fn write_xml(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                let items = match value {
+                    Value::Array(items) => items.iter().collect(),
+                    value => vec![value],
+                };
+                for item in items {
+                    out.push('<');
+                    out.push_str(name);
+                    out.push('>');
+                    write_xml(item, out);
+                    out.push_str("</");
+                    out.push_str(name);
+                    out.push('>');
+                }
+            }
+        }
+        Value::String(text) => out.push_str(&escape_xml(text)),
+        Value::Null => {}
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// Escape text for use in XML content or attribute values
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Write `key: value` lines, naming nested fields with dotted keys
+fn write_text(prefix: &str, value: &Value, lines: &mut Vec<String>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                let key = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                write_text(&key, value, lines);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                write_text(prefix, item, lines);
+            }
+        }
+        Value::String(text) => lines.push(format!("{}: {}", prefix, text)),
+        Value::Null => {}
+        other => lines.push(format!("{}: {}", prefix, other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    async fn ack_for(accept: Option<&str>, body: &Value) -> (String, String) {
+        let mut req = TestRequest::post();
+        if let Some(accept) = accept {
+            req = req.insert_header((header::ACCEPT, accept));
+        }
+        let response = ack(HttpResponse::Ok(), &req.to_http_request(), body);
+        let content_type = response.headers().get(header::CONTENT_TYPE).unwrap();
+        let content_type = content_type.to_str().unwrap().to_string();
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn captured() -> Value {
+        json!({"status": "captured", "request_id": "req-1"})
+    }
+
+    #[actix_web::test]
+    async fn json_is_the_default() {
+        for accept in [None, Some("application/json"), Some("*/*"), Some("image/png")] {
+            let (content_type, body) = ack_for(accept, &captured()).await;
+            assert_eq!(content_type, "application/json", "{:?}", accept);
+            assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), captured());
+        }
+    }
+
+    #[actix_web::test]
+    async fn xml_is_sent_when_asked_for() {
+        for accept in ["application/xml", "text/xml", "application/soap+xml"] {
+            let (content_type, body) = ack_for(Some(accept), &captured()).await;
+            assert_eq!(content_type, "application/xml; charset=utf-8", "{}", accept);
+            assert_eq!(
+                body,
+                "<response><status>captured</status><request_id>req-1</request_id></response>"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn text_is_sent_when_asked_for() {
+        let (content_type, body) = ack_for(Some("text/plain"), &captured()).await;
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+        assert_eq!(body, "status: captured\nrequest_id: req-1\n");
+    }
+
+    #[actix_web::test]
+    async fn quality_values_pick_the_preferred_format() {
+        let accept = "application/json;q=0.5, text/plain;q=0.9, application/xml;q=0";
+        let (content_type, _) = ack_for(Some(accept), &captured()).await;
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+    }
+
+    #[actix_web::test]
+    async fn xml_text_is_escaped() {
+        let body = json!({"status": "<captured> & \"done\""});
+        let (_, xml) = ack_for(Some("application/xml"), &body).await;
+        assert_eq!(
+            xml,
+            "<response><status>&lt;captured&gt; &amp; &quot;done&quot;</status></response>"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::models::{BodyEncoding, WebhookRequest};
+use reqwest::{Client, Url};
+use serde_json::json;
+use tracing::{debug, warn};
+
+/// Characters of the body included in a notification message
+const BODY_PREVIEW_CHARS: usize = 500;
+
+/// Post a short summary of a captured request to a Slack or Discord incoming
+/// webhook. Failures are logged and otherwise ignored.
+pub async fn notify(client: &Client, url: &str, session_id: &str, request: &WebhookRequest) {
+    let message = format_message(session_id, request);
+    // Discord expects `content`; Slack (and most compatible services) `text`
+    let payload = if is_discord(url) {
+        json!({ "content": message })
+    } else {
+        json!({ "text": message })
+    };
+
+    let result = async {
+        client
+            .post(url)
+            .header("content-type", "application/json")
+            .body(payload.to_string())
+            .send()
+            .await?
+            .error_for_status()
+    }
+    .await;
+
+    match result {
+        Ok(response) => debug!(
+            request_id = %request.request_id,
+            status = response.status().as_u16(),
+            "Sent capture notification"
+        ),
+        Err(e) => warn!(
+            request_id = %request.request_id,
+            error = %e,
+            "Failed to send capture notification"
+        ),
+    }
+}
+
+fn is_discord(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .is_some_and(|host| {
+            host == "discord.com"
+                || host == "discordapp.com"
+                || host.ends_with(".discord.com")
+                || host.ends_with(".discordapp.com")
+        })
+}
+
+fn format_message(session_id: &str, request: &WebhookRequest) -> String {
+    let target = if request.raw_query.is_empty() {
+        request.path.clone()
+    } else {
+        format!("{}?{}", request.path, request.raw_query)
+    };
+
+    let body = match request.body_encoding {
+        BodyEncoding::Base64 => format!("_(binary, {} bytes)_", request.decoded_length),
+        BodyEncoding::Utf8 if request.body.is_empty() => "_(empty)_".to_string(),
+        BodyEncoding::Utf8 => {
+            let mut preview: String = request.body.chars().take(BODY_PREVIEW_CHARS).collect();
+            if preview.len() < request.body.len() {
+                preview.push('…');
+            }
+            format!("```\n{}\n```", preview.replace("```", "'''"))
+        }
+    };
+
+    format!(
+        "*{}* `{}` captured on session `{}` at {}\n{}",
+        request.method, target, session_id, request.timestamp, body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accept one request, answer `200`, and hand back its raw bytes
+    async fn serve_once() -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                raw.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&raw).to_ascii_lowercase();
+                let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                    continue;
+                };
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |value| value.trim().parse().unwrap());
+                if body.len() >= length {
+                    break;
+                }
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(raw).unwrap()
+        });
+        (url, received)
+    }
+
+    fn captured(body: &str) -> WebhookRequest {
+        WebhookRequest {
+            request_id: "req-1".to_string(),
+            method: "POST".to_string(),
+            path: "/i/abc/github".to_string(),
+            raw_query: "x=1".to_string(),
+            timestamp: "2024-01-01T00:00:00Z".to_string(),
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn a_summary_is_posted_to_the_notification_url() {
+        let (url, received) = serve_once().await;
+
+        notify(&Client::new(), &url, "abc", &captured(r#"{"type":"push"}"#)).await;
+
+        let raw = received.await.unwrap();
+        assert!(raw.starts_with("POST /hook HTTP/1.1\r\n"), "{}", raw);
+        let body = raw.split_once("\r\n\r\n").unwrap().1;
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        let text = payload["text"].as_str().unwrap();
+        assert!(text.starts_with("*POST* `/i/abc/github?x=1` captured on session `abc`"));
+        assert!(text.contains("2024-01-01T00:00:00Z"), "{}", text);
+        assert!(text.contains(r#"{"type":"push"}"#), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn unreachable_notification_urls_are_only_logged() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+
+        notify(&Client::new(), &url, "abc", &captured("")).await;
+    }
+
+    #[test]
+    fn discord_hosts_get_a_content_payload() {
+        assert!(is_discord("https://discord.com/api/webhooks/1/x"));
+        assert!(is_discord("https://canary.discordapp.com/api/webhooks/1/x"));
+        assert!(!is_discord("https://hooks.slack.com/services/x"));
+        assert!(!is_discord("https://discord.com.evil.example/x"));
+    }
+
+    #[test]
+    fn long_bodies_are_truncated_in_the_message() {
+        let message = format_message("abc", &captured(&"a".repeat(BODY_PREVIEW_CHARS + 1)));
+        let preview = format!("```\n{}…\n```", "a".repeat(BODY_PREVIEW_CHARS));
+        assert!(message.ends_with(&preview), "{}", message);
+        assert!(format_message("abc", &captured("")).ends_with("_(empty)_"));
+    }
+}
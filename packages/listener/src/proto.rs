@@ -0,0 +1,195 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde_json::Value;
+
+/// Length of a gRPC message frame header: one flag byte and a big-endian u32 length
+const GRPC_FRAME_HEADER_LEN: usize = 5;
+
+/// How a protobuf body is wrapped on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    /// The serialized message itself (`application/x-protobuf`)
+    None,
+    /// gRPC length-prefixed frames (`application/grpc-web`, `application/grpc`)
+    Grpc,
+    /// Base64 of gRPC frames (`application/grpc-web-text`)
+    GrpcText,
+}
+
+/// Classify a `Content-Type`, returning `None` for non-protobuf bodies
+fn framing(content_type: &str) -> Option<Framing> {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    match media_type.as_str() {
+        "application/x-protobuf" | "application/protobuf" | "application/vnd.google.protobuf" => {
+            Some(Framing::None)
+        }
+        "application/grpc" | "application/grpc+proto" | "application/grpc-web"
+        | "application/grpc-web+proto" => Some(Framing::Grpc),
+        "application/grpc-web-text" | "application/grpc-web-text+proto" => {
+            Some(Framing::GrpcText)
+        }
+        _ => None,
+    }
+}
+
+/// Whether a `Content-Type` indicates a protobuf or gRPC(-Web) body
+pub fn is_protobuf_content_type(content_type: &str) -> bool {
+    framing(content_type).is_some()
+}
+
+/// Resolve a message type from a base64-encoded `FileDescriptorSet`
+pub fn message_descriptor(
+    descriptor_set: &str,
+    message_type: &str,
+) -> Result<MessageDescriptor, String> {
+    let bytes = BASE64
+        .decode(descriptor_set.trim())
+        .map_err(|e| format!("descriptor is not valid base64: {}", e))?;
+    let pool = DescriptorPool::decode(bytes.as_slice())
+        .map_err(|e| format!("invalid FileDescriptorSet: {}", e))?;
+    pool.get_message_by_name(message_type.trim_start_matches('.'))
+        .ok_or_else(|| format!("message type {} not found in descriptor", message_type))
+}
+
+/// Decode a protobuf body into its canonical JSON form. gRPC-Web bodies are
+/// unframed first; only the first data frame is decoded (trailer frames are
+/// skipped).
+pub fn decode_body(
+    content_type: &str,
+    descriptor: &MessageDescriptor,
+    body: &[u8],
+) -> Result<Value, String> {
+    let text_decoded;
+    let message = match framing(content_type) {
+        Some(Framing::None) | None => body,
+        Some(Framing::Grpc) => first_grpc_message(body)?,
+        Some(Framing::GrpcText) => {
+            text_decoded = BASE64
+                .decode(body.trim_ascii())
+                .map_err(|e| format!("grpc-web-text body is not valid base64: {}", e))?;
+            first_grpc_message(&text_decoded)?
+        }
+    };
+
+    let message = DynamicMessage::decode(descriptor.clone(), message)
+        .map_err(|e| format!("failed to decode {}: {}", descriptor.full_name(), e))?;
+    serde_json::to_value(&message).map_err(|e| e.to_string())
+}
+
+/// Payload of the first data frame in a gRPC length-prefixed stream
+fn first_grpc_message(mut body: &[u8]) -> Result<&[u8], String> {
+    while body.len() >= GRPC_FRAME_HEADER_LEN {
+        let flags = body[0];
+        let length = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+        let frame = body
+            .get(GRPC_FRAME_HEADER_LEN..GRPC_FRAME_HEADER_LEN + length)
+            .ok_or("gRPC frame is longer than the body")?;
+
+        // The high bit marks gRPC-Web trailer frames
+        if flags & 0x80 == 0 {
+            if flags & 0x01 != 0 {
+                return Err("compressed gRPC messages are not supported".to_string());
+            }
+            return Ok(frame);
+        }
+        body = &body[GRPC_FRAME_HEADER_LEN + length..];
+    }
+    Err("no gRPC data frame in body".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_reflect::prost::Message;
+    use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+    use prost_reflect::prost_types::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    };
+    use serde_json::json;
+
+    /// `message Event { string kind = 1; int32 count = 2; }` in package `test`,
+    /// as a base64 `FileDescriptorSet`
+    fn event_descriptor_set() -> String {
+        let field = |name: &str, number, kind: Type| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            json_name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(kind as i32),
+            ..Default::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("event.proto".to_string()),
+                package: Some("test".to_string()),
+                syntax: Some("proto3".to_string()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Event".to_string()),
+                    field: vec![field("kind", 1, Type::String), field("count", 2, Type::Int32)],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        BASE64.encode(set.encode_to_vec())
+    }
+
+    /// `Event { kind: "push", count: 3 }` on the wire
+    const EVENT: &[u8] = b"\x0a\x04push\x10\x03";
+
+    fn grpc_frame(flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![flags];
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn protobuf_bodies_decode_to_json() {
+        let descriptor = message_descriptor(&event_descriptor_set(), ".test.Event").unwrap();
+        let decoded = decode_body("application/x-protobuf", &descriptor, EVENT).unwrap();
+        assert_eq!(decoded, json!({"kind": "push", "count": 3}));
+    }
+
+    #[test]
+    fn grpc_web_bodies_are_unframed_first() {
+        let descriptor = message_descriptor(&event_descriptor_set(), "test.Event").unwrap();
+        let mut body = grpc_frame(0x80, b"grpc-status: 0\r\n");
+        body.extend(grpc_frame(0, EVENT));
+        body.extend(grpc_frame(0x80, b"grpc-status: 0\r\n"));
+        let expected = json!({"kind": "push", "count": 3});
+
+        let decoded = decode_body("application/grpc-web+proto", &descriptor, &body);
+        assert_eq!(decoded, Ok(expected.clone()));
+        let text = BASE64.encode(&body);
+        let decoded = decode_body("application/grpc-web-text", &descriptor, text.as_bytes());
+        assert_eq!(decoded, Ok(expected));
+    }
+
+    #[test]
+    fn undecodable_bodies_and_descriptors_are_errors() {
+        let descriptor_set = event_descriptor_set();
+        assert!(message_descriptor(&descriptor_set, "test.Missing").is_err());
+        assert!(message_descriptor("not base64!", "test.Event").is_err());
+        assert!(message_descriptor("AAAA", "test.Event").is_err());
+
+        let descriptor = message_descriptor(&descriptor_set, "test.Event").unwrap();
+        assert!(decode_body("application/x-protobuf", &descriptor, b"\x0a\x09push").is_err());
+        let compressed = grpc_frame(0x01, EVENT);
+        assert!(decode_body("application/grpc", &descriptor, &compressed).is_err());
+        let truncated = &grpc_frame(0, EVENT)[..4];
+        assert!(decode_body("application/grpc", &descriptor, truncated).is_err());
+    }
+
+    #[test]
+    fn protobuf_content_types_are_recognized() {
+        assert!(is_protobuf_content_type("application/x-protobuf"));
+        assert!(is_protobuf_content_type("Application/gRPC-Web-Text; charset=utf-8"));
+        assert!(!is_protobuf_content_type("application/json"));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::models::WebhookRequest;
+use futures::StreamExt;
+use redis::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Pub/sub channel prefix; requests for a session are published to `sse:{session_id}`
+const CHANNEL_PREFIX: &str = "sse";
+
+/// Pause before reconnecting a dropped pub/sub connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Local broadcast channels by session ID, shared with `RedisClient`
+pub type SseChannels = Arc<RwLock<HashMap<String, broadcast::Sender<WebhookRequest>>>>;
+
+/// Pub/sub channel carrying a session's requests
+pub fn channel_name(session_id: &str) -> String {
+    format!("{}:{}", CHANNEL_PREFIX, session_id)
+}
+
+/// A captured request as published to other instances
+#[derive(Serialize, Deserialize)]
+pub struct RelayMessage {
+    /// Instance that captured the request (and already broadcast it locally)
+    pub origin: String,
+    pub request: WebhookRequest,
+}
+
+enum Command {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+/// Feeds requests captured by other instances into the local broadcast
+/// channels. Each Redis node gets one pub/sub connection, subscribed to the
+/// sessions that have local subscribers and routed to by session ID.
+pub struct Relay {
+    /// This process, so it can skip the requests it published itself
+    pub instance_id: String,
+    shards: Vec<mpsc::UnboundedSender<Command>>,
+}
+
+impl Relay {
+    /// Spawn one subscriber task per Redis node
+    pub fn start(clients: Vec<Client>, channels: SseChannels) -> Self {
+        let instance_id = Uuid::now_v7().to_string();
+        let shards = clients
+            .into_iter()
+            .map(|client| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                tokio::spawn(run(client, receiver, channels.clone(), instance_id.clone()));
+                sender
+            })
+            .collect();
+        info!(instance_id = %instance_id, "Started SSE pub/sub relay");
+        Self {
+            instance_id,
+            shards,
+        }
+    }
+
+    /// Start receiving a session's requests from other instances
+    pub fn subscribe(&self, shard: usize, session_id: &str) {
+        let _ = self.shards[shard].send(Command::Subscribe(channel_name(session_id)));
+    }
+
+    /// Stop receiving a session's requests once it has no local subscribers
+    pub fn unsubscribe(&self, shard: usize, session_id: &str) {
+        let _ = self.shards[shard].send(Command::Unsubscribe(channel_name(session_id)));
+    }
+}
+
+/// Keep one node's pub/sub connection alive, re-subscribing to every wanted
+/// channel after a reconnect, until the relay is dropped
+async fn run(
+    client: Client,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    channels: SseChannels,
+    instance_id: String,
+) {
+    let mut wanted: HashSet<String> = HashSet::new();
+
+    loop {
+        let pubsub = match client.get_async_pubsub().await {
+            Ok(pubsub) => pubsub,
+            Err(e) => {
+                warn!(error = %e, "Failed to open Redis pub/sub connection, retrying");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        let (mut sink, mut messages) = pubsub.split();
+
+        let mut resubscribed = true;
+        for channel in &wanted {
+            if let Err(e) = sink.subscribe(channel).await {
+                warn!(channel = %channel, error = %e, "Failed to resubscribe to pub/sub channel");
+                resubscribed = false;
+                break;
+            }
+        }
+        if !resubscribed {
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            continue;
+        }
+
+        loop {
+            tokio::select! {
+                command = commands.recv() => {
+                    let result = match command {
+                        Some(Command::Subscribe(channel)) => {
+                            let result = sink.subscribe(&channel).await;
+                            wanted.insert(channel);
+                            result
+                        }
+                        Some(Command::Unsubscribe(channel)) => {
+                            let result = sink.unsubscribe(&channel).await;
+                            wanted.remove(&channel);
+                            result
+                        }
+                        None => return,
+                    };
+                    if let Err(e) = result {
+                        warn!(error = %e, "Redis pub/sub command failed, reconnecting");
+                        break;
+                    }
+                }
+                message = messages.next() => match message {
+                    Some(message) => deliver(&message, &channels, &instance_id).await,
+                    None => {
+                        warn!("Redis pub/sub connection closed, reconnecting");
+                        break;
+                    }
+                },
+            }
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Hand a request published by another instance to the local subscribers
+async fn deliver(message: &redis::Msg, channels: &SseChannels, instance_id: &str) {
+    let Some(session_id) = message
+        .get_channel_name()
+        .strip_prefix(CHANNEL_PREFIX)
+        .and_then(|name| name.strip_prefix(':'))
+    else {
+        return;
+    };
+    let relayed: RelayMessage = match message
+        .get_payload::<String>()
+        .map_err(|e| e.to_string())
+        .and_then(|payload| serde_json::from_str(&payload).map_err(|e| e.to_string()))
+    {
+        Ok(relayed) => relayed,
+        Err(e) => {
+            warn!(session_id = %session_id, error = %e, "Ignoring malformed pub/sub message");
+            return;
+        }
+    };
+    if relayed.origin == instance_id {
+        return;
+    }
+
+    if let Some(sender) = channels.read().await.get(session_id) {
+        debug!(
+            session_id = %session_id,
+            request_id = %relayed.request.request_id,
+            origin = %relayed.origin,
+            "Relaying request from another instance"
+        );
+        let _ = sender.send(relayed.request);
+    }
+}
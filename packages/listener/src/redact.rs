@@ -0,0 +1,116 @@
+use crate::config::RedactSettings;
+use crate::models::WebhookRequest;
+use std::collections::HashMap;
+
+/// Replacement for redacted header and JSON field values
+pub const REDACTED: &str = "***REDACTED***";
+
+/// Redact configured headers and top-level JSON body fields in place, so the
+/// secrets are never stored, broadcast or logged in the clear
+pub fn redact_request(request: &mut WebhookRequest, settings: &RedactSettings) {
+    redact_headers(&mut request.headers, &settings.headers);
+    // Parsed cookies carry the same secrets as the `Cookie` header
+    if settings.headers.iter().any(|n| n.eq_ignore_ascii_case("cookie")) {
+        if let Some(cookies) = request.cookies.as_mut() {
+            for value in cookies.values_mut() {
+                *value = REDACTED.to_string();
+            }
+        }
+    }
+
+    if let Some(body_json) = request.body_json.as_mut() {
+        if redact_json_fields(body_json, &settings.json_fields) {
+            // Keep the raw body consistent with the redacted JSON
+            request.body = body_json.to_string();
+        }
+    }
+    if let Some(body_transformed) = request.body_transformed.as_mut() {
+        redact_json_fields(body_transformed, &settings.json_fields);
+    }
+}
+
+/// Replace the values of headers whose names match (case-insensitively)
+fn redact_headers(headers: &mut HashMap<String, String>, names: &[String]) {
+    for (name, value) in headers.iter_mut() {
+        if names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            *value = REDACTED.to_string();
+        }
+    }
+}
+
+/// Replace matching top-level fields of a JSON object; returns whether any matched
+fn redact_json_fields(body: &mut serde_json::Value, fields: &[String]) -> bool {
+    let Some(object) = body.as_object_mut() else {
+        return false;
+    };
+
+    let mut redacted = false;
+    for field in fields {
+        if let Some(value) = object.get_mut(field) {
+            *value = serde_json::Value::String(REDACTED.to_string());
+            redacted = true;
+        }
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings() -> RedactSettings {
+        RedactSettings {
+            headers: vec!["Authorization".to_string(), "cookie".to_string()],
+            json_fields: vec!["password".to_string(), "token".to_string()],
+        }
+    }
+
+    #[test]
+    fn matching_headers_are_redacted_case_insensitively() {
+        let mut request = WebhookRequest {
+            headers: HashMap::from([
+                ("authorization".to_string(), "Bearer s3cret".to_string()),
+                ("cookie".to_string(), "session=abc".to_string()),
+                ("content-type".to_string(), "application/json".to_string()),
+            ]),
+            cookies: Some(HashMap::from([("session".to_string(), "abc".to_string())])),
+            ..Default::default()
+        };
+        redact_request(&mut request, &settings());
+
+        assert_eq!(request.headers["authorization"], REDACTED);
+        assert_eq!(request.headers["cookie"], REDACTED);
+        assert_eq!(request.headers["content-type"], "application/json");
+        assert_eq!(request.cookies.unwrap()["session"], REDACTED);
+    }
+
+    #[test]
+    fn matching_top_level_json_fields_are_redacted() {
+        let body = json!({"user": "ada", "password": "hunter2", "nested": {"token": "t"}});
+        let mut request = WebhookRequest {
+            body: body.to_string(),
+            body_json: Some(body.clone()),
+            body_transformed: Some(body),
+            ..Default::default()
+        };
+        redact_request(&mut request, &settings());
+
+        let expected = json!({"user": "ada", "password": REDACTED, "nested": {"token": "t"}});
+        assert_eq!(request.body_json.as_ref(), Some(&expected));
+        assert_eq!(request.body_transformed.as_ref(), Some(&expected));
+        assert!(!request.body.contains("hunter2"));
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&request.body).unwrap(), expected);
+    }
+
+    #[test]
+    fn bodies_without_matching_fields_are_untouched() {
+        let mut request = WebhookRequest {
+            body: "{\"user\": \"ada\"}".to_string(),
+            body_json: Some(json!({"user": "ada"})),
+            ..Default::default()
+        };
+        redact_request(&mut request, &settings());
+        assert_eq!(request.body, "{\"user\": \"ada\"}");
+    }
+}
@@ -1,11 +1,17 @@
 use crate::config::RedisSettings;
-use crate::error::AppResult;
-use crate::models::{Session, WebhookRequest};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    parse_ip_network, BodyEncoding, CreateSessionRequest, DeliveryAttempt, GcResponse, Session,
+    SessionSummary, WebhookRequest, DEFAULT_REDIRECT_STATUS, DEFAULT_SIGNATURE_HEADER,
+};
+use crate::pubsub::{self, Relay, RelayMessage, SseChannels};
+use crate::retry::ForwardJob;
 use chrono::{DateTime, Utc};
-use redis::aio::ConnectionManager;
-use redis::{AsyncCommands, Client as RedisClient2};
+use redis::aio::{ConnectionLike, ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, Client as RedisClient2, Cmd, Pipeline, RedisFuture, Value};
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::{broadcast, RwLock};
 use tracing::{debug, info, instrument, warn};
 
@@ -13,38 +19,134 @@ use tracing::{debug, info, instrument, warn};
 const SESSION_PREFIX: &str = "session";
 const REQUEST_PREFIX: &str = "request";
 
+/// Sorted set of live sessions, scored by expiry (unix seconds)
+const ACTIVE_SESSIONS_KEY: &str = "sessions:active";
+
+/// Per-IP ingest counters, one key per fixed window
+const IP_RATE_LIMIT_PREFIX: &str = "ratelimit:ip";
+
+/// Per-session ingest counters, one key per fixed window
+const SESSION_RATE_LIMIT_PREFIX: &str = "ratelimit:session";
+
+/// Times a dedupe claim is retried when the key it lost to expires before
+/// it can be read
+const DEDUPE_CLAIM_ATTEMPTS: usize = 3;
+
+/// Delete `KEYS[1]` only if it still holds `ARGV[1]`
+const RELEASE_IF_OWNED_SCRIPT: &str =
+    "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) end return 0";
+
+/// Sorted set of queued forwarding retries (JSON jobs scored by due time in ms)
+const FORWARD_RETRY_KEY: &str = "forward:retries";
+
+/// Request index scores are `capture_ms * SCORE_SEQUENCE_SLOTS` plus the
+/// request's slot within that millisecond, so requests sharing a
+/// millisecond still get distinct, capture-ordered scores. The slot count
+/// is bounded by Redis storing scores as f64; beyond it, requests to one
+/// session in the same millisecond share the last slot and the index orders
+/// them by member (pages are still sorted by full sequence).
+const SCORE_SEQUENCE_SLOTS: i64 = 1000;
+
+/// Increment the session sequence `KEYS[1]` and the per-millisecond counter
+/// `KEYS[2]` together, returning the sequence and the request's slot within
+/// the millisecond (from 0). The counter lives for a second.
+const NEXT_SEQUENCE_SCRIPT: &str = "local sequence = redis.call('INCR', KEYS[1]) \
+     local slot = redis.call('INCR', KEYS[2]) - 1 \
+     redis.call('PEXPIRE', KEYS[2], 1000) \
+     return {sequence, slot}";
+
+/// Keys requested per `SCAN`/`ZSCAN` round trip during garbage collection
+const GC_SCAN_COUNT: usize = 200;
+
+/// Pause before retrying a command that failed on a dropped connection,
+/// giving the ConnectionManager a chance to reconnect
+const COMMAND_RETRY_DELAY: Duration = Duration::from_millis(200);
+
 /// Redis client wrapper with automatic reconnection and SSE broadcast support
+///
+/// Session data is sharded across one or more Redis nodes by session ID;
+/// global keys (active session index, API URL) live on the first node.
+/// SSE broadcast channels are in-memory and per-process; with the pub/sub
+/// relay enabled, captured requests are also published through Redis so
+/// clients connected to other instances receive them.
 pub struct RedisClient {
-    /// ConnectionManager handles automatic reconnection on failures
-    connection: ConnectionManager,
+    /// One ConnectionManager per node; each handles automatic reconnection
+    connections: Vec<ConnectionManager>,
     /// Broadcast channels for SSE by session_id
-    sse_channels: RwLock<HashMap<String, broadcast::Sender<WebhookRequest>>>,
+    sse_channels: SseChannels,
+    /// Buffer size for newly created broadcast channels
+    sse_capacity: usize,
+    /// Cross-instance delivery of captured requests (`SSE_PUBSUB`)
+    relay: Option<Relay>,
 }
 
 impl RedisClient {
-    /// Create a new Redis client with automatic reconnection
-    pub async fn new(settings: &RedisSettings) -> anyhow::Result<Self> {
-        let client = RedisClient2::open(settings.url.as_str())?;
-        // ConnectionManager provides automatic reconnection on connection failures
-        let connection = ConnectionManager::new(client).await?;
+    /// Create a new Redis client with automatic reconnection.
+    ///
+    /// `settings.url` may be a comma-separated list of URLs to shard across.
+    /// `sse_pubsub` starts the relay that shares requests between instances.
+    pub async fn new(
+        settings: &RedisSettings,
+        sse_capacity: usize,
+        sse_pubsub: bool,
+    ) -> anyhow::Result<Self> {
+        // Reconnection attempts back off exponentially up to the configured cap
+        let config = ConnectionManagerConfig::new()
+            .set_min_delay(Duration::from_millis(settings.reconnect_min_delay_ms))
+            .set_max_delay(Duration::from_millis(settings.reconnect_max_delay_ms))
+            .set_exponent_base(2.0)
+            .set_number_of_retries(settings.reconnect_retries);
+
+        let mut clients = Vec::new();
+        let mut connections = Vec::new();
+        for url in settings.urls() {
+            let client = RedisClient2::open(url)?;
+            // ConnectionManager provides automatic reconnection on connection failures
+            connections
+                .push(ConnectionManager::new_with_config(client.clone(), config.clone()).await?);
+            clients.push(client);
+        }
+        if connections.is_empty() {
+            anyhow::bail!("No Redis URL configured");
+        }
+
+        let sse_channels: SseChannels = Arc::new(RwLock::new(HashMap::new()));
+        let relay = sse_pubsub.then(|| Relay::start(clients, sse_channels.clone()));
 
         Ok(Self {
-            connection,
-            sse_channels: RwLock::new(HashMap::new()),
+            connections,
+            sse_channels,
+            sse_capacity: sse_capacity.max(1),
+            relay,
         })
     }
 
-    /// Get a connection (ConnectionManager handles reconnection automatically)
-    fn get_connection(&self) -> ConnectionManager {
+    /// Get a connection to the primary node, used for global keys
+    /// (ConnectionManager handles reconnection automatically)
+    fn get_connection(&self) -> RetryingConnection {
         // ConnectionManager is Clone and handles reconnection internally
-        self.connection.clone()
+        RetryingConnection(self.connections[0].clone())
+    }
+
+    /// Get a connection to the node that owns a session's keys
+    fn connection_for(&self, session_id: &str) -> RetryingConnection {
+        RetryingConnection(self.connections[self.shard_for(session_id)].clone())
     }
 
-    /// Get or create a broadcast channel for a session
+    /// Index of the node that owns a session's keys
+    fn shard_for(&self, session_id: &str) -> usize {
+        shard_index(session_id, self.connections.len())
+    }
+
+    /// Get or create a broadcast channel for a session and subscribe to it.
+    /// Returns `None` when the channel already has `max_subscribers`
+    /// receivers (0 = unlimited); the check and the subscription happen under
+    /// one lock so concurrent clients can't overshoot the limit.
     pub async fn get_sse_channel(
         &self,
         session_id: &str,
-    ) -> broadcast::Receiver<WebhookRequest> {
+        max_subscribers: usize,
+    ) -> Option<broadcast::Receiver<WebhookRequest>> {
         info!(
             session_id = %session_id,
             "Getting SSE channel for session"
@@ -53,21 +155,32 @@ impl RedisClient {
 
         if let Some(sender) = channels.get(session_id) {
             let receiver_count = sender.receiver_count();
+            if max_subscribers > 0 && receiver_count >= max_subscribers {
+                warn!(
+                    session_id = %session_id,
+                    receiver_count = receiver_count,
+                    "SSE channel is at its subscriber limit"
+                );
+                return None;
+            }
             info!(
                 session_id = %session_id,
                 receiver_count = receiver_count,
                 "Subscribing to existing SSE channel"
             );
-            sender.subscribe()
+            Some(sender.subscribe())
         } else {
-            // Create a new channel with capacity for 256 messages
             info!(
                 session_id = %session_id,
+                capacity = self.sse_capacity,
                 "Creating new SSE broadcast channel"
             );
-            let (tx, rx) = broadcast::channel(256);
+            let (tx, rx) = broadcast::channel(self.sse_capacity);
             channels.insert(session_id.to_string(), tx);
-            rx
+            if let Some(relay) = &self.relay {
+                relay.subscribe(self.shard_for(session_id), session_id);
+            }
+            Some(rx)
         }
     }
 
@@ -109,37 +222,71 @@ impl RedisClient {
         }
     }
 
+    /// Publish a saved request for SSE clients connected to other instances.
+    /// Failures are logged; the capture itself already succeeded.
+    async fn publish_request(&self, session_id: &str, request: &WebhookRequest) {
+        let Some(relay) = &self.relay else {
+            return;
+        };
+        let message = RelayMessage {
+            origin: relay.instance_id.clone(),
+            request: request.clone(),
+        };
+        let result = match serde_json::to_string(&message) {
+            Ok(payload) => self
+                .connection_for(session_id)
+                .publish::<_, _, ()>(pubsub::channel_name(session_id), payload)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            warn!(
+                session_id = %session_id,
+                request_id = %request.request_id,
+                error = %e,
+                "Failed to publish request to other instances"
+            );
+        }
+    }
+
     /// Clean up SSE channel when no subscribers remain
     pub async fn cleanup_sse_channel(&self, session_id: &str) {
         let mut channels = self.sse_channels.write().await;
         if let Some(sender) = channels.get(session_id) {
             if sender.receiver_count() == 0 {
                 channels.remove(session_id);
+                if let Some(relay) = &self.relay {
+                    relay.unsubscribe(self.shard_for(session_id), session_id);
+                }
                 debug!(session_id = %session_id, "Cleaned up SSE channel");
             }
         }
     }
 
-    /// Clean up all stale SSE channels (no subscribers)
-    pub async fn cleanup_stale_sse_channels(&self) -> usize {
+    /// Remove every SSE channel that has no subscribers, returning how many
+    /// were dropped.
+    ///
+    /// The write lock is held for the whole sweep so a client subscribing via
+    /// `get_sse_channel` can't have its channel removed out from under it.
+    pub async fn sweep_idle_channels(&self) -> usize {
         let mut channels = self.sse_channels.write().await;
         let before_count = channels.len();
-        
+
         channels.retain(|session_id, sender| {
             let receiver_count = sender.receiver_count();
             if receiver_count == 0 {
-                debug!(session_id = %session_id, "Removing stale SSE channel");
+                debug!(session_id = %session_id, "Removing idle SSE channel");
+                if let Some(relay) = &self.relay {
+                    relay.unsubscribe(self.shard_for(session_id), session_id);
+                }
                 false
             } else {
                 true
             }
         });
-        
-        let removed = before_count - channels.len();
-        if removed > 0 {
-            info!(removed = removed, remaining = channels.len(), "Cleaned up stale SSE channels");
-        }
-        removed
+
+        before_count - channels.len()
     }
 
     /// Get count of active SSE channels (for monitoring)
@@ -147,46 +294,141 @@ impl RedisClient {
         self.sse_channels.read().await.len()
     }
 
+    /// Live SSE and WebSocket clients subscribed to a session (0 without a channel)
+    pub async fn subscriber_count(&self, session_id: &str) -> usize {
+        self.sse_channels
+            .read()
+            .await
+            .get(session_id)
+            .map_or(0, |sender| sender.receiver_count())
+    }
+
+    /// Count broadcast messages a lagging SSE or WebSocket client missed.
+    /// The counters live next to the session and expire with it.
+    #[instrument(skip(self))]
+    pub async fn record_stream_lag(&self, session_id: &str, dropped: u64) -> AppResult<()> {
+        let mut conn = self.connection_for(session_id);
+        let session_key = format!("{}:{}", SESSION_PREFIX, session_id);
+        let stats_key = format!("{}:{}:stream_stats", SESSION_PREFIX, session_id);
+
+        let ttl: i64 = conn.ttl(&session_key).await?;
+        if ttl <= 0 {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        pipe.hincr(&stats_key, "lag_events", 1)
+            .hincr(&stats_key, "dropped_messages", dropped)
+            .expire(&stats_key, ttl);
+        pipe.query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+
+    /// Lag counters recorded by `record_stream_lag`, as
+    /// `(lag_events, dropped_messages)`
+    #[instrument(skip(self))]
+    pub async fn stream_lag(&self, session_id: &str) -> AppResult<(u64, u64)> {
+        let mut conn = self.connection_for(session_id);
+        let stats_key = format!("{}:{}:stream_stats", SESSION_PREFIX, session_id);
+        let (lag_events, dropped_messages): (Option<u64>, Option<u64>) = redis::cmd("HMGET")
+            .arg(&stats_key)
+            .arg("lag_events")
+            .arg("dropped_messages")
+            .query_async(&mut conn)
+            .await?;
+        Ok((lag_events.unwrap_or(0), dropped_messages.unwrap_or(0)))
+    }
+
     /// Check Redis health
     #[instrument(skip(self))]
     pub async fn health_check(&self) -> AppResult<bool> {
-        let mut conn = self.get_connection();
-        let result: String = redis::cmd("PING").query_async(&mut conn).await?;
-        Ok(result == "PONG")
+        for connection in &self.connections {
+            let mut conn = connection.clone();
+            let result: String = redis::cmd("PING").query_async(&mut conn).await?;
+            if result != "PONG" {
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 
     /// Create a new session
-    #[instrument(skip(self))]
-    pub async fn create_session(&self, session_id: &str, ttl_seconds: u64) -> AppResult<Session> {
-        let mut conn = self.get_connection();
+    #[instrument(skip(self, options))]
+    pub async fn create_session(
+        &self,
+        session_id: &str,
+        ttl_seconds: u64,
+        options: &CreateSessionRequest,
+        max_sessions: usize,
+    ) -> AppResult<Session> {
+        let mut sessions = self
+            .create_sessions(&[session_id.to_string()], ttl_seconds, options, max_sessions)
+            .await?;
+        Ok(sessions.remove(0))
+    }
+
+    /// Create sessions sharing the same options. Slots for all of them are
+    /// reserved together (so either every session fits under `max_sessions`
+    /// or none is created), and each node's sessions are written in a
+    /// single pipeline.
+    #[instrument(skip(self, session_ids, options), fields(count = session_ids.len()))]
+    pub async fn create_sessions(
+        &self,
+        session_ids: &[String],
+        ttl_seconds: u64,
+        options: &CreateSessionRequest,
+        max_sessions: usize,
+    ) -> AppResult<Vec<Session>> {
+        let mut index_conn = self.get_connection();
         let now = Utc::now();
         let expires_at = now + chrono::Duration::seconds(ttl_seconds as i64);
 
-        let session = Session {
-            session_id: session_id.to_string(),
-            created_at: now.to_rfc3339(),
-            expires_at: expires_at.to_rfc3339(),
-        };
+        // Reserve slots first: prune expired members, add ours, and back out
+        // if that pushed the count over the limit (0 = unlimited)
+        let mut reserve = redis::pipe();
+        reserve
+            .atomic()
+            .zrembyscore(ACTIVE_SESSIONS_KEY, "-inf", now.timestamp())
+            .ignore();
+        for session_id in session_ids {
+            reserve
+                .zadd(ACTIVE_SESSIONS_KEY, session_id, expires_at.timestamp())
+                .ignore();
+        }
+        let (active,): (usize,) = reserve
+            .zcard(ACTIVE_SESSIONS_KEY)
+            .query_async(&mut index_conn)
+            .await?;
+        if max_sessions > 0 && active > max_sessions {
+            let _: () = index_conn.zrem(ACTIVE_SESSIONS_KEY, session_ids).await?;
+            return Err(AppError::SessionLimitExceeded(max_sessions));
+        }
 
-        let key = format!("{}:{}", SESSION_PREFIX, session_id);
+        let sessions: Vec<Session> = session_ids
+            .iter()
+            .map(|session_id| new_session(session_id, now, expires_at, options))
+            .collect();
 
-        // Use a pipeline for atomic operations
-        redis::pipe()
-            .hset(&key, "session_id", &session.session_id)
-            .hset(&key, "created_at", &session.created_at)
-            .hset(&key, "expires_at", &session.expires_at)
-            .expire(&key, ttl_seconds as i64)
-            .query_async::<()>(&mut conn)
-            .await?;
+        let mut pipes: HashMap<usize, Pipeline> = HashMap::new();
+        for session in &sessions {
+            let shard = shard_index(&session.session_id, self.connections.len());
+            queue_session_writes(pipes.entry(shard).or_default(), session, ttl_seconds)?;
+        }
+        for (shard, pipe) in pipes {
+            let mut conn = RetryingConnection(self.connections[shard].clone());
+            pipe.query_async::<()>(&mut conn).await?;
+        }
 
-        debug!(session_id = %session_id, "Created new session");
-        Ok(session)
+        for session in &sessions {
+            debug!(session_id = %session.session_id, "Created new session");
+        }
+        Ok(sessions)
     }
 
     /// Get a session by ID
     #[instrument(skip(self))]
     pub async fn get_session(&self, session_id: &str) -> AppResult<Option<Session>> {
-        let mut conn = self.get_connection();
+        let mut conn = self.connection_for(session_id);
         let key = format!("{}:{}", SESSION_PREFIX, session_id);
 
         let data: HashMap<String, String> = conn.hgetall(&key).await?;
@@ -199,27 +441,305 @@ impl RedisClient {
             session_id: data.get("session_id").cloned().unwrap_or_default(),
             created_at: data.get("created_at").cloned().unwrap_or_default(),
             expires_at: data.get("expires_at").cloned().unwrap_or_default(),
+            signed_receipt: data.get("signed_receipt").is_some_and(|v| v == "true"),
+            forward_url: data.get("forward_url").cloned(),
+            ingest_token: data.get("ingest_token").cloned(),
+            signing_secret: data.get("signing_secret").cloned(),
+            signature_header: data
+                .get("signature_header")
+                .cloned()
+                .unwrap_or_else(|| DEFAULT_SIGNATURE_HEADER.to_string()),
+            reject_invalid_signatures: data
+                .get("reject_invalid_signatures")
+                .is_some_and(|v| v == "true"),
+            capture_options_requests: data
+                .get("capture_options_requests")
+                .is_some_and(|v| v == "true"),
+            response_template: data.get("response_template").cloned(),
+            response_redirect_url: data.get("response_redirect_url").cloned(),
+            response_redirect_status: data
+                .get("response_redirect_status")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_REDIRECT_STATUS),
+            echo_body: data.get("echo_body").is_some_and(|v| v == "true"),
+            response_headers: data
+                .get("response_headers")
+                .and_then(|headers| serde_json::from_str(headers).ok())
+                .unwrap_or_default(),
+            allowed_methods: data
+                .get("allowed_methods")
+                .map(|methods| methods.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+            allowed_ips: data
+                .get("allowed_ips")
+                .map(|networks| networks.split(',').filter_map(parse_ip_network).collect())
+                .unwrap_or_default(),
+            store_headers_allowlist: data
+                .get("store_headers_allowlist")
+                .map(|names| names.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+            store_headers_blocklist: data
+                .get("store_headers_blocklist")
+                .map(|names| names.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+            parse_cookies: data.get("parse_cookies").is_some_and(|v| v == "true"),
+            paused: data.get("paused").is_some_and(|v| v == "true"),
+            body_schema: data
+                .get("body_schema")
+                .and_then(|schema| serde_json::from_str(schema).ok()),
+            reject_invalid_body: data.get("reject_invalid_body").is_some_and(|v| v == "true"),
+            body_transform: data
+                .get("body_transform")
+                .and_then(|patch| serde_json::from_str(patch).ok()),
+            proto_descriptor: data.get("proto_descriptor").cloned(),
+            proto_message_type: data.get("proto_message_type").cloned(),
+            dedupe_window_seconds: data
+                .get("dedupe_window_seconds")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+            notify_webhook_url: data.get("notify_webhook_url").cloned(),
+            notify_on: data
+                .get("notify_on")
+                .map(|methods| methods.split(',').map(String::from).collect())
+                .unwrap_or_default(),
         }))
     }
 
     /// Check if a session exists
     #[instrument(skip(self))]
     pub async fn session_exists(&self, session_id: &str) -> AppResult<bool> {
-        let mut conn = self.get_connection();
+        let mut conn = self.connection_for(session_id);
         let key = format!("{}:{}", SESSION_PREFIX, session_id);
         let exists: bool = conn.exists(&key).await?;
         Ok(exists)
     }
 
+    /// Re-apply the TTL to a session and its request index, plus all stored
+    /// requests when `extend_requests` is set (i.e. requests share the session
+    /// TTL). Returns the new `expires_at`, or `None` if the session no longer exists.
+    #[instrument(skip(self))]
+    pub async fn extend_session(
+        &self,
+        session_id: &str,
+        ttl_seconds: u64,
+        extend_requests: bool,
+    ) -> AppResult<Option<String>> {
+        let mut conn = self.connection_for(session_id);
+        let key = format!("{}:{}", SESSION_PREFIX, session_id);
+        let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
+
+        let extended: bool = conn.expire(&key, ttl_seconds as i64).await?;
+        if !extended {
+            return Ok(None);
+        }
+
+        let expires = Utc::now() + chrono::Duration::seconds(ttl_seconds as i64);
+        let expires_at = expires.to_rfc3339();
+        let request_ids = if extend_requests {
+            self.get_request_ids(session_id).await?
+        } else {
+            Vec::new()
+        };
+
+        let mut pipe = redis::pipe();
+        pipe.hset(&key, "expires_at", &expires_at)
+            .expire(&index_key, ttl_seconds as i64)
+            .expire(format!("{}:{}:seq", SESSION_PREFIX, session_id), ttl_seconds as i64)
+            .expire(
+                format!("{}:{}:stream_stats", SESSION_PREFIX, session_id),
+                ttl_seconds as i64,
+            );
+        for request_id in &request_ids {
+            let request_key = format!("{}:{}:{}", REQUEST_PREFIX, session_id, request_id);
+            pipe.expire(&request_key, ttl_seconds as i64);
+        }
+        pipe.query_async::<()>(&mut conn).await?;
+
+        let _: () = self
+            .get_connection()
+            .zadd(ACTIVE_SESSIONS_KEY, session_id, expires.timestamp())
+            .await?;
+
+        debug!(session_id = %session_id, expires_at = %expires_at, "Extended session");
+        Ok(Some(expires_at))
+    }
+
+    /// Pause or resume capture for a session. Returns `false` if the session
+    /// no longer exists.
+    #[instrument(skip(self))]
+    pub async fn set_session_paused(&self, session_id: &str, paused: bool) -> AppResult<bool> {
+        let mut conn = self.connection_for(session_id);
+        let key = format!("{}:{}", SESSION_PREFIX, session_id);
+
+        // Never recreate an expired session hash (it would have no TTL)
+        let exists: bool = conn.exists(&key).await?;
+        if !exists {
+            return Ok(false);
+        }
+        let _: () = conn.hset(&key, "paused", paused.to_string()).await?;
+
+        debug!(session_id = %session_id, paused = paused, "Updated session capture state");
+        Ok(true)
+    }
+
+    /// Get the remaining TTL of a session in seconds (`None` if it no longer exists)
+    #[instrument(skip(self))]
+    pub async fn get_session_ttl(&self, session_id: &str) -> AppResult<Option<i64>> {
+        let mut conn = self.connection_for(session_id);
+        let key = format!("{}:{}", SESSION_PREFIX, session_id);
+        // TTL returns -2 for missing keys and -1 for keys without expiry
+        let ttl: i64 = conn.ttl(&key).await?;
+        Ok((ttl != -2).then_some(ttl))
+    }
+
+    /// Count a hit against the client IP's current fixed window.
+    /// Returns the hits so far in this window and seconds until it resets.
+    #[instrument(skip(self))]
+    pub async fn hit_ip_rate_limit(
+        &self,
+        ip_address: &str,
+        window_seconds: u64,
+    ) -> AppResult<(u64, u64)> {
+        let mut conn = self.get_connection();
+        let prefix = format!("{}:{}", IP_RATE_LIMIT_PREFIX, ip_address);
+        hit_fixed_window(&mut conn, &prefix, window_seconds).await
+    }
+
+    /// Record `fingerprint` for `window_seconds` unless it was already seen.
+    /// Returns the ID of the earlier request when this one is a duplicate.
+    #[instrument(skip(self))]
+    pub async fn claim_dedupe_key(
+        &self,
+        session_id: &str,
+        fingerprint: &str,
+        request_id: &str,
+        window_seconds: u64,
+    ) -> AppResult<Option<String>> {
+        let mut conn = self.connection_for(session_id);
+        let key = format!("{}:{}:dedupe:{}", SESSION_PREFIX, session_id, fingerprint);
+
+        for _ in 0..DEDUPE_CLAIM_ATTEMPTS {
+            let claimed: Option<String> = redis::cmd("SET")
+                .arg(&key)
+                .arg(request_id)
+                .arg("NX")
+                .arg("EX")
+                .arg(window_seconds)
+                .query_async(&mut conn)
+                .await?;
+            if claimed.is_some() {
+                return Ok(None);
+            }
+
+            // The earlier claim can expire between the SET and this GET;
+            // then there is nothing to be a duplicate of, so claim again
+            let original: Option<String> = conn.get(&key).await?;
+            if let Some(original) = original {
+                return Ok(Some(original));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Drop a dedupe claim made by `request_id`, leaving it alone if it has
+    /// since expired and been claimed by another request
+    #[instrument(skip(self))]
+    pub async fn release_dedupe_key(
+        &self,
+        session_id: &str,
+        fingerprint: &str,
+        request_id: &str,
+    ) -> AppResult<()> {
+        let mut conn = self.connection_for(session_id);
+        let key = format!("{}:{}:dedupe:{}", SESSION_PREFIX, session_id, fingerprint);
+
+        redis::cmd("EVAL")
+            .arg(RELEASE_IF_OWNED_SCRIPT)
+            .arg(1)
+            .arg(&key)
+            .arg(request_id)
+            .query_async::<()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Count an ingest against the session's current fixed window.
+    /// Returns the hits so far in this window and seconds until it resets.
+    #[instrument(skip(self))]
+    pub async fn hit_session_rate_limit(
+        &self,
+        session_id: &str,
+        window_seconds: u64,
+    ) -> AppResult<(u64, u64)> {
+        let mut conn = self.connection_for(session_id);
+        let prefix = format!("{}:{}", SESSION_RATE_LIMIT_PREFIX, session_id);
+        hit_fixed_window(&mut conn, &prefix, window_seconds).await
+    }
+
+    /// Count active (unexpired) sessions
+    #[instrument(skip(self))]
+    pub async fn count_active_sessions(&self) -> AppResult<usize> {
+        let mut conn = self.get_connection();
+        let (_, count): ((), usize) = redis::pipe()
+            .atomic()
+            .zrembyscore(ACTIVE_SESSIONS_KEY, "-inf", Utc::now().timestamp())
+            .zcard(ACTIVE_SESSIONS_KEY)
+            .query_async(&mut conn)
+            .await?;
+        Ok(count)
+    }
+
+    /// List active sessions (latest expiry first) with their request counts,
+    /// alongside the total number of active sessions
+    #[instrument(skip(self))]
+    pub async fn list_sessions(
+        &self,
+        limit: usize,
+        offset: usize,
+    ) -> AppResult<(Vec<SessionSummary>, usize)> {
+        let mut conn = self.get_connection();
+        let end = (offset + limit).saturating_sub(1) as isize;
+        let (_, total, session_ids): ((), usize, Vec<String>) = redis::pipe()
+            .atomic()
+            .zrembyscore(ACTIVE_SESSIONS_KEY, "-inf", Utc::now().timestamp())
+            .zcard(ACTIVE_SESSIONS_KEY)
+            .zrevrange(ACTIVE_SESSIONS_KEY, offset as isize, end)
+            .query_async(&mut conn)
+            .await?;
+
+        let mut sessions = Vec::with_capacity(session_ids.len());
+        for session_id in session_ids {
+            match self.get_session(&session_id).await? {
+                Some(session) => sessions.push(SessionSummary {
+                    request_count: self.get_request_count(&session_id).await?,
+                    session_id: session.session_id,
+                    created_at: session.created_at,
+                    expires_at: session.expires_at,
+                }),
+                // Deleted out from under the index; drop the stale entry
+                None => {
+                    let _: () = conn.zrem(ACTIVE_SESSIONS_KEY, &session_id).await?;
+                }
+            }
+        }
+
+        Ok((sessions, total))
+    }
+
     /// Save a webhook request
+    ///
+    /// The request hash expires after `request_ttl` while the session's index
+    /// follows `session_ttl`; index entries older than `request_ttl` are
+    /// pruned here so the per-session cap only counts live requests.
     #[instrument(skip(self, request), fields(request_id = %request.request_id))]
     pub async fn save_request(
         &self,
         session_id: &str,
-        request: &WebhookRequest,
-        ttl_seconds: u64,
+        request: &mut WebhookRequest,
+        request_ttl: u64,
+        session_ttl: u64,
     ) -> AppResult<()> {
-        let mut conn = self.get_connection();
+        let mut conn = self.connection_for(session_id);
 
         let request_key = format!("{}:{}:{}", REQUEST_PREFIX, session_id, request.request_id);
         let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
@@ -236,28 +756,128 @@ impl RedisClient {
                     .as_millis() as i64
             });
 
+        // Per-session capture order; its slot within the millisecond is
+        // folded into the index score so requests that share a millisecond
+        // still have distinct scores (Redis orders equal scores by member,
+        // i.e. by UUIDv7, which is only monotonic within a single process)
+        let sequence_key = format!("{}:{}:seq", SESSION_PREFIX, session_id);
+        let slot_key = format!("{}:{}", sequence_key, timestamp_ms);
+        let (sequence, slot): (u64, u64) = redis::cmd("EVAL")
+            .arg(NEXT_SEQUENCE_SCRIPT)
+            .arg(2)
+            .arg(&sequence_key)
+            .arg(&slot_key)
+            .query_async(&mut conn)
+            .await?;
+        request.sequence = sequence;
+
         let headers_json = serde_json::to_string(&request.headers)?;
 
         // Use a pipeline for atomic operations
-        redis::pipe()
-            .hset(&request_key, "request_id", &request.request_id)
+        let mut pipe = redis::pipe();
+        pipe.hset(&request_key, "request_id", &request.request_id)
             .hset(&request_key, "method", &request.method)
             .hset(&request_key, "path", &request.path)
             .hset(&request_key, "query_params", &serde_json::to_string(&request.query_params)?)
+            .hset(&request_key, "raw_query", &request.raw_query)
+            .hset(&request_key, "raw_target", &request.raw_target)
+            .hset(&request_key, "subpath", &request.subpath)
             .hset(&request_key, "headers", &headers_json)
             .hset(&request_key, "body", &request.body)
+            .hset(&request_key, "body_encoding", request.body_encoding.as_str())
+            .hset(&request_key, "body_valid_json", request.body_valid_json.to_string())
             .hset(&request_key, "ip_address", &request.ip_address)
             .hset(&request_key, "user_agent", &request.user_agent)
+            .hset(&request_key, "content_type", &request.content_type)
             .hset(&request_key, "timestamp", &request.timestamp)
+            .hset(&request_key, "received_at", &request.received_at)
             .hset(&request_key, "content_length", request.content_length)
-            .expire(&request_key, ttl_seconds as i64)
-            .zadd(&index_key, &request.request_id, timestamp_ms)
-            .expire(&index_key, ttl_seconds as i64)
+            .hset(&request_key, "decoded_length", request.decoded_length)
+            .hset(&request_key, "byte_length", request.byte_length)
+            .hset(&request_key, "sequence", request.sequence);
+        if let Some(body_json) = &request.body_json {
+            pipe.hset(&request_key, "body_json", serde_json::to_string(body_json)?);
+        }
+        if let Some(body_transformed) = &request.body_transformed {
+            pipe.hset(&request_key, "body_transformed", serde_json::to_string(body_transformed)?);
+        }
+        if let Some(transform_error) = &request.transform_error {
+            pipe.hset(&request_key, "transform_error", transform_error);
+        }
+        if let Some(detected_content_type) = &request.detected_content_type {
+            pipe.hset(&request_key, "detected_content_type", detected_content_type);
+        }
+        if let Some(original_session_id) = &request.original_session_id {
+            pipe.hset(&request_key, "original_session_id", original_session_id);
+        }
+        if let Some(proto_error) = &request.proto_error {
+            pipe.hset(&request_key, "proto_error", proto_error);
+        }
+        if let Some(form_fields) = &request.form_fields {
+            pipe.hset(&request_key, "form_fields", serde_json::to_string(form_fields)?);
+        }
+        if let Some(parts) = &request.multipart_parts {
+            pipe.hset(&request_key, "multipart_parts", serde_json::to_string(parts)?);
+        }
+        if let Some(cookies) = &request.cookies {
+            pipe.hset(&request_key, "cookies", serde_json::to_string(cookies)?);
+        }
+        if let Some(original_encoding) = &request.original_encoding {
+            pipe.hset(&request_key, "original_encoding", original_encoding);
+        }
+        if let Some(signature_valid) = request.signature_valid {
+            pipe.hset(&request_key, "signature_valid", signature_valid.to_string());
+        }
+        let connection_fields = [
+            ("scheme", &request.scheme),
+            ("http_version", &request.http_version),
+            ("peer_address", &request.peer_address),
+            ("tls_version", &request.tls_version),
+            ("client_cert_verify", &request.client_cert_verify),
+            ("client_cert_subject", &request.client_cert_subject),
+        ];
+        for (field, value) in connection_fields {
+            if let Some(value) = value {
+                pipe.hset(&request_key, field, value);
+            }
+        }
+        if request.headers_truncated {
+            pipe.hset(&request_key, "headers_truncated", "true");
+        }
+        if !request.tags.is_empty() {
+            pipe.hset(&request_key, "tags", serde_json::to_string(&request.tags)?);
+        }
+        if !request.declared_trailers.is_empty() {
+            pipe.hset(&request_key, "declared_trailers", request.declared_trailers.join(","));
+        }
+        if let (Some(char_length), Some(line_count)) = (request.char_length, request.line_count) {
+            pipe.hset(&request_key, "char_length", char_length)
+                .hset(&request_key, "line_count", line_count);
+        }
+        if let Some(original_content_length) = request.original_content_length {
+            pipe.hset(&request_key, "truncated", "true")
+                .hset(&request_key, "original_content_length", original_content_length);
+        }
+        if let Some(schema_valid) = request.schema_valid {
+            let schema_errors = serde_json::to_string(&request.schema_errors)?;
+            pipe.hset(&request_key, "schema_valid", schema_valid.to_string())
+                .hset(&request_key, "schema_errors", schema_errors);
+        }
+        if request_ttl < session_ttl {
+            let cutoff_ms = timestamp_ms - (request_ttl as i64) * 1000;
+            let cutoff = format!("({}", cutoff_ms * SCORE_SEQUENCE_SLOTS);
+            pipe.zrembyscore(&index_key, "-inf", cutoff).ignore();
+        }
+        pipe.expire(&request_key, request_ttl as i64)
+            .zadd(&index_key, &request.request_id, request_score(timestamp_ms, slot))
+            .expire(&index_key, session_ttl as i64)
+            .expire(&sequence_key, session_ttl as i64)
             .query_async::<()>(&mut conn)
             .await?;
 
-        // Broadcast to SSE subscribers (in-memory, no Redis pub/sub needed)
+        // Broadcast to local SSE subscribers, then to other instances
         self.broadcast_request(session_id, request).await;
+        self.publish_request(session_id, request).await;
 
         debug!(
             session_id = %session_id,
@@ -268,6 +888,94 @@ impl RedisClient {
         Ok(())
     }
 
+    /// Record the outcome of a forwarding attempt: the latest status and
+    /// response, and the attempt appended to `delivery_attempts`. Returns
+    /// `false` when the request no longer exists.
+    #[instrument(skip(self, attempt, response))]
+    pub async fn save_forward_result(
+        &self,
+        session_id: &str,
+        request_id: &str,
+        attempt: &DeliveryAttempt,
+        response: &str,
+    ) -> AppResult<bool> {
+        let mut conn = self.connection_for(session_id);
+        let request_key = format!("{}:{}:{}", REQUEST_PREFIX, session_id, request_id);
+
+        // The request may have expired while forwarding was in flight
+        if !conn.exists::<_, bool>(&request_key).await? {
+            return Ok(false);
+        }
+
+        // Attempts for one request never overlap, so read-modify-write is safe
+        let stored: Option<String> = conn.hget(&request_key, "delivery_attempts").await?;
+        let mut attempts: Vec<DeliveryAttempt> = stored
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        attempts.push(attempt.clone());
+
+        let mut pipe = redis::pipe();
+        match attempt.status {
+            Some(status) => pipe.hset(&request_key, "forward_status", status),
+            None => pipe.hdel(&request_key, "forward_status"),
+        };
+        pipe.hset(&request_key, "forward_response", response)
+            .hset(&request_key, "delivery_attempts", serde_json::to_string(&attempts)?)
+            .query_async::<()>(&mut conn)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Queue a forwarding retry to run at `due`
+    #[instrument(skip(self, job), fields(request_id = %job.request_id))]
+    pub async fn schedule_forward_retry(
+        &self,
+        job: &ForwardJob,
+        due: DateTime<Utc>,
+    ) -> AppResult<()> {
+        let mut conn = self.get_connection();
+        conn.zadd::<_, _, _, ()>(
+            FORWARD_RETRY_KEY,
+            serde_json::to_string(job)?,
+            due.timestamp_millis(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Take up to `limit` retries due by `now` off the queue. A job is only
+    /// returned to the instance whose `ZREM` removed it, so concurrent workers
+    /// never deliver the same retry twice.
+    pub async fn claim_forward_retries(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> AppResult<Vec<ForwardJob>> {
+        let mut conn = self.get_connection();
+        let due: Vec<String> = conn
+            .zrangebyscore_limit(
+                FORWARD_RETRY_KEY,
+                "-inf",
+                now.timestamp_millis(),
+                0,
+                limit as isize,
+            )
+            .await?;
+
+        let mut jobs = Vec::with_capacity(due.len());
+        for member in due {
+            if conn.zrem::<_, _, u32>(FORWARD_RETRY_KEY, &member).await? == 0 {
+                continue;
+            }
+            match serde_json::from_str(&member) {
+                Ok(job) => jobs.push(job),
+                Err(e) => warn!(error = %e, "Dropping malformed forwarding retry"),
+            }
+        }
+        Ok(jobs)
+    }
+
     /// Get requests for a session with pagination
     #[instrument(skip(self))]
     pub async fn get_requests(
@@ -276,7 +984,7 @@ impl RedisClient {
         limit: usize,
         offset: usize,
     ) -> AppResult<Vec<WebhookRequest>> {
-        let mut conn = self.get_connection();
+        let mut conn = self.connection_for(session_id);
         let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
 
         // Get request IDs from sorted set (reverse order, newest first)
@@ -287,67 +995,1438 @@ impl RedisClient {
         };
         let request_ids: Vec<String> = conn.zrevrange(&index_key, offset as isize, end).await?;
 
-        let mut requests = Vec::with_capacity(request_ids.len());
-
-        for request_id in request_ids {
-            let request_key = format!("{}:{}:{}", REQUEST_PREFIX, session_id, request_id);
-            let data: HashMap<String, String> = conn.hgetall(&request_key).await?;
-
-            if data.is_empty() {
-                continue;
-            }
-
-            let headers: HashMap<String, String> = data
-                .get("headers")
-                .and_then(|h| serde_json::from_str(h).ok())
-                .unwrap_or_default();
-
-            requests.push(WebhookRequest {
-                request_id: data.get("request_id").cloned().unwrap_or_default(),
-                method: data.get("method").cloned().unwrap_or_default(),
-                path: data.get("path").cloned().unwrap_or_default(),
-                query_params: data.get("query_params").and_then(|q| serde_json::from_str(q).ok()).unwrap_or_default(),
-                headers,
-                body: data.get("body").cloned().unwrap_or_default(),
-                ip_address: data.get("ip_address").cloned().unwrap_or_default(),
-                user_agent: data.get("user_agent").cloned().unwrap_or_default(),
-                timestamp: data.get("timestamp").cloned().unwrap_or_default(),
-                content_length: data
-                    .get("content_length")
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0),
-            });
-        }
-
+        let mut requests = self.load_requests(session_id, request_ids).await?;
+        sort_newest_first(&mut requests);
         Ok(requests)
     }
 
-    /// Get total request count for a session
+    /// Get up to `limit` requests scored below `before` (from the newest when
+    /// `None`), newest first, plus the cursor for the next page (`None` once
+    /// the index is exhausted). Index scores are unique, so paging by score
+    /// neither repeats nor skips requests while new ones arrive or old ones
+    /// are pruned.
     #[instrument(skip(self))]
-    pub async fn get_request_count(&self, session_id: &str) -> AppResult<usize> {
-        let mut conn = self.get_connection();
+    pub async fn get_requests_before(
+        &self,
+        session_id: &str,
+        before: Option<i64>,
+        limit: usize,
+    ) -> AppResult<(Vec<WebhookRequest>, Option<i64>)> {
+        let mut conn = self.connection_for(session_id);
         let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
-        let count: usize = conn.zcard(&index_key).await?;
-        Ok(count)
+
+        let entries: Vec<(String, f64)> = conn
+            .zrevrangebyscore_limit_withscores(
+                &index_key,
+                score_cursor(before),
+                "-inf",
+                0,
+                limit as isize,
+            )
+            .await?;
+        // Taken before loading, so expired requests still advance the cursor
+        let next = entries
+            .last()
+            .filter(|_| entries.len() == limit)
+            .map(|(_, score)| *score as i64);
+
+        let request_ids = entries.into_iter().map(|(request_id, _)| request_id).collect();
+        let mut requests = self.load_requests(session_id, request_ids).await?;
+        sort_newest_first(&mut requests);
+        Ok((requests, next))
     }
 
-    /// Store API URL in Redis (for frontend discovery)
+    /// Get requests captured within an inclusive time range (epoch millis,
+    /// open-ended when `None`), newest first, along with the number in range.
+    /// `page` is `(offset, limit)`; `None` returns every request in range.
     #[instrument(skip(self))]
-    pub async fn set_api_url(&self, api_url: &str) -> AppResult<()> {
-        let mut conn = self.get_connection();
-        let _: () = conn.set("config:api_url", api_url).await?;
-        // Set TTL to 24 hours, but backend will refresh it periodically
-        let _: () = conn.expire("config:api_url", 86400).await?;
-        info!(api_url = %api_url, "Stored API URL in Redis");
-        Ok(())
+    pub async fn get_requests_by_time_range(
+        &self,
+        session_id: &str,
+        from_ms: Option<i64>,
+        to_ms: Option<i64>,
+        page: Option<(usize, usize)>,
+    ) -> AppResult<(Vec<WebhookRequest>, usize)> {
+        let mut conn = self.connection_for(session_id);
+        let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
+        let (min, max) = score_bounds(from_ms, to_ms);
+
+        let (offset, count) = match page {
+            Some((offset, limit)) => (offset as isize, limit as isize),
+            None => (0, -1),
+        };
+        let (request_ids, total): (Vec<String>, usize) = redis::pipe()
+            .zrevrangebyscore_limit(&index_key, &max, &min, offset, count)
+            .zcount(&index_key, &min, &max)
+            .query_async(&mut conn)
+            .await?;
+
+        let mut requests = self.load_requests(session_id, request_ids).await?;
+        sort_newest_first(&mut requests);
+        Ok((requests, total))
     }
 
-    /// Get API URL from Redis
+    /// Get all request IDs for a session (newest first)
     #[instrument(skip(self))]
-    pub async fn get_api_url(&self) -> AppResult<Option<String>> {
-        let mut conn = self.get_connection();
-        let api_url: Option<String> = conn.get("config:api_url").await?;
-        Ok(api_url)
+    pub async fn get_request_ids(&self, session_id: &str) -> AppResult<Vec<String>> {
+        let mut conn = self.connection_for(session_id);
+        let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
+        let request_ids: Vec<String> = conn.zrevrange(&index_key, 0, -1).await?;
+        Ok(request_ids)
     }
-}
 
+    /// Get a single request by ID
+    #[instrument(skip(self))]
+    pub async fn get_request(
+        &self,
+        session_id: &str,
+        request_id: &str,
+    ) -> AppResult<Option<WebhookRequest>> {
+        let mut conn = self.connection_for(session_id);
+        let request_key = format!("{}:{}:{}", REQUEST_PREFIX, session_id, request_id);
+        let data: HashMap<String, String> = conn.hgetall(&request_key).await?;
+
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(parse_request(&data)))
+    }
+
+    /// Delete a single request and remove it from the session index.
+    /// Returns `false` if the request did not exist.
+    #[instrument(skip(self))]
+    pub async fn delete_request(&self, session_id: &str, request_id: &str) -> AppResult<bool> {
+        let mut conn = self.connection_for(session_id);
+        let request_key = format!("{}:{}:{}", REQUEST_PREFIX, session_id, request_id);
+        let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
+
+        let (deleted, removed): (usize, usize) = redis::pipe()
+            .del(&request_key)
+            .zrem(&index_key, request_id)
+            .query_async(&mut conn)
+            .await?;
+
+        debug!(
+            session_id = %session_id,
+            request_id = %request_id,
+            "Deleted webhook request"
+        );
+        Ok(deleted > 0 || removed > 0)
+    }
+
+    /// Delete every captured request in a session, leaving the session and its
+    /// TTL untouched. Returns the number of requests deleted.
+    #[instrument(skip(self))]
+    pub async fn clear_requests(&self, session_id: &str) -> AppResult<usize> {
+        let request_ids = self.get_request_ids(session_id).await?;
+        if request_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut conn = self.connection_for(session_id);
+        let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
+
+        let mut pipe = redis::pipe();
+        for request_id in &request_ids {
+            pipe.del(format!("{}:{}:{}", REQUEST_PREFIX, session_id, request_id))
+                .ignore();
+        }
+        // Only drop the IDs we deleted, in case a request arrived meanwhile
+        pipe.zrem(&index_key, &request_ids)
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await?;
+
+        debug!(
+            session_id = %session_id,
+            deleted = request_ids.len(),
+            "Cleared webhook requests"
+        );
+        Ok(request_ids.len())
+    }
+
+    /// Get all requests for a session (newest first), e.g. for filtering
+    #[instrument(skip(self))]
+    pub async fn get_all_requests(&self, session_id: &str) -> AppResult<Vec<WebhookRequest>> {
+        let request_ids = self.get_request_ids(session_id).await?;
+        let mut requests = self.load_requests(session_id, request_ids).await?;
+        sort_newest_first(&mut requests);
+        Ok(requests)
+    }
+
+    /// Get requests stored after `last_request_id` (oldest first), for SSE replay
+    #[instrument(skip(self))]
+    pub async fn get_requests_after(
+        &self,
+        session_id: &str,
+        last_request_id: &str,
+    ) -> AppResult<Vec<WebhookRequest>> {
+        let mut conn = self.connection_for(session_id);
+        let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
+
+        // Scores are unique, so everything scored above the stored one is newer
+        let score: Option<i64> = conn.zscore(&index_key, last_request_id).await?;
+        let request_ids: Vec<String> = match score {
+            Some(score) => conn.zrangebyscore(&index_key, format!("({}", score), "+inf").await?,
+            // Already evicted from the index: fall back to its UUIDv7
+            // millisecond, dropping anything not strictly newer by ID (UUIDv7
+            // strings sort chronologically)
+            None => {
+                let Some(millis) = uuid_v7_millis(last_request_id) else {
+                    return Ok(Vec::new());
+                };
+                let request_ids: Vec<String> = conn
+                    .zrangebyscore(&index_key, millis * SCORE_SEQUENCE_SLOTS, "+inf")
+                    .await?;
+                request_ids
+                    .into_iter()
+                    .filter(|id| id.as_str() > last_request_id)
+                    .collect()
+            }
+        };
+
+        self.load_requests(session_id, request_ids).await
+    }
+
+    /// Load request hashes for the given IDs, skipping any that have expired
+    async fn load_requests(
+        &self,
+        session_id: &str,
+        request_ids: Vec<String>,
+    ) -> AppResult<Vec<WebhookRequest>> {
+        let mut requests = Vec::with_capacity(request_ids.len());
+        let mut expired = Vec::new();
+
+        for request_id in request_ids {
+            match self.get_request(session_id, &request_id).await? {
+                Some(request) => requests.push(request),
+                None => expired.push(request_id),
+            }
+        }
+
+        // Drop index entries whose request hash has already expired
+        if !expired.is_empty() {
+            let mut conn = self.connection_for(session_id);
+            let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
+            let _: () = conn.zrem(&index_key, &expired).await?;
+            debug!(
+                session_id = %session_id,
+                removed = expired.len(),
+                "Removed expired request IDs from index"
+            );
+        }
+
+        Ok(requests)
+    }
+
+    /// Get total request count for a session
+    #[instrument(skip(self))]
+    pub async fn get_request_count(&self, session_id: &str) -> AppResult<usize> {
+        let mut conn = self.connection_for(session_id);
+        let index_key = format!("{}:{}:requests", SESSION_PREFIX, session_id);
+        let count: usize = conn.zcard(&index_key).await?;
+        Ok(count)
+    }
+
+    /// Remove state that drifted from the TTL-managed keys: active-session
+    /// entries for expired sessions, request indexes outliving their session,
+    /// and index entries whose request hash expired. Walks the keyspace with
+    /// cursor-based `SCAN`/`ZSCAN` so Redis is never blocked by a full scan.
+    #[instrument(skip(self))]
+    pub async fn garbage_collect(&self) -> AppResult<GcResponse> {
+        let mut report = GcResponse::default();
+
+        let mut index_conn = self.get_connection();
+        let mut cursor = 0;
+        loop {
+            let (next, session_ids) =
+                zscan_members(&mut index_conn, ACTIVE_SESSIONS_KEY, cursor).await?;
+            for session_id in session_ids {
+                if !self.session_exists(&session_id).await? {
+                    let removed: usize = index_conn.zrem(ACTIVE_SESSIONS_KEY, &session_id).await?;
+                    report.sessions_removed += removed;
+                }
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        let pattern = format!("{}:*:requests", SESSION_PREFIX);
+        for connection in &self.connections {
+            let mut conn = RetryingConnection(connection.clone());
+            let mut cursor = 0;
+            loop {
+                let (next, index_keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                    .arg(cursor)
+                    .arg("MATCH")
+                    .arg(&pattern)
+                    .arg("COUNT")
+                    .arg(GC_SCAN_COUNT)
+                    .query_async(&mut conn)
+                    .await?;
+                for index_key in index_keys {
+                    let Some(session_id) = index_key
+                        .strip_prefix(&format!("{}:", SESSION_PREFIX))
+                        .and_then(|rest| rest.strip_suffix(":requests"))
+                    else {
+                        continue;
+                    };
+
+                    let session_key = format!("{}:{}", SESSION_PREFIX, session_id);
+                    if !conn.exists::<_, bool>(&session_key).await? {
+                        let _: () = conn.del(&index_key).await?;
+                        report.indexes_removed += 1;
+                    } else {
+                        report.requests_pruned +=
+                            prune_request_index(&mut conn, session_id, &index_key).await?;
+                    }
+                }
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+        }
+
+        info!(
+            sessions_removed = report.sessions_removed,
+            indexes_removed = report.indexes_removed,
+            requests_pruned = report.requests_pruned,
+            "Garbage collection finished"
+        );
+        Ok(report)
+    }
+
+    /// Store API URL in Redis (for frontend discovery)
+    #[instrument(skip(self))]
+    pub async fn set_api_url(&self, api_url: &str) -> AppResult<()> {
+        let mut conn = self.get_connection();
+        let _: () = conn.set("config:api_url", api_url).await?;
+        // Set TTL to 24 hours, but backend will refresh it periodically
+        let _: () = conn.expire("config:api_url", 86400).await?;
+        info!(api_url = %api_url, "Stored API URL in Redis");
+        Ok(())
+    }
+
+    /// Get the CORS origin override (`*` or a comma-separated list), if set
+    #[instrument(skip(self))]
+    pub async fn get_cors_origins(&self) -> AppResult<Option<String>> {
+        let mut conn = self.get_connection();
+        let origins: Option<String> = conn.get("config:cors_origins").await?;
+        Ok(origins)
+    }
+
+    /// Get API URL from Redis
+    #[instrument(skip(self))]
+    pub async fn get_api_url(&self) -> AppResult<Option<String>> {
+        let mut conn = self.get_connection();
+        let api_url: Option<String> = conn.get("config:api_url").await?;
+        Ok(api_url)
+    }
+}
+
+/// Connection handle that retries a read once when it fails because the
+/// connection dropped (e.g. Redis restarted), instead of surfacing the error
+/// while the ConnectionManager is still reconnecting in the background.
+/// Writes are never resent: the connection may have dropped after Redis
+/// applied them, and repeating an INCR, PUBLISH or save pipeline would
+/// double-count or duplicate it.
+struct RetryingConnection(ConnectionManager);
+
+impl ConnectionLike for RetryingConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            match self.0.req_packed_command(cmd).await {
+                Err(e) if is_connection_error(&e) && is_idempotent(cmd) => {
+                    warn!(error = %e, "Redis connection lost, retrying command");
+                    tokio::time::sleep(COMMAND_RETRY_DELAY).await;
+                    self.0.req_packed_command(cmd).await
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            match self.0.req_packed_commands(cmd, offset, count).await {
+                Err(e) if is_connection_error(&e) && cmd.cmd_iter().all(is_idempotent) => {
+                    warn!(error = %e, "Redis connection lost, retrying pipeline");
+                    tokio::time::sleep(COMMAND_RETRY_DELAY).await;
+                    self.0.req_packed_commands(cmd, offset, count).await
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.0.get_db()
+    }
+}
+
+/// Increment the counter for the current `window_seconds` window under
+/// `prefix`, returning the count and seconds until the window resets
+async fn hit_fixed_window(
+    conn: &mut RetryingConnection,
+    prefix: &str,
+    window_seconds: u64,
+) -> AppResult<(u64, u64)> {
+    let now = Utc::now().timestamp().max(0) as u64;
+    let window = now / window_seconds;
+    let key = format!("{}:{}", prefix, window);
+
+    let (count, _): (u64, ()) = redis::pipe()
+        .atomic()
+        .incr(&key, 1)
+        .expire(&key, window_seconds as i64)
+        .query_async(conn)
+        .await?;
+
+    let retry_after = (window + 1) * window_seconds - now;
+    Ok((count, retry_after))
+}
+
+/// One `ZSCAN` page of a sorted set: the next cursor (0 when done) and members
+async fn zscan_members(
+    conn: &mut RetryingConnection,
+    key: &str,
+    cursor: u64,
+) -> AppResult<(u64, Vec<String>)> {
+    let (next, entries): (u64, Vec<String>) = redis::cmd("ZSCAN")
+        .arg(key)
+        .arg(cursor)
+        .arg("COUNT")
+        .arg(GC_SCAN_COUNT)
+        .query_async(conn)
+        .await?;
+    // Replies alternate member and score
+    Ok((next, entries.into_iter().step_by(2).collect()))
+}
+
+/// Drop index entries whose request hash has expired; returns how many
+async fn prune_request_index(
+    conn: &mut RetryingConnection,
+    session_id: &str,
+    index_key: &str,
+) -> AppResult<usize> {
+    let mut pruned = 0;
+    let mut cursor = 0;
+    loop {
+        let (next, request_ids) = zscan_members(conn, index_key, cursor).await?;
+        if !request_ids.is_empty() {
+            let mut pipe = redis::pipe();
+            for request_id in &request_ids {
+                pipe.exists(format!("{}:{}:{}", REQUEST_PREFIX, session_id, request_id));
+            }
+            let exists: Vec<bool> = pipe.query_async(conn).await?;
+            let expired: Vec<&String> = request_ids
+                .iter()
+                .zip(exists)
+                .filter_map(|(id, exists)| (!exists).then_some(id))
+                .collect();
+            if !expired.is_empty() {
+                let removed: usize = conn.zrem(index_key, &expired).await?;
+                pruned += removed;
+            }
+        }
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    Ok(pruned)
+}
+
+/// Lowercase a list of header names (an absent list is empty)
+fn lowercase_names(names: Option<&[String]>) -> Vec<String> {
+    names
+        .into_iter()
+        .flatten()
+        .map(|name| name.to_ascii_lowercase())
+        .collect()
+}
+
+/// Build a new session from its create options
+pub fn new_session(
+    session_id: &str,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    options: &CreateSessionRequest,
+) -> Session {
+    Session {
+        session_id: session_id.to_string(),
+        created_at: created_at.to_rfc3339(),
+        expires_at: expires_at.to_rfc3339(),
+        signed_receipt: options.signed_receipt,
+        forward_url: options.forward_url.clone(),
+        ingest_token: options.ingest_token.clone(),
+        signing_secret: options.signing_secret.clone(),
+        signature_header: options
+            .signature_header
+            .as_deref()
+            .unwrap_or(DEFAULT_SIGNATURE_HEADER)
+            .to_ascii_lowercase(),
+        reject_invalid_signatures: options.reject_invalid_signatures,
+        capture_options_requests: options.capture_options_requests,
+        response_template: options.response_template.clone(),
+        response_redirect_url: options.response_redirect_url.clone(),
+        response_redirect_status: options
+            .response_redirect_status
+            .unwrap_or(DEFAULT_REDIRECT_STATUS),
+        echo_body: options.echo_body,
+        response_headers: options.response_headers.clone().unwrap_or_default(),
+        allowed_methods: options
+            .allowed_methods
+            .iter()
+            .flatten()
+            .map(|m| m.to_ascii_uppercase())
+            .collect(),
+        allowed_ips: options
+            .allowed_ips
+            .iter()
+            .flatten()
+            .filter_map(|network| parse_ip_network(network))
+            .collect(),
+        store_headers_allowlist: lowercase_names(options.store_headers_allowlist.as_deref()),
+        store_headers_blocklist: lowercase_names(options.store_headers_blocklist.as_deref()),
+        parse_cookies: options.parse_cookies,
+        paused: false,
+        body_schema: options.body_schema.clone(),
+        reject_invalid_body: options.reject_invalid_body,
+        // Validated by the handler
+        body_transform: options
+            .body_transform
+            .clone()
+            .and_then(|patch| serde_json::from_value(patch).ok()),
+        proto_descriptor: options.proto_descriptor.clone(),
+        proto_message_type: options.proto_message_type.clone(),
+        dedupe_window_seconds: options.dedupe_window_seconds.unwrap_or(0),
+        notify_webhook_url: options.notify_webhook_url.clone(),
+        notify_on: options
+            .notify_on
+            .iter()
+            .flatten()
+            .map(|m| m.to_ascii_uppercase())
+            .collect(),
+    }
+}
+
+/// Queue the writes that store a new session's hash and its expiry
+fn queue_session_writes(pipe: &mut Pipeline, session: &Session, ttl_seconds: u64) -> AppResult<()> {
+    let key = format!("{}:{}", SESSION_PREFIX, session.session_id);
+
+    pipe.hset(&key, "session_id", &session.session_id)
+        .hset(&key, "created_at", &session.created_at)
+        .hset(&key, "expires_at", &session.expires_at)
+        .hset(&key, "signed_receipt", session.signed_receipt.to_string())
+        .hset(&key, "signature_header", &session.signature_header)
+        .hset(
+            &key,
+            "reject_invalid_signatures",
+            session.reject_invalid_signatures.to_string(),
+        )
+        .hset(
+            &key,
+            "capture_options_requests",
+            session.capture_options_requests.to_string(),
+        )
+        .hset(&key, "reject_invalid_body", session.reject_invalid_body.to_string())
+        .hset(&key, "echo_body", session.echo_body.to_string())
+        .hset(&key, "parse_cookies", session.parse_cookies.to_string());
+    if let Some(forward_url) = &session.forward_url {
+        pipe.hset(&key, "forward_url", forward_url);
+    }
+    if let Some(ingest_token) = &session.ingest_token {
+        pipe.hset(&key, "ingest_token", ingest_token);
+    }
+    if let Some(signing_secret) = &session.signing_secret {
+        pipe.hset(&key, "signing_secret", signing_secret);
+    }
+    if let Some(response_template) = &session.response_template {
+        pipe.hset(&key, "response_template", response_template);
+    }
+    if !session.response_headers.is_empty() {
+        pipe.hset(&key, "response_headers", serde_json::to_string(&session.response_headers)?);
+    }
+    if let Some(redirect_url) = &session.response_redirect_url {
+        pipe.hset(&key, "response_redirect_url", redirect_url)
+            .hset(&key, "response_redirect_status", session.response_redirect_status);
+    }
+    if !session.allowed_methods.is_empty() {
+        pipe.hset(&key, "allowed_methods", session.allowed_methods.join(","));
+    }
+    if !session.allowed_ips.is_empty() {
+        let allowed_ips: Vec<String> =
+            session.allowed_ips.iter().map(ToString::to_string).collect();
+        pipe.hset(&key, "allowed_ips", allowed_ips.join(","));
+    }
+    if !session.store_headers_allowlist.is_empty() {
+        pipe.hset(&key, "store_headers_allowlist", session.store_headers_allowlist.join(","));
+    }
+    if !session.store_headers_blocklist.is_empty() {
+        pipe.hset(&key, "store_headers_blocklist", session.store_headers_blocklist.join(","));
+    }
+    if let Some(body_schema) = &session.body_schema {
+        pipe.hset(&key, "body_schema", serde_json::to_string(body_schema)?);
+    }
+    if let Some(body_transform) = &session.body_transform {
+        pipe.hset(&key, "body_transform", serde_json::to_string(body_transform)?);
+    }
+    if let (Some(descriptor), Some(message_type)) =
+        (&session.proto_descriptor, &session.proto_message_type)
+    {
+        pipe.hset(&key, "proto_descriptor", descriptor)
+            .hset(&key, "proto_message_type", message_type);
+    }
+    if session.dedupe_window_seconds > 0 {
+        pipe.hset(&key, "dedupe_window_seconds", session.dedupe_window_seconds);
+    }
+    if let Some(notify_webhook_url) = &session.notify_webhook_url {
+        pipe.hset(&key, "notify_webhook_url", notify_webhook_url);
+    }
+    if !session.notify_on.is_empty() {
+        pipe.hset(&key, "notify_on", session.notify_on.join(","));
+    }
+    pipe.expire(&key, ttl_seconds as i64);
+    Ok(())
+}
+
+/// Whether an error means the connection itself failed rather than the command
+fn is_connection_error(error: &redis::RedisError) -> bool {
+    error.is_connection_dropped() || error.is_connection_refusal() || error.is_io_error()
+}
+
+/// Read-only commands, which are safe to send again after a dropped
+/// connection
+const IDEMPOTENT_COMMANDS: &[&str] = &[
+    "EXISTS", "GET", "HGET", "HGETALL", "HMGET", "PING", "SCAN", "TTL", "ZCARD", "ZCOUNT",
+    "ZRANGEBYSCORE", "ZREVRANGE", "ZREVRANGEBYSCORE", "ZSCAN", "ZSCORE",
+];
+
+/// Whether resending the command cannot apply it twice
+fn is_idempotent(cmd: &Cmd) -> bool {
+    match cmd.args_iter().next() {
+        Some(redis::Arg::Simple(name)) => {
+            IDEMPOTENT_COMMANDS.iter().any(|read| read.as_bytes().eq_ignore_ascii_case(name))
+        }
+        _ => false,
+    }
+}
+
+/// Pick the shard for a session: FNV-1a hash of the ID modulo the node count.
+/// The hash is fixed (unlike `DefaultHasher`) so routing is stable across
+/// restarts and builds.
+fn shard_index(session_id: &str, shard_count: usize) -> usize {
+    if shard_count <= 1 {
+        return 0;
+    }
+    let hash = session_id
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    (hash % shard_count as u64) as usize
+}
+
+/// Index score of the request in `slot` of millisecond `timestamp_ms`
+fn request_score(timestamp_ms: i64, slot: u64) -> i64 {
+    timestamp_ms * SCORE_SEQUENCE_SLOTS + slot.min(SCORE_SEQUENCE_SLOTS as u64 - 1) as i64
+}
+
+/// Order a page of requests newest first by capture millisecond, then by
+/// sequence (which, unlike the index slot, has no cap). The sort is stable,
+/// so requests stored before sequences existed (all 0) keep the index order.
+fn sort_newest_first(requests: &mut [WebhookRequest]) {
+    requests.sort_by_key(|request| {
+        let millis = request
+            .timestamp
+            .parse::<DateTime<Utc>>()
+            .map(|dt| dt.timestamp_millis())
+            .unwrap_or_default();
+        std::cmp::Reverse((millis, request.sequence))
+    });
+}
+
+/// Extract the millisecond timestamp embedded in a UUIDv7 string
+fn uuid_v7_millis(id: &str) -> Option<i64> {
+    let uuid = uuid::Uuid::parse_str(id).ok()?;
+    let (secs, nanos) = uuid.get_timestamp()?.to_unix();
+    Some(secs as i64 * 1000 + (nanos / 1_000_000) as i64)
+}
+
+/// Exclusive upper score bound for the page after cursor `before`
+fn score_cursor(before: Option<i64>) -> String {
+    before.map_or_else(|| "+inf".to_string(), |score| format!("({}", score))
+}
+
+/// Index score bounds for an inclusive time range, covering every sequence
+/// slot of both end milliseconds; an open end is unbounded
+fn score_bounds(from_ms: Option<i64>, to_ms: Option<i64>) -> (String, String) {
+    (
+        from_ms.map_or_else(|| "-inf".to_string(), |ms| request_score(ms, 0).to_string()),
+        to_ms.map_or_else(
+            || "+inf".to_string(),
+            |ms| (request_score(ms + 1, 0) - 1).to_string(),
+        ),
+    )
+}
+
+/// Parse stored query params, accepting the older single-value format
+fn parse_query_params(data: &str) -> HashMap<String, Vec<String>> {
+    serde_json::from_str(data)
+        .or_else(|_| {
+            serde_json::from_str::<HashMap<String, String>>(data)
+                .map(|params| params.into_iter().map(|(k, v)| (k, vec![v])).collect())
+        })
+        .unwrap_or_default()
+}
+
+/// Build a `WebhookRequest` from its stored Redis hash fields
+fn parse_request(data: &HashMap<String, String>) -> WebhookRequest {
+    let headers: HashMap<String, String> = data
+        .get("headers")
+        .and_then(|h| serde_json::from_str(h).ok())
+        .unwrap_or_default();
+
+    WebhookRequest {
+        request_id: data.get("request_id").cloned().unwrap_or_default(),
+        method: data.get("method").cloned().unwrap_or_default(),
+        path: data.get("path").cloned().unwrap_or_default(),
+        query_params: data.get("query_params").map(|q| parse_query_params(q.as_str())).unwrap_or_default(),
+        raw_query: data.get("raw_query").cloned().unwrap_or_default(),
+        raw_target: data.get("raw_target").cloned().unwrap_or_default(),
+        subpath: data.get("subpath").cloned().unwrap_or_default(),
+        original_session_id: data.get("original_session_id").cloned(),
+        headers,
+        headers_truncated: data.get("headers_truncated").is_some_and(|v| v == "true"),
+        declared_trailers: data
+            .get("declared_trailers")
+            .map(|names| names.split(',').map(String::from).collect())
+            .unwrap_or_default(),
+        tags: data
+            .get("tags")
+            .and_then(|tags| serde_json::from_str(tags).ok())
+            .unwrap_or_default(),
+        sequence: data
+            .get("sequence")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0),
+        body: data.get("body").cloned().unwrap_or_default(),
+        body_encoding: data
+            .get("body_encoding")
+            .map(|e| BodyEncoding::from_stored(e))
+            .unwrap_or_default(),
+        body_json: data
+            .get("body_json")
+            .and_then(|j| serde_json::from_str(j).ok()),
+        body_valid_json: data.get("body_valid_json").is_some_and(|v| v == "true"),
+        body_transformed: data
+            .get("body_transformed")
+            .and_then(|j| serde_json::from_str(j).ok()),
+        transform_error: data.get("transform_error").cloned(),
+        proto_error: data.get("proto_error").cloned(),
+        form_fields: data
+            .get("form_fields")
+            .and_then(|f| serde_json::from_str(f).ok()),
+        multipart_parts: data
+            .get("multipart_parts")
+            .and_then(|p| serde_json::from_str(p).ok()),
+        cookies: data.get("cookies").and_then(|c| serde_json::from_str(c).ok()),
+        ip_address: data.get("ip_address").cloned().unwrap_or_default(),
+        user_agent: data.get("user_agent").cloned().unwrap_or_default(),
+        content_type: data.get("content_type").cloned().unwrap_or_default(),
+        detected_content_type: data.get("detected_content_type").cloned(),
+        timestamp: data.get("timestamp").cloned().unwrap_or_default(),
+        // Requests stored before `received_at` existed only have `timestamp`
+        received_at: data
+            .get("received_at")
+            .or_else(|| data.get("timestamp"))
+            .cloned()
+            .unwrap_or_default(),
+        content_length: data
+            .get("content_length")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0),
+        original_encoding: data.get("original_encoding").cloned(),
+        decoded_length: data
+            .get("decoded_length")
+            .or_else(|| data.get("content_length"))
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0),
+        byte_length: data
+            .get("byte_length")
+            .or_else(|| data.get("content_length"))
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0),
+        char_length: data.get("char_length").and_then(|s| s.parse().ok()),
+        line_count: data.get("line_count").and_then(|s| s.parse().ok()),
+        forward_status: data.get("forward_status").and_then(|s| s.parse().ok()),
+        forward_response: data.get("forward_response").cloned(),
+        delivery_attempts: data
+            .get("delivery_attempts")
+            .and_then(|a| serde_json::from_str(a).ok())
+            .unwrap_or_default(),
+        signature_valid: data.get("signature_valid").map(|v| v == "true"),
+        scheme: data.get("scheme").cloned(),
+        http_version: data.get("http_version").cloned(),
+        peer_address: data.get("peer_address").cloned(),
+        tls_version: data.get("tls_version").cloned(),
+        client_cert_verify: data.get("client_cert_verify").cloned(),
+        client_cert_subject: data.get("client_cert_subject").cloned(),
+        schema_valid: data.get("schema_valid").map(|v| v == "true"),
+        schema_errors: data
+            .get("schema_errors")
+            .and_then(|e| serde_json::from_str(e).ok())
+            .unwrap_or_default(),
+        truncated: data.get("truncated").is_some_and(|v| v == "true"),
+        original_content_length: data
+            .get("original_content_length")
+            .and_then(|s| s.parse().ok()),
+    }
+}
+
+/// A stand-in Redis server for tests, speaking just enough RESP to get
+/// through the connection handshake and answer the commands under test
+#[cfg(test)]
+pub(crate) mod fake_redis {
+    use super::{RedisClient, RedisSettings, NEXT_SEQUENCE_SCRIPT, RELEASE_IF_OWNED_SCRIPT};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Parse one RESP array of bulk strings from the front of `buf`,
+    /// returning its arguments and the bytes it took
+    fn parse_command(buf: &[u8]) -> Option<(Vec<String>, usize)> {
+        fn line(buf: &[u8], at: usize) -> Option<(&str, usize)> {
+            let end = at + buf.get(at..)?.windows(2).position(|w| w == b"\r\n")?;
+            Some((std::str::from_utf8(&buf[at..end]).ok()?, end + 2))
+        }
+        let (header, mut at) = line(buf, 0)?;
+        let count: usize = header.strip_prefix('*')?.parse().ok()?;
+        let mut args = Vec::new();
+        for _ in 0..count {
+            let (len, start) = line(buf, at)?;
+            let len: usize = len.strip_prefix('$')?.parse().ok()?;
+            let arg = buf.get(start..start + len)?;
+            args.push(String::from_utf8_lossy(arg).into_owned());
+            at = start + len + 2;
+        }
+        (buf.len() >= at).then_some((args, at))
+    }
+
+    /// Start a server that answers each command (other than the handshake's
+    /// `CLIENT SETINFO`) with the RESP bytes from `reply`, dropping the
+    /// connection when it returns `None`. `MULTI` blocks are queued and
+    /// answered together on `EXEC`. Returns the server's URL.
+    pub(crate) async fn serve<F>(reply: F) -> String
+    where
+        F: Fn(&[String]) -> Option<Vec<u8>> + Send + Sync + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        let reply = Arc::new(reply);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let reply = reply.clone();
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    let mut queued: Option<Vec<Vec<String>>> = None;
+                    while let Ok(read @ 1..) = socket.read(&mut chunk).await {
+                        buf.extend_from_slice(&chunk[..read]);
+                        while let Some((args, used)) = parse_command(&buf) {
+                            buf.drain(..used);
+                            let bytes = match (args[0].as_str(), queued.as_mut()) {
+                                ("CLIENT", _) => b"+OK\r\n".to_vec(),
+                                ("MULTI", _) => {
+                                    queued = Some(Vec::new());
+                                    b"+OK\r\n".to_vec()
+                                }
+                                ("EXEC", Some(_)) => {
+                                    let commands = queued.take().unwrap_or_default();
+                                    let mut bytes = format!("*{}\r\n", commands.len()).into_bytes();
+                                    for command in commands {
+                                        match reply(&command) {
+                                            Some(reply) => bytes.extend(reply),
+                                            None => return,
+                                        }
+                                    }
+                                    bytes
+                                }
+                                (_, Some(commands)) => {
+                                    commands.push(args);
+                                    b"+QUEUED\r\n".to_vec()
+                                }
+                                (_, None) => match reply(&args) {
+                                    Some(bytes) => bytes,
+                                    None => return,
+                                },
+                            };
+                            if socket.write_all(&bytes).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    /// A RESP2 reply
+    enum Reply {
+        Ok,
+        Int(i64),
+        Bulk(String),
+        Nil,
+        Array(Vec<Reply>),
+        Error(String),
+    }
+
+    impl Reply {
+        fn encode(&self, out: &mut Vec<u8>) {
+            match self {
+                Reply::Ok => out.extend(b"+OK\r\n"),
+                Reply::Int(n) => out.extend(format!(":{}\r\n", n).into_bytes()),
+                Reply::Bulk(s) => out.extend(format!("${}\r\n{}\r\n", s.len(), s).into_bytes()),
+                Reply::Nil => out.extend(b"$-1\r\n"),
+                Reply::Array(items) => {
+                    out.extend(format!("*{}\r\n", items.len()).into_bytes());
+                    for item in items {
+                        item.encode(out);
+                    }
+                }
+                Reply::Error(e) => out.extend(format!("-ERR {}\r\n", e).into_bytes()),
+            }
+        }
+
+        fn bulks(items: impl IntoIterator<Item = String>) -> Self {
+            Reply::Array(items.into_iter().map(Reply::Bulk).collect())
+        }
+    }
+
+    enum Data {
+        Text(String),
+        Hash(HashMap<String, String>),
+        /// Members with their scores, kept sorted by (score, member)
+        Sorted(Vec<(f64, String)>),
+    }
+
+    /// An inclusive or exclusive (`(`) score bound; `-inf`/`+inf` allowed
+    fn bound(value: &str) -> (f64, bool) {
+        let (value, exclusive) = match value.strip_prefix('(') {
+            Some(value) => (value, true),
+            None => (value, false),
+        };
+        let value = match value {
+            "-inf" => f64::NEG_INFINITY,
+            "+inf" | "inf" => f64::INFINITY,
+            value => value.parse().unwrap(),
+        };
+        (value, exclusive)
+    }
+
+    fn above(score: f64, (min, exclusive): (f64, bool)) -> bool {
+        if exclusive {
+            score > min
+        } else {
+            score >= min
+        }
+    }
+
+    fn below(score: f64, (max, exclusive): (f64, bool)) -> bool {
+        if exclusive {
+            score < max
+        } else {
+            score <= max
+        }
+    }
+
+    /// Keys with optional expiry deadlines, expired lazily on access
+    #[derive(Default)]
+    struct Store {
+        keys: HashMap<String, Data>,
+        deadlines: HashMap<String, Instant>,
+    }
+
+    impl Store {
+        fn live(&mut self, key: &str) -> Option<&mut Data> {
+            if self.deadlines.get(key).is_some_and(|deadline| *deadline <= Instant::now()) {
+                self.keys.remove(key);
+                self.deadlines.remove(key);
+            }
+            self.keys.get_mut(key)
+        }
+
+        fn exists(&mut self, key: &str) -> bool {
+            self.live(key).is_some()
+        }
+
+        fn remove(&mut self, key: &str) -> bool {
+            let existed = self.exists(key);
+            self.keys.remove(key);
+            self.deadlines.remove(key);
+            existed
+        }
+
+        fn hash(&mut self, key: &str) -> &mut HashMap<String, String> {
+            if self.live(key).is_none() {
+                self.keys.insert(key.to_string(), Data::Hash(HashMap::new()));
+            }
+            match self.keys.get_mut(key) {
+                Some(Data::Hash(hash)) => hash,
+                _ => panic!("{} is not a hash", key),
+            }
+        }
+
+        fn sorted(&mut self, key: &str) -> &mut Vec<(f64, String)> {
+            if self.live(key).is_none() {
+                self.keys.insert(key.to_string(), Data::Sorted(Vec::new()));
+            }
+            match self.keys.get_mut(key) {
+                Some(Data::Sorted(members)) => members,
+                _ => panic!("{} is not a sorted set", key),
+            }
+        }
+
+        /// A read-only view of a sorted set (empty when missing)
+        fn members(&mut self, key: &str) -> Vec<(f64, String)> {
+            match self.live(key) {
+                Some(Data::Sorted(members)) => members.clone(),
+                _ => Vec::new(),
+            }
+        }
+
+        fn text(&mut self, key: &str) -> Option<String> {
+            match self.live(key) {
+                Some(Data::Text(value)) => Some(value.clone()),
+                _ => None,
+            }
+        }
+
+        fn set_text(&mut self, key: &str, value: String) {
+            self.keys.insert(key.to_string(), Data::Text(value));
+            self.deadlines.remove(key);
+        }
+
+        fn expire(&mut self, key: &str, after: Duration) -> bool {
+            if !self.exists(key) {
+                return false;
+            }
+            self.deadlines.insert(key.to_string(), Instant::now() + after);
+            true
+        }
+
+        fn incr(&mut self, key: &str, by: i64) -> i64 {
+            let value = self.text(key).map_or(0, |v| v.parse::<i64>().unwrap()) + by;
+            let deadline = self.deadlines.get(key).copied();
+            self.set_text(key, value.to_string());
+            if let Some(deadline) = deadline {
+                self.deadlines.insert(key.to_string(), deadline);
+            }
+            value
+        }
+
+        /// Members of a sorted set scored within `min..=max`, lowest first
+        fn by_score(&mut self, key: &str, min: &str, max: &str) -> Vec<(f64, String)> {
+            let (min, max) = (bound(min), bound(max));
+            self.members(key)
+                .into_iter()
+                .filter(|(score, _)| above(*score, min) && below(*score, max))
+                .collect()
+        }
+
+        fn run(&mut self, args: &[String]) -> Reply {
+            let arg = |i: usize| args[i].as_str();
+            let int = |i: usize| args[i].parse::<i64>().unwrap();
+            match args[0].to_ascii_uppercase().as_str() {
+                "PING" => Reply::Bulk("PONG".to_string()),
+                "PUBLISH" => Reply::Int(0),
+                "GET" => self.text(arg(1)).map_or(Reply::Nil, Reply::Bulk),
+                "SET" => {
+                    let options: Vec<String> =
+                        args[3..].iter().map(|a| a.to_ascii_uppercase()).collect();
+                    if options.iter().any(|o| o == "NX") && self.exists(arg(1)) {
+                        return Reply::Nil;
+                    }
+                    self.set_text(arg(1), arg(2).to_string());
+                    if let Some(at) = options.iter().position(|o| o == "EX") {
+                        let seconds = args[3 + at + 1].parse().unwrap();
+                        self.expire(arg(1), Duration::from_secs(seconds));
+                    }
+                    Reply::Ok
+                }
+                "DEL" => Reply::Int(args[1..].iter().filter(|key| self.remove(key)).count() as i64),
+                "EXISTS" => {
+                    Reply::Int(args[1..].iter().filter(|key| self.exists(key)).count() as i64)
+                }
+                "EXPIRE" => {
+                    Reply::Int(self.expire(arg(1), Duration::from_secs(int(2) as u64)) as i64)
+                }
+                "PEXPIRE" => {
+                    Reply::Int(self.expire(arg(1), Duration::from_millis(int(2) as u64)) as i64)
+                }
+                "TTL" => {
+                    if !self.exists(arg(1)) {
+                        return Reply::Int(-2);
+                    }
+                    Reply::Int(self.deadlines.get(arg(1)).map_or(-1, |deadline| {
+                        let left = deadline.saturating_duration_since(Instant::now());
+                        left.as_secs_f64().round() as i64
+                    }))
+                }
+                "INCR" => Reply::Int(self.incr(arg(1), 1)),
+                "INCRBY" => Reply::Int(self.incr(arg(1), int(2))),
+                "HSET" => {
+                    let hash = self.hash(arg(1));
+                    let added = args[2..]
+                        .chunks(2)
+                        .filter(|pair| hash.insert(pair[0].clone(), pair[1].clone()).is_none())
+                        .count();
+                    Reply::Int(added as i64)
+                }
+                "HGET" => match self.live(arg(1)) {
+                    Some(Data::Hash(hash)) => {
+                        hash.get(arg(2)).cloned().map_or(Reply::Nil, Reply::Bulk)
+                    }
+                    _ => Reply::Nil,
+                },
+                "HMGET" => {
+                    let hash = match self.live(arg(1)) {
+                        Some(Data::Hash(hash)) => hash.clone(),
+                        _ => HashMap::new(),
+                    };
+                    Reply::Array(
+                        args[2..]
+                            .iter()
+                            .map(|field| hash.get(field).cloned().map_or(Reply::Nil, Reply::Bulk))
+                            .collect(),
+                    )
+                }
+                "HGETALL" => match self.live(arg(1)) {
+                    Some(Data::Hash(hash)) => {
+                        Reply::bulks(hash.clone().into_iter().flat_map(|(k, v)| [k, v]))
+                    }
+                    _ => Reply::Array(Vec::new()),
+                },
+                "HDEL" => {
+                    let hash = self.hash(arg(1));
+                    let removed = args[2..].iter().filter(|f| hash.remove(*f).is_some()).count();
+                    Reply::Int(removed as i64)
+                }
+                "HINCRBY" => {
+                    let by = int(3);
+                    let hash = self.hash(arg(1));
+                    let value = hash.get(arg(2)).map_or(0, |v| v.parse::<i64>().unwrap()) + by;
+                    hash.insert(arg(2).to_string(), value.to_string());
+                    Reply::Int(value)
+                }
+                "ZADD" => {
+                    let members = self.sorted(arg(1));
+                    let mut added = 0;
+                    for pair in args[2..].chunks(2) {
+                        let score: f64 = pair[0].parse().unwrap();
+                        match members.iter_mut().find(|(_, member)| *member == pair[1]) {
+                            Some(entry) => entry.0 = score,
+                            None => {
+                                members.push((score, pair[1].clone()));
+                                added += 1;
+                            }
+                        }
+                    }
+                    members.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+                    Reply::Int(added)
+                }
+                "ZREM" => {
+                    if !self.exists(arg(1)) {
+                        return Reply::Int(0);
+                    }
+                    let members = self.sorted(arg(1));
+                    let before = members.len();
+                    members.retain(|(_, member)| !args[2..].contains(member));
+                    Reply::Int((before - members.len()) as i64)
+                }
+                "ZCARD" => Reply::Int(self.members(arg(1)).len() as i64),
+                "ZCOUNT" => Reply::Int(self.by_score(arg(1), arg(2), arg(3)).len() as i64),
+                "ZSCORE" => self
+                    .members(arg(1))
+                    .into_iter()
+                    .find(|(_, member)| member == arg(2))
+                    .map_or(Reply::Nil, |(score, _)| Reply::Bulk(score.to_string())),
+                "ZREMRANGEBYSCORE" => {
+                    let doomed = self.by_score(arg(1), arg(2), arg(3));
+                    if doomed.is_empty() {
+                        return Reply::Int(0);
+                    }
+                    self.sorted(arg(1)).retain(|entry| !doomed.contains(entry));
+                    Reply::Int(doomed.len() as i64)
+                }
+                name @ ("ZRANGE" | "ZREVRANGE") => {
+                    let mut members = self.members(arg(1));
+                    if name == "ZREVRANGE" {
+                        members.reverse();
+                    }
+                    let len = members.len() as i64;
+                    let index = |i: i64| if i < 0 { len + i } else { i };
+                    let (start, stop) = (index(int(2)).max(0), index(int(3)).min(len - 1));
+                    let picked = (start..=stop).map(|i| members[i as usize].1.clone());
+                    Reply::bulks(picked.collect::<Vec<_>>())
+                }
+                name @ ("ZRANGEBYSCORE" | "ZREVRANGEBYSCORE") => {
+                    let reverse = name == "ZREVRANGEBYSCORE";
+                    let (min, max) = if reverse { (arg(3), arg(2)) } else { (arg(2), arg(3)) };
+                    let mut members = self.by_score(arg(1), min, max);
+                    if reverse {
+                        members.reverse();
+                    }
+                    let options: Vec<String> =
+                        args[4..].iter().map(|a| a.to_ascii_uppercase()).collect();
+                    if let Some(at) = options.iter().position(|o| o == "LIMIT") {
+                        let (offset, count) = (int(4 + at + 1) as usize, int(4 + at + 2));
+                        let count = if count < 0 { usize::MAX } else { count as usize };
+                        members = members.into_iter().skip(offset).take(count).collect();
+                    }
+                    let with_scores = options.iter().any(|o| o == "WITHSCORES");
+                    Reply::bulks(members.into_iter().flat_map(|(score, member)| {
+                        let score = with_scores.then(|| score.to_string());
+                        std::iter::once(member).chain(score)
+                    }))
+                }
+                "EVAL" if arg(1) == NEXT_SEQUENCE_SCRIPT => {
+                    let sequence = self.incr(arg(3), 1);
+                    let slot = self.incr(arg(4), 1) - 1;
+                    self.expire(arg(4), Duration::from_secs(1));
+                    Reply::Array(vec![Reply::Int(sequence), Reply::Int(slot)])
+                }
+                "EVAL" if arg(1) == RELEASE_IF_OWNED_SCRIPT => {
+                    if self.text(arg(3)).as_deref() == Some(arg(4)) {
+                        Reply::Int(self.remove(arg(3)) as i64)
+                    } else {
+                        Reply::Int(0)
+                    }
+                }
+                other => Reply::Error(format!("unsupported command {}", other)),
+            }
+        }
+    }
+
+    /// A client for an in-memory Redis that starts out with no keys
+    pub(crate) async fn memory() -> RedisClient {
+        let store = Mutex::new(Store::default());
+        let url = serve(move |args| {
+            let mut bytes = Vec::new();
+            store.lock().unwrap().run(args).encode(&mut bytes);
+            Some(bytes)
+        })
+        .await;
+        let settings = RedisSettings {
+            url,
+            ..Default::default()
+        };
+        RedisClient::new(&settings, 16, false).await.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_always_map_to_the_same_shard() {
+        let settings = RedisSettings {
+            url: "redis://node-a:6379, redis://node-b:6379".to_string(),
+            ..Default::default()
+        };
+        let shards = settings.urls().count();
+        assert_eq!(shards, 2);
+
+        for session_id in ["session-a", "session-b", "abc123"] {
+            let shard = shard_index(session_id, shards);
+            assert!(shard < shards);
+            assert!((0..10).all(|_| shard_index(session_id, shards) == shard));
+        }
+        // FNV-1a is fixed, so these hold on every build and restart
+        assert_eq!(shard_index("session-a", shards), 1);
+        assert_eq!(shard_index("session-b", shards), 0);
+        assert_eq!(shard_index("abc123", 3), 0);
+    }
+
+    #[test]
+    fn stored_query_params_keep_duplicates() {
+        let params = HashMap::from([("a".to_string(), vec!["1".to_string(), "2".to_string()])]);
+        let stored = serde_json::to_string(&params).unwrap();
+        assert_eq!(parse_query_params(&stored), params);
+
+        // Captures stored before repeated keys were kept
+        let legacy = parse_query_params(r#"{"a":"1"}"#);
+        assert_eq!(legacy["a"], ["1"]);
+    }
+
+    #[test]
+    fn time_range_bounds_are_inclusive() {
+        // Redis treats a bare score as inclusive; `(` would exclude it
+        let (min, max) = score_bounds(Some(1_700_000_000_000), Some(1_700_000_000_999));
+        assert_eq!(min, "1700000000000000");
+        assert_eq!(max, "1700000000999999");
+
+        // Both ends cover every request captured in that millisecond
+        let (min, max) = score_bounds(Some(42), Some(42));
+        assert_eq!((min.as_str(), max.as_str()), ("42000", "42999"));
+        let (first, last) = (request_score(42, 1), request_score(42, 999));
+        assert!(first >= min.parse().unwrap() && last <= max.parse().unwrap());
+        let (min, max) = score_bounds(None, None);
+        assert_eq!((min.as_str(), max.as_str()), ("-inf", "+inf"));
+    }
+
+    #[test]
+    fn requests_in_the_same_millisecond_get_distinct_ordered_scores() {
+        let scores: Vec<i64> = (0..50).map(|slot| request_score(1_000, slot)).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(request_score(1_000, 999) < request_score(1_001, 0));
+        // Scores stay exact as f64, which is how Redis stores them
+        let score = request_score(4_102_444_800_000, 999); // 2100-01-01
+        assert_eq!(score as f64 as i64, score);
+    }
+
+    #[test]
+    fn pages_split_same_millisecond_requests_in_capture_order() {
+        let request = |sequence: u64| WebhookRequest {
+            request_id: format!("{:x}", 100 - sequence), // member order disagrees
+            timestamp: "2024-01-01T00:00:00.123+00:00".to_string(),
+            sequence,
+            ..Default::default()
+        };
+        // The index as Redis would hold it: highest score first
+        let mut index: Vec<(i64, WebhookRequest)> = (1..=7)
+            .map(|sequence| (request_score(1_704_067_200_123, sequence - 1), request(sequence)))
+            .collect();
+        index.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        let mut listed = Vec::new();
+        for page in index.chunks(3) {
+            let mut page: Vec<WebhookRequest> = page.iter().map(|(_, r)| r.clone()).collect();
+            sort_newest_first(&mut page);
+            listed.extend(page.into_iter().map(|r| r.sequence));
+        }
+        assert_eq!(listed, vec![7, 6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn many_requests_inserted_quickly_stay_in_capture_order() {
+        // 5000 requests over 10 ms, as a burst of concurrent ingests would be;
+        // each millisecond's slots count up from 0
+        let scores: Vec<i64> = (0..5000)
+            .map(|captured: u64| request_score(1_000 + (captured / 500) as i64, captured % 500))
+            .collect();
+        assert!(scores.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // Past the last slot of a millisecond the scores tie rather than
+        // wrap, and a page is still put back in capture order
+        assert_eq!(request_score(1_000, 999), request_score(1_000, 1_500));
+        assert!(request_score(1_000, 1_500) < request_score(1_001, 0));
+        let mut page: Vec<WebhookRequest> = (995..=1005)
+            .map(|sequence| WebhookRequest {
+                request_id: format!("{:x}", 2000 - sequence), // member order disagrees
+                timestamp: "2024-01-01T00:00:00.123+00:00".to_string(),
+                sequence,
+                ..Default::default()
+            })
+            .collect();
+        page.sort_by(|a, b| b.request_id.cmp(&a.request_id));
+        sort_newest_first(&mut page);
+        let sequences: Vec<u64> = page.iter().map(|r| r.sequence).collect();
+        assert_eq!(sequences, (995..=1005).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn export_cursors_exclude_the_last_score_seen() {
+        assert_eq!(score_cursor(None), "+inf");
+        let score = request_score(1_704_067_200_123, 7);
+        assert_eq!(score_cursor(Some(score)), "(1704067200123007");
+    }
+
+    #[test]
+    fn a_single_node_takes_every_session() {
+        assert_eq!(shard_index("session-a", 1), 0);
+        assert_eq!(shard_index("session-a", 0), 0);
+    }
+
+    /// A connection to a fake Redis that drops the connection when the
+    /// first command after the handshake arrives, then serves GET and INCRBY
+    /// on reconnection. Also returns the commands it received.
+    async fn flaky_redis() -> (RetryingConnection, Arc<std::sync::Mutex<Vec<String>>>) {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let dropped = AtomicBool::new(false);
+        let url = fake_redis::serve({
+            let received = received.clone();
+            move |args| {
+                received.lock().unwrap().push(args[0].clone());
+                if !dropped.swap(true, Ordering::SeqCst) {
+                    return None;
+                }
+                let reply: &[u8] = match args[0].as_str() {
+                    "GET" => b"$5\r\nvalue\r\n",
+                    "INCRBY" => b":1\r\n",
+                    _ => b"+OK\r\n",
+                };
+                Some(reply.to_vec())
+            }
+        })
+        .await;
+
+        let client = RedisClient2::open(url).unwrap();
+        let config = ConnectionManagerConfig::new()
+            .set_min_delay(Duration::from_millis(10))
+            .set_max_delay(Duration::from_millis(50))
+            .set_number_of_retries(3);
+        let manager = ConnectionManager::new_with_config(client, config).await.unwrap();
+        (RetryingConnection(manager), received)
+    }
+
+    #[tokio::test]
+    async fn a_read_is_retried_after_the_connection_drops() {
+        let (mut conn, received) = flaky_redis().await;
+
+        let value: String = conn.get("key").await.unwrap();
+
+        assert_eq!(value, "value");
+        assert_eq!(*received.lock().unwrap(), ["GET", "GET"]);
+    }
+
+    #[tokio::test]
+    async fn a_write_is_not_resent_after_the_connection_drops() {
+        let (mut conn, received) = flaky_redis().await;
+
+        let result: redis::RedisResult<u64> = conn.incr("key", 1).await;
+
+        assert!(result.is_err_and(|e| is_connection_error(&e)));
+        assert_eq!(*received.lock().unwrap(), ["INCRBY"]);
+    }
+
+    #[test]
+    fn only_reads_count_as_idempotent() {
+        assert!(is_idempotent(&redis::cmd("GET")));
+        assert!(is_idempotent(redis::cmd("zrangebyscore").arg("key")));
+        assert!(!is_idempotent(&redis::cmd("INCR")));
+        assert!(!is_idempotent(&redis::cmd("PUBLISH")));
+
+        let mut reads = redis::pipe();
+        reads.exists("a").hgetall("b");
+        assert!(reads.cmd_iter().all(is_idempotent));
+        let mut save = redis::pipe();
+        save.hset("a", "f", "v").zadd("b", "m", 1);
+        assert!(!save.cmd_iter().all(is_idempotent));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::config::{ForwardSettings, Settings};
+use crate::forwarder::{self, ForwardOutcome, Outbound};
+use crate::models::DeliveryAttempt;
+use crate::redis_client::RedisClient;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How often the worker looks for retries that are due
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Retries claimed per poll
+const CLAIM_BATCH_SIZE: usize = 32;
+
+/// Everything needed to (re-)deliver a captured request. The headers are
+/// the original ones, before storage filtering and redaction, so a queued
+/// retry holds them in Redis until it is delivered or gives up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardJob {
+    pub session_id: String,
+    pub request_id: String,
+    pub target: String,
+    pub method: String,
+    pub headers: HashMap<String, String>,
+    pub raw_query: String,
+    /// Base64 of the body exactly as received
+    pub body: String,
+    /// Attempts made so far
+    pub attempt: u32,
+}
+
+/// Whether an attempt may succeed if repeated: no response at all, `429`
+/// or a `5xx`
+fn is_retryable(outcome: &ForwardOutcome) -> bool {
+    match outcome.status {
+        None => true,
+        Some(status) => status == 429 || status >= 500,
+    }
+}
+
+/// Delay before the attempt following `attempt`: the base delay, doubled
+/// per attempt made and capped at the maximum
+fn backoff(settings: &ForwardSettings, attempt: u32) -> Duration {
+    let factor = 1u64.checked_shl(attempt.saturating_sub(1)).unwrap_or(u64::MAX);
+    let seconds = settings
+        .retry_base_delay_seconds
+        .saturating_mul(factor)
+        .min(settings.retry_max_delay_seconds);
+    Duration::from_secs(seconds)
+}
+
+/// Make the next attempt for `job`, record it on the request and, if it
+/// failed in a retryable way with attempts left, queue the one after
+pub async fn deliver(
+    client: &Client,
+    redis: &RedisClient,
+    settings: &Settings,
+    mut job: ForwardJob,
+) {
+    let (attempt, response, retry_at) = attempt(client, settings, &mut job).await;
+
+    let recorded = match redis
+        .save_forward_result(&job.session_id, &job.request_id, &attempt, &response)
+        .await
+    {
+        Ok(recorded) => recorded,
+        Err(e) => {
+            warn!(
+                session_id = %job.session_id,
+                request_id = %job.request_id,
+                error = %e,
+                "Failed to record forwarding result"
+            );
+            true
+        }
+    };
+    // Nothing left to deliver once the request has expired
+    let Some(retry_at) = retry_at.filter(|_| recorded) else {
+        return;
+    };
+
+    debug!(
+        session_id = %job.session_id,
+        request_id = %job.request_id,
+        attempt = job.attempt,
+        retry_at = %retry_at,
+        "Scheduling forwarding retry"
+    );
+    if let Err(e) = redis.schedule_forward_retry(&job, retry_at).await {
+        warn!(
+            session_id = %job.session_id,
+            request_id = %job.request_id,
+            error = %e,
+            "Failed to schedule forwarding retry"
+        );
+    }
+}
+
+/// Deliver `job` once, counting the attempt on it. Returns the attempt as it
+/// is recorded, the upstream response to keep as `forward_response` and when
+/// the next retry is due (if one should be made).
+async fn attempt(
+    client: &Client,
+    settings: &Settings,
+    job: &mut ForwardJob,
+) -> (DeliveryAttempt, String, Option<DateTime<Utc>>) {
+    job.attempt += 1;
+    let attempted_at = Utc::now();
+    let outcome = match BASE64.decode(&job.body) {
+        Ok(body) => {
+            let outbound = Outbound {
+                request_id: &job.request_id,
+                method: &job.method,
+                headers: &job.headers,
+                query_string: &job.raw_query,
+            };
+            forwarder::forward(
+                client,
+                &job.target,
+                &outbound,
+                body.into(),
+                settings.server.allow_private_targets,
+            )
+            .await
+        }
+        Err(e) => ForwardOutcome {
+            status: None,
+            response: format!("Forwarding failed: stored body is not valid base64: {}", e),
+        },
+    };
+
+    let forward = &settings.forward;
+    let retry_at = (is_retryable(&outcome) && job.attempt < forward.max_attempts)
+        .then(|| attempted_at + backoff(forward, job.attempt));
+    let attempt = DeliveryAttempt {
+        attempt: job.attempt,
+        attempted_at: attempted_at.to_rfc3339(),
+        status: outcome.status,
+        error: outcome.status.is_none().then(|| outcome.response.clone()),
+        next_attempt_at: retry_at.map(|at| at.to_rfc3339()),
+    };
+    (attempt, outcome.response, retry_at)
+}
+
+/// Drain due retries for the lifetime of the process. Each retry is claimed
+/// by exactly one instance, so several can share the queue.
+pub async fn run(redis: Arc<RedisClient>, client: Client, settings: Arc<Settings>) {
+    info!(max_attempts = settings.forward.max_attempts, "Started forwarding retry worker");
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let jobs = match redis.claim_forward_retries(Utc::now(), CLAIM_BATCH_SIZE).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                warn!(error = %e, "Failed to claim forwarding retries");
+                continue;
+            }
+        };
+        for job in jobs {
+            let redis = redis.clone();
+            let client = client.clone();
+            let settings = settings.clone();
+            tokio::spawn(async move {
+                deliver(&client, &redis, &settings, job).await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn settings(max_attempts: u32) -> Settings {
+        let mut settings = Settings::default();
+        settings.server.allow_private_targets = true;
+        settings.forward = ForwardSettings {
+            max_attempts,
+            retry_base_delay_seconds: 5,
+            retry_max_delay_seconds: 60,
+        };
+        settings
+    }
+
+    fn job(target: String) -> ForwardJob {
+        ForwardJob {
+            session_id: "session".to_string(),
+            request_id: "request".to_string(),
+            target,
+            method: "POST".to_string(),
+            headers: HashMap::new(),
+            raw_query: String::new(),
+            body: BASE64.encode("payload"),
+            attempt: 0,
+        }
+    }
+
+    /// Upstream that answers `503` to the first request and `200 ok` after
+    async fn flaky_upstream() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let response: &[u8] = if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 4\r\n\r\ndown"
+                } else {
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"
+                };
+                let _ = socket.write_all(response).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        format!("http://{}/hook", addr)
+    }
+
+    #[test]
+    fn only_unreachable_throttled_and_server_errors_are_retried() {
+        let outcome = |status| ForwardOutcome {
+            status,
+            response: String::new(),
+        };
+        assert!(is_retryable(&outcome(None)));
+        assert!(is_retryable(&outcome(Some(429))));
+        assert!(is_retryable(&outcome(Some(500))));
+        assert!(is_retryable(&outcome(Some(503))));
+        assert!(!is_retryable(&outcome(Some(200))));
+        assert!(!is_retryable(&outcome(Some(302))));
+        assert!(!is_retryable(&outcome(Some(404))));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let settings = settings(10).forward;
+        let delays: Vec<u64> = (1..=6).map(|n| backoff(&settings, n).as_secs()).collect();
+        assert_eq!(delays, vec![5, 10, 20, 40, 60, 60]);
+        assert_eq!(backoff(&settings, u32::MAX).as_secs(), 60);
+    }
+
+    #[tokio::test]
+    async fn a_failed_attempt_is_retried_until_it_succeeds() {
+        let settings = settings(3);
+        let client = forwarder::build_client().unwrap();
+        let mut job = job(flaky_upstream().await);
+
+        let (first, response, retry_at) = attempt(&client, &settings, &mut job).await;
+        assert_eq!(first.attempt, 1);
+        assert_eq!(first.status, Some(503));
+        assert_eq!(response, "down");
+        let retry_at = retry_at.expect("a 503 is retried");
+        assert_eq!(first.next_attempt_at, Some(retry_at.to_rfc3339()));
+
+        let (second, response, retry_at) = attempt(&client, &settings, &mut job).await;
+        assert_eq!(second.attempt, 2);
+        assert_eq!(second.status, Some(200));
+        assert_eq!(response, "ok");
+        assert!(retry_at.is_none());
+        assert!(second.next_attempt_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn the_last_attempt_is_not_retried() {
+        let settings = settings(1);
+        let client = forwarder::build_client().unwrap();
+        let mut job = job(flaky_upstream().await);
+
+        let (attempt, _, retry_at) = attempt(&client, &settings, &mut job).await;
+        assert_eq!(attempt.status, Some(503));
+        assert!(retry_at.is_none());
+    }
+
+    #[test]
+    fn jobs_survive_the_queue_round_trip() {
+        let original = job("https://example.com/hook".to_string());
+        let queued: ForwardJob =
+            serde_json::from_str(&serde_json::to_string(&original).unwrap()).unwrap();
+        assert_eq!(queued.target, original.target);
+        assert_eq!(BASE64.decode(&queued.body).unwrap(), b"payload");
+    }
+}
@@ -0,0 +1,69 @@
+use crate::config::{SessionIdFormat, SessionSettings};
+use rand::seq::IndexedRandom;
+use uuid::Uuid;
+
+/// Generate a new session ID in the configured format. Short IDs are random,
+/// so callers must check for collisions with existing sessions.
+pub fn generate(settings: &SessionSettings) -> String {
+    match settings.id_format {
+        SessionIdFormat::Uuid => Uuid::now_v7().to_string(),
+        SessionIdFormat::Short => {
+            let alphabet: Vec<char> = settings.short_id_alphabet.chars().collect();
+            let mut rng = rand::rng();
+            (0..settings.short_id_length)
+                .filter_map(|_| alphabet.choose(&mut rng))
+                .collect()
+        }
+    }
+}
+
+/// Whether `id` looks like a session ID this server could have issued.
+/// UUIDs are always accepted so sessions survive a switch to short IDs.
+pub fn is_valid(id: &str, settings: &SessionSettings) -> bool {
+    if Uuid::parse_str(id).is_ok() {
+        return true;
+    }
+    settings.id_format == SessionIdFormat::Short
+        && id.chars().count() == settings.short_id_length
+        && id.chars().all(|c| settings.short_id_alphabet.contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn short_settings() -> SessionSettings {
+        SessionSettings {
+            id_format: SessionIdFormat::Short,
+            short_id_length: 10,
+            short_id_alphabet: "abc123".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn short_ids_use_the_configured_length_and_alphabet() {
+        let settings = short_settings();
+        let id = generate(&settings);
+        assert_eq!(id.len(), 10);
+        assert!(id.chars().all(|c| "abc123".contains(c)), "{}", id);
+        assert!(is_valid(&id, &settings));
+    }
+
+    #[test]
+    fn short_ids_outside_the_format_are_rejected() {
+        let settings = short_settings();
+        assert!(!is_valid("abc123abc", &settings));
+        assert!(!is_valid("abc123abc12", &settings));
+        assert!(!is_valid("abc123abcz", &settings));
+        assert!(!is_valid("abc123abca", &SessionSettings::default()));
+    }
+
+    #[test]
+    fn uuids_stay_valid_in_either_format() {
+        let uuid = generate(&SessionSettings::default());
+        assert!(Uuid::parse_str(&uuid).is_ok());
+        assert!(is_valid(&uuid, &SessionSettings::default()));
+        assert!(is_valid(&uuid, &short_settings()));
+    }
+}
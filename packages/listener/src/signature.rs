@@ -0,0 +1,131 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How far a Stripe-style `t=` timestamp may be from now, in seconds, as
+/// Stripe's own libraries allow; older signatures are treated as replays
+const TIMESTAMP_TOLERANCE_SECONDS: i64 = 300;
+
+/// Compute the hex-encoded SHA-256 digest of a payload
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Compute the hex-encoded HMAC-SHA256 of a payload with the given key
+pub fn hmac_sha256_hex(key: &[u8], data: &[u8]) -> String {
+    // HMAC accepts keys of any length, so this cannot fail
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Compare two byte strings in constant time (for secrets and signatures)
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Verify a provider signature header against the raw body.
+///
+/// Supports GitHub-style `sha256=<hex>` (or bare hex) HMACs over the body and
+/// Stripe-style `t=<ts>,v1=<hex>` HMACs over `{ts}.{body}`, whose timestamp
+/// must be within five minutes of now.
+pub fn verify_webhook_signature(secret: &str, header_value: &str, body: &[u8]) -> bool {
+    verify_webhook_signature_at(secret, header_value, body, chrono::Utc::now().timestamp())
+}
+
+/// `verify_webhook_signature` as checked at `now` (Unix seconds)
+fn verify_webhook_signature_at(secret: &str, header_value: &str, body: &[u8], now: i64) -> bool {
+    let header_value = header_value.trim();
+
+    if header_value.contains("v1=") {
+        let mut timestamp = None;
+        let mut candidates = Vec::new();
+        for part in header_value.split(',') {
+            match part.trim().split_once('=') {
+                Some(("t", ts)) => timestamp = Some(ts),
+                Some(("v1", sig)) => candidates.push(sig),
+                _ => {}
+            }
+        }
+        let Some(timestamp) = timestamp else {
+            return false;
+        };
+        let fresh = timestamp
+            .parse::<i64>()
+            .is_ok_and(|ts| (now - ts).abs() <= TIMESTAMP_TOLERANCE_SECONDS);
+        if !fresh {
+            return false;
+        }
+
+        let mut payload = Vec::with_capacity(timestamp.len() + 1 + body.len());
+        payload.extend_from_slice(timestamp.as_bytes());
+        payload.push(b'.');
+        payload.extend_from_slice(body);
+        let expected = hmac_sha256_hex(secret.as_bytes(), &payload);
+
+        return candidates
+            .iter()
+            .any(|sig| constant_time_eq(sig.to_ascii_lowercase().as_bytes(), expected.as_bytes()));
+    }
+
+    let provided = header_value
+        .strip_prefix("sha256=")
+        .unwrap_or(header_value)
+        .to_ascii_lowercase();
+    let expected = hmac_sha256_hex(secret.as_bytes(), body);
+    constant_time_eq(provided.as_bytes(), expected.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "whsec_test";
+    const BODY: &[u8] = br#"{"id":"evt_1"}"#;
+    const NOW: i64 = 1_700_000_000;
+
+    fn stripe_header(timestamp: i64) -> String {
+        let payload = format!("{}.{}", timestamp, std::str::from_utf8(BODY).unwrap());
+        let signature = hmac_sha256_hex(SECRET.as_bytes(), payload.as_bytes());
+        format!("t={},v1=deadbeef,v1={}", timestamp, signature)
+    }
+
+    #[test]
+    fn github_signatures_are_checked_over_the_body() {
+        let signature = hmac_sha256_hex(SECRET.as_bytes(), BODY);
+        assert!(verify_webhook_signature(SECRET, &format!("sha256={}", signature), BODY));
+        assert!(verify_webhook_signature(SECRET, &signature.to_uppercase(), BODY));
+        assert!(!verify_webhook_signature(SECRET, &format!("sha256={}", signature), b"{}"));
+        assert!(!verify_webhook_signature("other", &signature, BODY));
+    }
+
+    #[test]
+    fn stripe_signatures_are_checked_over_the_timestamp_and_body() {
+        let header = stripe_header(NOW);
+        assert!(verify_webhook_signature_at(SECRET, &header, BODY, NOW));
+        assert!(!verify_webhook_signature_at(SECRET, &header, b"{}", NOW));
+        assert!(!verify_webhook_signature_at("other", &header, BODY, NOW));
+
+        // The signed timestamp can't be swapped for a fresher one
+        let forged = header.replacen(&NOW.to_string(), &(NOW + 1).to_string(), 1);
+        assert!(!verify_webhook_signature_at(SECRET, &forged, BODY, NOW));
+        let missing = header.replacen(&format!("t={},", NOW), "", 1);
+        assert!(!verify_webhook_signature_at(SECRET, &missing, BODY, NOW));
+    }
+
+    #[test]
+    fn stale_stripe_timestamps_are_rejected() {
+        for skew in [-300, 0, 300] {
+            assert!(verify_webhook_signature_at(SECRET, &stripe_header(NOW + skew), BODY, NOW));
+        }
+        for skew in [-301, 301, -86_400] {
+            assert!(!verify_webhook_signature_at(SECRET, &stripe_header(NOW + skew), BODY, NOW));
+        }
+        let header = stripe_header(NOW).replacen(&NOW.to_string(), "soon", 1);
+        assert!(!verify_webhook_signature_at(SECRET, &header, BODY, NOW));
+    }
+}
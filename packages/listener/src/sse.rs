@@ -1,13 +1,19 @@
-use crate::models::WebhookRequest;
+use crate::body;
+use crate::config::LagStrategy;
+use crate::metrics::SseConnectionGuard;
+use crate::models::{BodyEncoding, BodyFilter, WebhookRequest};
+use crate::redis_client::RedisClient;
 use actix_web::web::Bytes;
 use chrono::Utc;
 use futures::{Stream, StreamExt};
 use serde_json::json;
+use std::collections::HashSet;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio::time::interval;
+use tokio::time::{interval, Interval};
 use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
 use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use tracing::{debug, info, warn};
@@ -15,50 +21,100 @@ use tracing::{debug, info, warn};
 /// Ping interval for SSE keep-alive (30 seconds)
 const PING_INTERVAL: Duration = Duration::from_secs(30);
 
+/// How often the session is checked for expiry (10 seconds)
+const LIFECYCLE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Reconnect delay suggested to clients (via SSE `retry:`) when the server
+/// ends a stream on purpose (shutdown or maximum lifetime)
+const RECONNECT_RETRY_MS: u64 = 1000;
+
+/// Per-connection stream behavior
+pub struct SseOptions {
+    /// Lead time before expiry at which `session_expiring` is sent
+    pub expiry_warning: Duration,
+    pub lag_strategy: LagStrategy,
+    /// Only forward requests whose JSON body matches (pings are unaffected)
+    pub filter: Option<BodyFilter>,
+    /// Name request events after their content category (`request.json`, ...)
+    /// instead of sending every request as a plain `request` event
+    pub typed_events: bool,
+    /// Fires when the server begins shutting down
+    pub shutdown: broadcast::Receiver<()>,
+    /// End the stream with a `reconnect` event after this long
+    pub max_lifetime: Option<Duration>,
+}
+
+/// Events produced by the combined stream; `Final` ends the stream once sent
+enum SseEvent {
+    Message(Bytes),
+    Final(Bytes),
+}
 /// SSE stream for real-time webhook notifications
 pub struct SseStream {
-    event_stream:
-        Pin<Box<dyn Stream<Item = Result<Bytes, actix_web::Error>> + Send + Sync + 'static>>,
+    event_stream: Pin<Box<dyn Stream<Item = Bytes> + Send + 'static>>,
+    /// Keeps the connected-clients gauge accurate until the client disconnects
+    _connection_guard: SseConnectionGuard,
 }
 
 impl SseStream {
     /// Create a new SSE stream with an already-initialized receiver
-    pub fn new(receiver: broadcast::Receiver<WebhookRequest>, session_id: String) -> Self {
+    ///
+    /// `replay` holds stored requests the client missed (from `Last-Event-ID`),
+    /// sent oldest first before switching to the live broadcast channel.
+    pub fn new(
+        receiver: broadcast::Receiver<WebhookRequest>,
+        session_id: String,
+        replay: Vec<WebhookRequest>,
+        connection_guard: SseConnectionGuard,
+        redis: Arc<RedisClient>,
+        options: SseOptions,
+    ) -> Self {
+        let SseOptions {
+            expiry_warning,
+            lag_strategy,
+            filter,
+            typed_events,
+            shutdown,
+            max_lifetime,
+        } = options;
+
         info!(
             session_id = %session_id,
             receiver_count = receiver.len(),
+            replay_count = replay.len(),
             "Creating new SSE stream"
         );
 
+        let (replay, replayed) = replay_events(replay, filter.as_ref(), typed_events);
+        let replay_stream = futures::stream::iter(replay);
+        let replayed = Arc::new(replayed);
+
         // Stream of webhook requests from broadcast channel
         let request_stream = BroadcastStream::new(receiver).filter_map({
             let session_id = session_id.clone();
+            let redis = redis.clone();
             move |result| {
                 let session_id = session_id.clone();
+                let replayed = replayed.clone();
+                let filter = filter.clone();
+                let redis = redis.clone();
                 async move {
-                    match result {
-                        Ok(request) => {
-                            info!(
-                                session_id = %session_id,
-                                request_id = %request.request_id,
-                                method = %request.method,
-                                "Broadcast request received, sending via SSE"
-                            );
-                            let data = serde_json::to_string(&request).unwrap_or_default();
-                            Some(Ok(Bytes::from(format!(
-                                "event: request\ndata: {}\n\n",
-                                data
-                            ))))
-                        }
-                        Err(BroadcastStreamRecvError::Lagged(count)) => {
+                    if let Err(BroadcastStreamRecvError::Lagged(count)) = &result {
+                        if let Err(e) = redis.record_stream_lag(&session_id, *count).await {
                             warn!(
                                 session_id = %session_id,
-                                lagged = count,
-                                "SSE receiver lagged, messages dropped"
+                                error = %e,
+                                "Failed to record SSE lag"
                             );
-                            None
                         }
                     }
+                    let live = LiveOptions {
+                        replayed: &replayed,
+                        filter: filter.as_ref(),
+                        typed_events,
+                        lag_strategy,
+                    };
+                    live_event(result, &live, &session_id)
                 }
             }
         });
@@ -68,7 +124,7 @@ impl SseStream {
             move |_| {
                 let ping_data = json!({ "timestamp": Utc::now().to_rfc3339() });
                 debug!("Sending SSE ping");
-                Ok(Bytes::from(format!(
+                SseEvent::Message(Bytes::from(format!(
                     "event: ping\ndata: {}\n\n",
                     ping_data
                 )))
@@ -78,25 +134,486 @@ impl SseStream {
         // Initial ping once
         let initial_ping = futures::stream::once(async {
             let ping_data = json!({ "timestamp": Utc::now().to_rfc3339() });
-            Ok(Bytes::from(format!(
+            SseEvent::Message(Bytes::from(format!(
                 "event: ping\ndata: {}\n\n",
                 ping_data
             )))
         });
 
-        // Combine: initial ping -> then requests and pings interleaved
-        let event_stream = initial_ping.chain(futures::stream::select(request_stream, ping_stream));
+        // Session expiry warning and closure notifications
+        let lifecycle_stream = lifecycle_events(redis, session_id.clone(), expiry_warning);
+
+        // Tell the client to reconnect (to this or a replacement instance)
+        // instead of just dropping the connection when the server stops
+        let shutdown_stream = BroadcastStream::new(shutdown).take(1).map({
+            let session_id = session_id.clone();
+            move |_| {
+                info!(session_id = %session_id, "Server shutting down, ending SSE stream");
+                let data = json!({
+                    "reconnect_after_ms": RECONNECT_RETRY_MS,
+                    "timestamp": Utc::now().to_rfc3339(),
+                });
+                SseEvent::Final(Bytes::from(format!(
+                    "retry: {}\nevent: server_shutdown\ndata: {}\n\n",
+                    RECONNECT_RETRY_MS, data
+                )))
+            }
+        });
+
+        // Cap the connection's lifetime so clients that stopped reading
+        // (but never disconnected) are eventually dropped
+        let lifetime_stream = futures::stream::once({
+            let session_id = session_id.clone();
+            async move {
+                match max_lifetime {
+                    Some(lifetime) => tokio::time::sleep(lifetime).await,
+                    None => futures::future::pending::<()>().await,
+                }
+                debug!(session_id = %session_id, "SSE stream reached its maximum lifetime");
+                let data = json!({
+                    "reason": "max_lifetime",
+                    "reconnect_after_ms": RECONNECT_RETRY_MS,
+                    "timestamp": Utc::now().to_rfc3339(),
+                });
+                SseEvent::Final(Bytes::from(format!(
+                    "retry: {}\nevent: reconnect\ndata: {}\n\n",
+                    RECONNECT_RETRY_MS, data
+                )))
+            }
+        });
+
+        // Combine: initial ping -> replayed requests -> then requests, pings,
+        // lifecycle, shutdown and lifetime events interleaved, ending right
+        // after `session_closed`, `server_shutdown` or `reconnect`
+        let live_stream = futures::stream::select(
+            futures::stream::select(request_stream, ping_stream),
+            futures::stream::select(
+                lifecycle_stream,
+                futures::stream::select(shutdown_stream, lifetime_stream),
+            ),
+        );
+        let event_stream = initial_ping
+            .chain(replay_stream)
+            .chain(live_stream)
+            .scan(false, |finished, event| {
+                let item = if *finished {
+                    None
+                } else {
+                    match event {
+                        SseEvent::Message(bytes) => Some(bytes),
+                        SseEvent::Final(bytes) => {
+                            *finished = true;
+                            Some(bytes)
+                        }
+                    }
+                };
+                futures::future::ready(item)
+            });
 
         Self {
             event_stream: Box::pin(event_stream),
+            _connection_guard: connection_guard,
+        }
+    }
+}
+
+/// Events for the replayed requests (oldest first) that pass the body
+/// filter, plus the IDs of every replayed request. The receiver was
+/// subscribed before the replay was loaded, so live requests with these IDs
+/// were already covered.
+fn replay_events(
+    replay: Vec<WebhookRequest>,
+    filter: Option<&BodyFilter>,
+    typed_events: bool,
+) -> (Vec<SseEvent>, HashSet<String>) {
+    let replayed = replay.iter().map(|r| r.request_id.clone()).collect();
+    let events = replay
+        .iter()
+        // Replayed requests go through the same body filter as live ones
+        .filter(|request| filter.is_none_or(|f| f.matches(request)))
+        .map(|request| request_event(request, typed_events))
+        .collect();
+    (events, replayed)
+}
+
+/// How live broadcast items are turned into events
+struct LiveOptions<'a> {
+    replayed: &'a HashSet<String>,
+    filter: Option<&'a BodyFilter>,
+    typed_events: bool,
+    lag_strategy: LagStrategy,
+}
+
+/// The event for one broadcast item. Requests are matched against the
+/// replay by ID rather than by order, since concurrent ingests (and other
+/// instances) can save requests out of ID order.
+fn live_event(
+    result: Result<WebhookRequest, BroadcastStreamRecvError>,
+    options: &LiveOptions,
+    session_id: &str,
+) -> Option<SseEvent> {
+    match result {
+        Ok(request) => {
+            if options.replayed.contains(&request.request_id) {
+                return None;
+            }
+            if options.filter.is_some_and(|f| !f.matches(&request)) {
+                return None;
+            }
+            info!(
+                session_id = %session_id,
+                request_id = %request.request_id,
+                method = %request.method,
+                "Broadcast request received, sending via SSE"
+            );
+            Some(request_event(&request, options.typed_events))
+        }
+        Err(BroadcastStreamRecvError::Lagged(count)) => {
+            warn!(
+                session_id = %session_id,
+                lagged = count,
+                strategy = ?options.lag_strategy,
+                "SSE receiver lagged, messages dropped"
+            );
+            match options.lag_strategy {
+                LagStrategy::Drop => None,
+                // End the stream; the client reconnects and replays what it
+                // missed via Last-Event-ID
+                LagStrategy::Close => {
+                    let data = json!({ "reason": "lagged", "lagged": count });
+                    Some(SseEvent::Final(Bytes::from(format!(
+                        "event: error\ndata: {}\n\n",
+                        data
+                    ))))
+                }
+            }
+        }
+    }
+}
+
+/// Coarse kind of a request body, used to name SSE events so browsers can
+/// dispatch each kind to its own renderer with `addEventListener`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCategory {
+    Json,
+    Xml,
+    Form,
+    Text,
+    Binary,
+    Other,
+}
+
+impl ContentCategory {
+    /// Classify a request by its `Content-Type` (or the type detected from
+    /// its body), treating undecodable text as binary
+    pub fn classify(request: &WebhookRequest) -> Self {
+        let media_type = request
+            .detected_content_type
+            .as_deref()
+            .unwrap_or(&request.content_type)
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+
+        if body::is_json_content_type(&media_type) {
+            Self::Json
+        } else if media_type.ends_with("/xml") || media_type.ends_with("+xml") {
+            Self::Xml
+        } else if media_type == "application/x-www-form-urlencoded"
+            || media_type == "multipart/form-data"
+        {
+            Self::Form
+        } else if request.body_encoding == BodyEncoding::Base64
+            || media_type == "application/octet-stream"
+            || ["image/", "audio/", "video/"].iter().any(|p| media_type.starts_with(p))
+        {
+            Self::Binary
+        } else if media_type.starts_with("text/") {
+            Self::Text
+        } else {
+            Self::Other
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Xml => "xml",
+            Self::Form => "form",
+            Self::Text => "text",
+            Self::Binary => "binary",
+            Self::Other => "other",
         }
     }
+
+    /// SSE event name; uncategorized requests keep the generic `request`
+    fn event_name(&self) -> &'static str {
+        match self {
+            Self::Json => "request.json",
+            Self::Xml => "request.xml",
+            Self::Form => "request.form",
+            Self::Text => "request.text",
+            Self::Binary => "request.binary",
+            Self::Other => "request",
+        }
+    }
+}
+
+/// Serialize a request for live delivery (SSE `data:` and WebSocket frames),
+/// adding its `content_category`
+pub fn request_payload(request: &WebhookRequest) -> String {
+    let mut data = serde_json::to_value(request).unwrap_or_default();
+    if let Some(object) = data.as_object_mut() {
+        object.insert(
+            "content_category".to_string(),
+            ContentCategory::classify(request).as_str().into(),
+        );
+    }
+    data.to_string()
+}
+
+/// Format a request as an SSE request event, tagged with its ID for `Last-Event-ID`
+fn request_event(request: &WebhookRequest, typed_events: bool) -> SseEvent {
+    let event = if typed_events {
+        ContentCategory::classify(request).event_name()
+    } else {
+        "request"
+    };
+    SseEvent::Message(Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        request.request_id,
+        event,
+        request_payload(request)
+    )))
+}
+
+/// Polling state for session lifecycle events
+struct LifecycleState {
+    redis: Arc<RedisClient>,
+    session_id: String,
+    interval: Interval,
+    expiry_warning: Duration,
+    warned: bool,
+    closed: bool,
+}
+
+/// Poll the session TTL, emitting `session_expiring` once the warning lead
+/// time is reached and `session_closed` (then ending) once it is gone
+fn lifecycle_events(
+    redis: Arc<RedisClient>,
+    session_id: String,
+    expiry_warning: Duration,
+) -> impl Stream<Item = SseEvent> + Send + 'static {
+    let state = LifecycleState {
+        redis,
+        session_id,
+        interval: interval(LIFECYCLE_POLL_INTERVAL),
+        expiry_warning,
+        warned: false,
+        closed: false,
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        if state.closed {
+            return None;
+        }
+
+        loop {
+            state.interval.tick().await;
+
+            let ttl = match state.redis.get_session_ttl(&state.session_id).await {
+                Ok(ttl) => ttl,
+                Err(e) => {
+                    warn!(
+                        session_id = %state.session_id,
+                        error = %e,
+                        "Failed to check session TTL for SSE lifecycle"
+                    );
+                    continue;
+                }
+            };
+
+            match ttl {
+                None => {
+                    info!(session_id = %state.session_id, "Session closed, ending SSE stream");
+                    state.closed = true;
+                    let data = json!({
+                        "session_id": state.session_id,
+                        "timestamp": Utc::now().to_rfc3339(),
+                    });
+                    let event = Bytes::from(format!("event: session_closed\ndata: {}\n\n", data));
+                    return Some((SseEvent::Final(event), state));
+                }
+                Some(ttl) if ttl >= 0 && (ttl as u64) <= state.expiry_warning.as_secs() => {
+                    if !state.warned {
+                        state.warned = true;
+                        let expires_at = Utc::now() + chrono::Duration::seconds(ttl);
+                        let data = json!({
+                            "session_id": state.session_id,
+                            "expires_in_seconds": ttl,
+                            "expires_at": expires_at.to_rfc3339(),
+                        });
+                        let event =
+                            Bytes::from(format!("event: session_expiring\ndata: {}\n\n", data));
+                        return Some((SseEvent::Message(event), state));
+                    }
+                }
+                // Re-arm the warning if the session was extended
+                Some(_) => state.warned = false,
+            }
+        }
+    })
 }
 
 impl Stream for SseStream {
     type Item = Result<Bytes, actix_web::Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.event_stream.as_mut().poll_next(cx)
+        self.event_stream.as_mut().poll_next(cx).map(|event| event.map(Ok))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StreamRequestsQuery;
+    use tokio::sync::broadcast;
+
+    fn request(id: &str, body: serde_json::Value) -> WebhookRequest {
+        WebhookRequest {
+            request_id: id.to_string(),
+            method: "POST".to_string(),
+            body_json: Some(body),
+            ..Default::default()
+        }
+    }
+
+    fn type_filter(value: &str) -> Option<BodyFilter> {
+        StreamRequestsQuery {
+            filter_field: Some("type".to_string()),
+            filter_value: Some(value.to_string()),
+            typed_events: false,
+        }
+        .body_filter()
+    }
+
+    fn text(event: &SseEvent) -> String {
+        match event {
+            SseEvent::Message(bytes) | SseEvent::Final(bytes) => {
+                String::from_utf8(bytes.to_vec()).unwrap()
+            }
+        }
+    }
+
+    /// Events for everything sent on a channel, as a connected client sees it
+    async fn live_events(
+        receiver: broadcast::Receiver<WebhookRequest>,
+        live: LiveOptions<'_>,
+    ) -> Vec<SseEvent> {
+        BroadcastStream::new(receiver)
+            .filter_map(|result| futures::future::ready(live_event(result, &live, "sess")))
+            .collect()
+            .await
+    }
+
+    #[test]
+    fn the_replay_is_filtered_but_every_id_is_remembered() {
+        let replay = vec![
+            request("a", json!({"type": "paid"})),
+            request("b", json!({"type": "refunded"})),
+        ];
+
+        let (events, replayed) = replay_events(replay, type_filter("paid").as_ref(), false);
+
+        assert_eq!(events.len(), 1);
+        assert!(text(&events[0]).starts_with("id: a\nevent: request\n"));
+        assert_eq!(replayed, HashSet::from(["a".to_string(), "b".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn live_requests_skip_only_what_the_replay_covered() {
+        let (sender, receiver) = broadcast::channel(16);
+        let (_, replayed) =
+            replay_events(vec![request("b", json!({})), request("c", json!({}))], None, false);
+
+        // "a" sorts before the replay but was saved after it was loaded
+        for id in ["b", "a", "c", "d"] {
+            sender.send(request(id, json!({}))).unwrap();
+        }
+        drop(sender);
+
+        let live = LiveOptions {
+            replayed: &replayed,
+            filter: None,
+            typed_events: false,
+            lag_strategy: LagStrategy::Drop,
+        };
+        let events = live_events(receiver, live).await;
+
+        assert_eq!(events.len(), 2);
+        assert!(text(&events[0]).starts_with("id: a\n"));
+        assert!(text(&events[1]).starts_with("id: d\n"));
+    }
+
+    /// Send five requests into a channel that holds two, then see what a
+    /// client that wasn't reading gets
+    async fn overflowed(lag_strategy: LagStrategy) -> Vec<SseEvent> {
+        let (sender, receiver) = broadcast::channel(2);
+        for id in ["a", "b", "c", "d", "e"] {
+            sender.send(request(id, json!({}))).unwrap();
+        }
+        drop(sender);
+
+        let live = LiveOptions {
+            replayed: &HashSet::new(),
+            filter: None,
+            typed_events: false,
+            lag_strategy,
+        };
+        live_events(receiver, live).await
+    }
+
+    #[tokio::test]
+    async fn dropping_lagged_messages_carries_on_with_the_rest() {
+        let events = overflowed(LagStrategy::Drop).await;
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], SseEvent::Message(_)));
+        assert!(text(&events[0]).starts_with("id: d\n"));
+        assert!(text(&events[1]).starts_with("id: e\n"));
+    }
+
+    #[tokio::test]
+    async fn closing_on_lag_sends_a_final_error_first() {
+        let events = overflowed(LagStrategy::Close).await;
+
+        // The stream ends after the `Final` event, so what follows is unsent
+        assert!(matches!(events[0], SseEvent::Final(_)));
+        let event = text(&events[0]);
+        let data = event.strip_prefix("event: error\ndata: ").unwrap();
+        let data: serde_json::Value = serde_json::from_str(data.trim_end()).unwrap();
+        assert_eq!(data, json!({ "reason": "lagged", "lagged": 3 }));
+    }
+
+    #[tokio::test]
+    async fn live_requests_go_through_the_body_filter() {
+        let (sender, receiver) = broadcast::channel(16);
+        sender.send(request("a", json!({"type": "refunded"}))).unwrap();
+        sender.send(request("b", json!({"type": "paid"}))).unwrap();
+        drop(sender);
+
+        let filter = type_filter("paid");
+        let live = LiveOptions {
+            replayed: &HashSet::new(),
+            filter: filter.as_ref(),
+            typed_events: false,
+            lag_strategy: LagStrategy::Drop,
+        };
+        let events = live_events(receiver, live).await;
+
+        assert_eq!(events.len(), 1);
+        assert!(text(&events[0]).starts_with("id: b\n"));
     }
 }
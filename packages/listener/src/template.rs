@@ -0,0 +1,87 @@
+/// Placeholders available in a session's `response_template`
+pub const TEMPLATE_VARIABLES: &[&str] = &["request_id", "session_id", "timestamp"];
+
+/// Values substituted into a response template
+pub struct TemplateValues<'a> {
+    pub request_id: &'a str,
+    pub session_id: &'a str,
+    pub timestamp: &'a str,
+}
+
+/// Check that every `{{` is closed and names a known placeholder
+pub fn validate_template(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "Unclosed placeholder in response_template".to_string())?;
+        let name = after[..end].trim();
+        if !TEMPLATE_VARIABLES.contains(&name) {
+            return Err(format!(
+                "Unknown placeholder in response_template: {{{{{}}}}} (expected one of {})",
+                name,
+                TEMPLATE_VARIABLES.join(", ")
+            ));
+        }
+        rest = &after[end + 2..];
+    }
+    Ok(())
+}
+
+/// Substitute placeholders; the template is validated at session creation
+pub fn render_template(template: &str, values: &TemplateValues) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        match after[..end].trim() {
+            "request_id" => rendered.push_str(values.request_id),
+            "session_id" => rendered.push_str(values.session_id),
+            "timestamp" => rendered.push_str(values.timestamp),
+            _ => rendered.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: TemplateValues<'static> = TemplateValues {
+        request_id: "req-1",
+        session_id: "abc",
+        timestamp: "2024-01-01T00:00:00+00:00",
+    };
+
+    #[test]
+    fn every_placeholder_is_substituted() {
+        let template = "{{request_id}}|{{ session_id }}|{{timestamp}}|{{request_id}}";
+        assert!(validate_template(template).is_ok());
+        assert_eq!(
+            render_template(template, &VALUES),
+            "req-1|abc|2024-01-01T00:00:00+00:00|req-1"
+        );
+    }
+
+    #[test]
+    fn text_without_placeholders_is_unchanged() {
+        let template = r#"{"ok": true, "braces": "{ } }}"}"#;
+        assert!(validate_template(template).is_ok());
+        assert_eq!(render_template(template, &VALUES), template);
+    }
+
+    #[test]
+    fn malformed_templates_are_rejected() {
+        assert!(validate_template("{{request_id").unwrap_err().contains("Unclosed"));
+        let unknown = validate_template("{{ secret }}").unwrap_err();
+        assert!(unknown.contains("{{secret}}"), "{}", unknown);
+    }
+}
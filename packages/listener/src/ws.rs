@@ -0,0 +1,99 @@
+use crate::models::WebhookRequest;
+use crate::redis_client::RedisClient;
+use crate::sse;
+use actix_ws::{CloseReason, Message, MessageStream, Session};
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+
+/// Ping interval for WebSocket keep-alive (matches the SSE ping cadence)
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Close the socket if the client hasn't answered pings for this long
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Drive a WebSocket connection: push broadcast requests as JSON text frames
+/// (same payload as the SSE `data:` field) until either side goes away.
+pub async fn run(
+    mut session: Session,
+    mut messages: MessageStream,
+    mut receiver: broadcast::Receiver<WebhookRequest>,
+    redis: Arc<RedisClient>,
+    session_id: String,
+) {
+    info!(session_id = %session_id, "WebSocket client connected");
+
+    let mut ping_interval = interval(PING_INTERVAL);
+    let mut last_heartbeat = Instant::now();
+    let mut close_reason: Option<CloseReason> = None;
+
+    loop {
+        tokio::select! {
+            result = receiver.recv() => match result {
+                Ok(request) => {
+                    let data = sse::request_payload(&request);
+                    if session.text(data).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(count)) => {
+                    warn!(
+                        session_id = %session_id,
+                        lagged = count,
+                        "WebSocket receiver lagged, messages dropped"
+                    );
+                    if let Err(e) = redis.record_stream_lag(&session_id, count).await {
+                        warn!(
+                            session_id = %session_id,
+                            error = %e,
+                            "Failed to record WebSocket lag"
+                        );
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = messages.next() => match message {
+                Some(Ok(Message::Ping(bytes))) => {
+                    last_heartbeat = Instant::now();
+                    if session.pong(&bytes).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Pong(_))) => {
+                    last_heartbeat = Instant::now();
+                }
+                Some(Ok(Message::Close(reason))) => {
+                    debug!(session_id = %session_id, ?reason, "WebSocket client sent close");
+                    close_reason = reason;
+                    break;
+                }
+                // Clients have nothing to send; ignore text/binary frames
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    warn!(session_id = %session_id, error = %e, "WebSocket protocol error");
+                    break;
+                }
+                None => break,
+            },
+            _ = ping_interval.tick() => {
+                if last_heartbeat.elapsed() > CLIENT_TIMEOUT {
+                    info!(session_id = %session_id, "WebSocket client heartbeat timed out");
+                    break;
+                }
+                if session.ping(b"").await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let _ = session.close(close_reason).await;
+
+    // Drop our subscription first so the channel is removed if nobody else listens
+    drop(receiver);
+    redis.cleanup_sse_channel(&session_id).await;
+    info!(session_id = %session_id, "WebSocket client disconnected");
+}